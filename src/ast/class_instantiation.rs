@@ -60,10 +60,14 @@ impl Codegen for ClassInstantiation {
     let generic_variant_suffix =
       GenericContext::generic_variant_suffix_from_types(&stringified_types);
 
+    let class_name = context
+      .resolve_namespaced_name(&self.class_name)
+      .unwrap_or_else(|| self.class_name.clone());
+
     write!(
       f,
-      "new {}{generic_variant_suffix} in {}",
-      self.class_name, self.lifetime
+      "new {class_name}{generic_variant_suffix} in {}",
+      self.lifetime
     )
   }
 }
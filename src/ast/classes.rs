@@ -2,6 +2,7 @@ use std::collections::HashSet;
 use std::rc::Rc;
 
 use super::codegen::context::Context;
+use super::codegen::span_markers::write_span_marker;
 use super::visitor::Visited;
 use super::*;
 
@@ -37,7 +38,7 @@ impl Visited for ClassDeclaration {
 }
 
 impl Codegen for ClassDeclaration {
-  fn emit(&self, context: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
+  fn emit(&self, _context: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
     let has_generic_context = self.context.borrow().generic_context.is_some();
     if has_generic_context {
       let mut variants = Vec::new();
@@ -58,7 +59,7 @@ impl Codegen for ClassDeclaration {
         emit_class(self, &self.context.borrow(), f, &variant)?;
       }
     } else {
-      emit_class(self, &context, f, "")?;
+      emit_class(self, &self.context.borrow(), f, "")?;
     }
 
     Ok(())
@@ -70,6 +71,8 @@ fn emit_class(
 ) -> Result<(), std::io::Error> {
   use std::io::Write as IoWrite;
 
+  write_span_marker(f, this.span_name)?;
+
   let generic_variant_suffix_prefix = match generic_variant_suffix.is_empty() {
     true => "",
     false => "_"
@@ -90,10 +93,18 @@ fn emit_class(
   }
 
   if let Some(parent_class_name) = &this.parent_class_name {
+    let parent_class_name = context
+      .resolve_namespaced_name(parent_class_name)
+      .unwrap_or_else(|| parent_class_name.clone());
+
     write!(f, " in {parent_class_name}")?;
   }
 
   if let Some(extended_class_name) = &this.extended_class_name {
+    let extended_class_name = context
+      .resolve_namespaced_name(extended_class_name)
+      .unwrap_or_else(|| extended_class_name.clone());
+
     write!(f, " extends {extended_class_name}")?;
   }
 
@@ -155,7 +166,11 @@ pub enum ClassBodyStatement {
     is_editable: bool
   },
   DefaultValue(VariableAssignment),
-  Hint(VariableAssignment)
+  Hint(VariableAssignment),
+
+  /// A member that could not be parsed, the syntax error was recorded by the
+  /// parser.
+  Error
 }
 
 impl Codegen for ClassBodyStatement {
@@ -205,6 +220,7 @@ impl Codegen for ClassBodyStatement {
         x.emit(context, f)?;
         writeln!(f, ";")?
       }
+      ClassBodyStatement::Error => {}
     };
 
     Ok(())
@@ -233,6 +249,7 @@ impl visitor::Visited for ClassBodyStatement {
       } => property_declaration.accept(visitor),
       ClassBodyStatement::DefaultValue(_) => {}
       ClassBodyStatement::Hint(_) => {}
+      ClassBodyStatement::Error => {}
     }
   }
 }
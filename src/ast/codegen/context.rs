@@ -1,9 +1,10 @@
 use std::borrow::Borrow;
 use std::cell::{Ref, RefCell};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::rc::Rc;
 
-use crate::ast::TypedIdentifier;
+use crate::ast::{get_symbol_path, TypedIdentifier};
+use crate::utils::stable_hash;
 
 #[derive(Debug)]
 pub struct Context {
@@ -24,6 +25,9 @@ pub struct Context {
   pub is_library: bool,
   pub mangled_accessor: Option<String>,
 
+  /// The name of the class a class context extends, if any
+  pub extended_class_name: Option<String>,
+
   /// Stores the variable declarations in the context. To be able to emit them
   /// at the start of the functions/classes/structs
   pub variable_declarations: Vec<Rc<TypedIdentifier>>,
@@ -40,6 +44,15 @@ pub struct Context {
 
   pub local_parameters_inference: HashMap<String, String>,
 
+  /// The names the `import` statements of the context bring in, along with
+  /// the full path of the symbol they refer to.
+  pub imports: HashMap<String, String>,
+
+  /// Stores for the full path of the classes, structs and functions declared
+  /// in namespaces (used as the keys) the name they are emitted under. Only
+  /// the top most context fills it.
+  pub namespaced_symbols: HashMap<String, String>,
+
   /// A bool flag that will be used by identifiers matching with "this", so
   /// they know it should be replaced by the given string, it is used by the
   /// lambda expression to capture "this" expressions.
@@ -62,19 +75,48 @@ impl Context {
       generic_context: generic_types.and_then(|t| Some(GenericContext::new(t))),
       is_library: false,
       mangled_accessor: None,
+      extended_class_name: None,
       variable_declarations: Vec::new(),
       local_variables_inference: HashMap::new(),
       local_parameters_inference: HashMap::new(),
+      imports: HashMap::new(),
+      namespaced_symbols: HashMap::new(),
       replace_this_with_self: RefCell::new(None)
     }
   }
 
-  pub fn set_as_library(&mut self) {
+  /// Marks the context as coming from a library, its declarations are then
+  /// emitted under a mangled name derived from the `library_path`. The path
+  /// must identify the declaration uniquely and stay the same between two
+  /// compilations, so the emitted code does too.
+  pub fn set_as_library(&mut self, library_path: &str) {
     self.is_library = true;
-    self.mangled_accessor = Some(format!(
-      "wss{}",
-      uuid::Uuid::new_v4().to_string().replace("-", "")
-    ));
+    self.mangled_accessor = Some(format!("wss{}", stable_hash(&[library_path])));
+  }
+
+  /// Clears what the visitors stored in the context during the analysis of the
+  /// project, the context keeps its place in the tree. So the AST of a file
+  /// can be analyzed again without being parsed again.
+  pub fn clear_analysis(&mut self) {
+    self.variable_declarations.clear();
+    self.local_variables_inference.clear();
+    self.local_parameters_inference.clear();
+    self.imports.clear();
+    self.namespaced_symbols.clear();
+
+    if let Some(generic_context) = &mut self.generic_context {
+      generic_context.translation_variants.clear();
+      generic_context.currently_used_variant = None;
+    }
+  }
+
+  /// Returns the names of the contexts from the top most one to this one, in
+  /// the form of `Program/file: "a.wss"/class: Foo/function: bar`.
+  pub fn get_path(&self) -> String {
+    match &self.parent_context {
+      Some(parent) => format!("{}/{}", Self::get_ref(parent).get_path(), self.name),
+      None => self.name.clone()
+    }
   }
 
   pub fn get_class_name(&self) -> Option<String> {
@@ -106,14 +148,26 @@ impl Context {
 
   pub fn set_parent_context(this: &Rc<RefCell<Context>>, parent: &Rc<RefCell<Context>>) {
     if let Some(parent_context) = &Self::get_ref(this).parent_context {
+      // the context is already in place, when the AST is analyzed again
+      if Rc::ptr_eq(parent_context, parent) {
+        return;
+      }
+
       Self::remove_child(parent_context, &this);
     }
 
     (*parent).borrow_mut().children_contexts.push(this.clone());
     (*this).borrow_mut().parent_context = Some(parent.clone());
 
-    if Self::get_ref(parent).is_library {
-      (*this).borrow_mut().set_as_library();
+    let parent_library_path = match &Self::get_ref(parent).mangled_accessor {
+      Some(accessor) if Self::get_ref(parent).is_library => Some(accessor.clone()),
+      _ => None
+    };
+
+    if let Some(parent_library_path) = parent_library_path {
+      let library_path = format!("{parent_library_path}/{}", Self::get_ref(this).name);
+
+      (*this).borrow_mut().set_as_library(&library_path);
     }
   }
 
@@ -138,46 +192,150 @@ impl Context {
     this.clone()
   }
 
+  /// Returns the context of the global function the name refers to when it is
+  /// used in this context, the imports and the namespaces are resolved.
   pub fn find_global_function_declaration(
     this: &Rc<RefCell<Context>>, name: &str
   ) -> Option<Rc<RefCell<Context>>> {
-    let program = Self::get_top_most_context(this);
-    let context_name = format!("function: {}", name);
-
-    for file_context in &Self::get_ref(&program).children_contexts {
-      let result = Self::get_ref(&file_context)
-        .children_contexts
-        .iter()
-        .find(|context| Self::get_ref(context).name == context_name)
-        .and_then(|c| Some(c.clone()));
-
-      if result.is_some() {
-        return result;
-      }
-    }
+    let qualified_name = Self::get_ref(this).resolve_qualified_name(name);
 
-    None
+    Self::find_global_declaration(this, &["function"], &qualified_name)
   }
 
+  /// Returns the context of the class the name refers to when it is used in
+  /// this context, the imports and the namespaces are resolved.
   pub fn find_global_class_declaration(
     this: &Rc<RefCell<Context>>, name: &str
+  ) -> Option<Rc<RefCell<Context>>> {
+    let qualified_name = Self::get_ref(this).resolve_qualified_name(name);
+
+    Self::find_global_declaration(this, &["class"], &qualified_name)
+  }
+
+  /// Returns the context of the class or struct with the given qualified
+  /// name, see `get_qualified_name`.
+  pub fn find_global_compound_declaration(
+    this: &Rc<RefCell<Context>>, qualified_name: &str
+  ) -> Option<Rc<RefCell<Context>>> {
+    Self::find_global_declaration(this, &["class", "struct"], qualified_name)
+  }
+
+  fn find_global_declaration(
+    this: &Rc<RefCell<Context>>, kinds: &[&str], qualified_name: &str
   ) -> Option<Rc<RefCell<Context>>> {
     let program = Self::get_top_most_context(this);
-    let context_name = format!("class: {}", name);
+    let program = Self::get_ref(&program);
 
-    for file_context in &Self::get_ref(&program).children_contexts {
-      let result = Self::get_ref(&file_context)
-        .children_contexts
-        .iter()
-        .find(|context| Self::get_ref(&context).name == context_name)
-        .and_then(|c| Some(c.clone()));
+    program
+      .children_contexts
+      .iter()
+      .find_map(|file_context| Self::find_declaration(file_context, kinds, qualified_name))
+  }
 
-      if result.is_some() {
-        return result;
-      }
+  /// Returns the child context of one of the given kinds, like `class`, with
+  /// the given qualified name. The namespaces are searched as well.
+  fn find_declaration(
+    this: &Rc<RefCell<Context>>, kinds: &[&str], qualified_name: &str
+  ) -> Option<Rc<RefCell<Context>>> {
+    Self::get_ref(this)
+      .children_contexts
+      .iter()
+      .find_map(|context| {
+        let child = Self::get_ref(context);
+
+        let name = kinds.iter().find_map(|kind| {
+          child
+            .name
+            .strip_prefix(kind)
+            .and_then(|name| name.strip_prefix(": "))
+        });
+
+        if name.is_some_and(|name| child.get_qualified_name(name) == qualified_name) {
+          return Some(context.clone());
+        }
+
+        match child.context_type {
+          ContextType::Namespace { .. } => Self::find_declaration(context, kinds, qualified_name),
+          _ => None
+        }
+      })
+  }
+
+  /// Returns the full path of the namespace the context is, if it is one
+  pub fn get_namespace_path(&self) -> Option<&Vec<String>> {
+    match &self.context_type {
+      ContextType::Namespace { path } => Some(path),
+      _ => None
     }
+  }
 
-    None
+  /// Registers the name a class, struct or function of a namespace is emitted
+  /// under.
+  pub fn register_namespaced_symbol(
+    this: &Rc<RefCell<Context>>, symbol_path: String, emitted_name: String
+  ) {
+    let program = Self::get_top_most_context(this);
+
+    (*program)
+      .borrow_mut()
+      .namespaced_symbols
+      .insert(symbol_path, emitted_name);
+  }
+
+  /// Returns the name the namespaced symbol with the given full path is
+  /// emitted under, if it exists.
+  pub fn find_namespaced_symbol(&self, symbol_path: &str) -> Option<String> {
+    match &self.parent_context {
+      Some(parent) => Self::get_ref(parent).find_namespaced_symbol(symbol_path),
+      None => self.namespaced_symbols.get(symbol_path).cloned()
+    }
+  }
+
+  /// Returns the name a class, struct or function used in this context is
+  /// emitted under, if it comes from a namespace. The imports come first and
+  /// then the namespaces the context is in, from the innermost one.
+  pub fn resolve_namespaced_name(&self, name: &str) -> Option<String> {
+    let imported = self
+      .imports
+      .get(name)
+      .and_then(|symbol_path| self.find_namespaced_symbol(symbol_path));
+
+    let declared = || {
+      self
+        .get_namespace_path()
+        .and_then(|path| self.find_namespaced_symbol(&get_symbol_path(path, name)))
+    };
+
+    imported
+      .or_else(declared)
+      .or_else(|| match &self.parent_context {
+        Some(parent) => Self::get_ref(parent).resolve_namespaced_name(name),
+        None => None
+      })
+  }
+
+  /// Returns the name the class, struct or function of this context, declared
+  /// as `name`, is known under in the whole program. Two namespaces may
+  /// declare the same name, so the declarations of a namespace are known under
+  /// the name they are emitted under.
+  pub fn get_qualified_name(&self, name: &str) -> String {
+    let in_namespace = self
+      .parent_context
+      .as_ref()
+      .is_some_and(|parent| Self::get_ref(parent).get_namespace_path().is_some());
+
+    match &self.mangled_accessor {
+      Some(emitted_name) if in_namespace => emitted_name.clone(),
+      _ => name.to_string()
+    }
+  }
+
+  /// Returns the qualified name, see `get_qualified_name`, of the class, struct
+  /// or function the name refers to when it is used in this context.
+  pub fn resolve_qualified_name(&self, name: &str) -> String {
+    self
+      .resolve_namespaced_name(name)
+      .unwrap_or_else(|| name.to_string())
   }
 
   /// Returns an optional mangled name the identifier should use to use the
@@ -255,8 +413,15 @@ impl Context {
 pub enum ContextType {
   Global,
   ClassOrStruct,
-  State { parent_class_name: String },
-  Function
+  State {
+    parent_class_name: String
+  },
+  Function,
+
+  /// A namespace, with its full path
+  Namespace {
+    path: Vec<String>
+  }
 }
 
 type GenericType = String;
@@ -268,9 +433,10 @@ pub struct GenericContext {
   /// The list of generic types the node accepts
   pub types: Vec<GenericType>,
 
-  /// Contains the list of variant the node accepts
+  /// Contains the list of variant the node accepts, sorted so the variants are
+  /// always emitted in the same order.
   pub translation_variants:
-    HashMap<GenericVariantIdentifier, HashMap<GenericType, ResolvedGenericType>>,
+    BTreeMap<GenericVariantIdentifier, HashMap<GenericType, ResolvedGenericType>>,
 
   pub currently_used_variant: Option<GenericVariantIdentifier>
 }
@@ -279,7 +445,7 @@ impl GenericContext {
   pub fn new(types: Vec<GenericType>) -> Self {
     Self {
       types,
-      translation_variants: HashMap::new(),
+      translation_variants: BTreeMap::new(),
       currently_used_variant: None
     }
   }
@@ -289,8 +455,15 @@ impl GenericContext {
   }
 
   pub fn add_generic_variant(&mut self, types: HashMap<GenericType, ResolvedGenericType>) {
-    // the identifier is just the concatenation of all types used in the variant
-    let identifier = types.values().map(|s| s.to_string()).collect::<String>();
+    // the identifier is the list of all types used in the variant, in the order
+    // the generic types are declared
+    let identifier = self
+      .types
+      .iter()
+      .filter_map(|generic_type| types.get(generic_type))
+      .map(|s| s.to_string())
+      .collect::<Vec<String>>()
+      .join("_");
 
     // we already have the variant in the map
     if self.translation_variants.contains_key(&identifier) {
@@ -337,3 +510,144 @@ impl<'a> GenericContext {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn add_child(
+    parent: &Rc<RefCell<Context>>, name: &str, context_type: ContextType
+  ) -> Rc<RefCell<Context>> {
+    let child = Rc::new(RefCell::new(Context::new(name, None, context_type)));
+    Context::set_parent_context(&child, parent);
+
+    child
+  }
+
+  fn add_namespace(parent: &Rc<RefCell<Context>>, path: &str) -> Rc<RefCell<Context>> {
+    add_child(
+      parent,
+      &format!("namespace: {path}"),
+      ContextType::Namespace {
+        path: path.split('.').map(str::to_string).collect()
+      }
+    )
+  }
+
+  fn resolve(context: &Rc<RefCell<Context>>, name: &str) -> Option<String> {
+    Context::get_ref(context).resolve_namespaced_name(name)
+  }
+
+  /// Builds a program with a file that declares the `a.b` and `a.b.c`
+  /// namespaces, returns the program and the file.
+  fn build_program() -> (Rc<RefCell<Context>>, Rc<RefCell<Context>>) {
+    let program = Rc::new(RefCell::new(Context::new(
+      "Program",
+      None,
+      ContextType::Global
+    )));
+    let file = add_child(&program, "file: \"a.wss\"", ContextType::Global);
+
+    for (symbol_path, emitted_name) in [
+      ("a.b.Foo", "a_b_Foo"),
+      ("a.b.c.Bar", "a_b_c_Bar"),
+      ("x.Baz", "x_Baz")
+    ] {
+      Context::register_namespaced_symbol(&file, symbol_path.to_string(), emitted_name.to_string());
+    }
+
+    (program, file)
+  }
+
+  #[test]
+  fn resolves_the_names_declared_in_the_enclosing_namespaces() {
+    let (_program, file) = build_program();
+    let namespace = add_namespace(&file, "a.b");
+    let nested_namespace = add_namespace(&namespace, "a.b.c");
+    let function = add_child(&nested_namespace, "function: run", ContextType::Function);
+
+    assert_eq!(resolve(&namespace, "Foo").as_deref(), Some("a_b_Foo"));
+    assert_eq!(resolve(&namespace, "Bar"), None);
+    assert_eq!(resolve(&function, "Bar").as_deref(), Some("a_b_c_Bar"));
+    assert_eq!(resolve(&function, "Foo").as_deref(), Some("a_b_Foo"));
+    assert_eq!(resolve(&function, "Baz"), None);
+  }
+
+  #[test]
+  fn leaves_the_names_outside_of_namespaces() {
+    let (_program, file) = build_program();
+    let function = add_child(&file, "function: run", ContextType::Function);
+
+    assert_eq!(resolve(&function, "Foo"), None);
+    assert_eq!(resolve(&function, "a_b_Foo"), None);
+  }
+
+  #[test]
+  fn resolves_the_imported_names() {
+    let (_program, file) = build_program();
+    let function = add_child(&file, "function: run", ContextType::Function);
+
+    {
+      let mut file = file.borrow_mut();
+      file
+        .imports
+        .insert(String::from("Baz"), String::from("x.Baz"));
+      file
+        .imports
+        .insert(String::from("Missing"), String::from("x.Missing"));
+    }
+
+    assert_eq!(resolve(&function, "Baz").as_deref(), Some("x_Baz"));
+    assert_eq!(resolve(&function, "Missing"), None);
+  }
+
+  #[test]
+  fn prefers_the_imports_to_the_enclosing_namespaces() {
+    let (_program, file) = build_program();
+    let namespace = add_namespace(&file, "a.b");
+    let function = add_child(&namespace, "function: run", ContextType::Function);
+
+    namespace
+      .borrow_mut()
+      .imports
+      .insert(String::from("Foo"), String::from("x.Baz"));
+
+    assert_eq!(resolve(&function, "Foo").as_deref(), Some("x_Baz"));
+  }
+
+  #[test]
+  fn finds_the_classes_that_share_a_name_in_different_namespaces() {
+    let (program, file) = build_program();
+    let other_file = add_child(&program, "file: \"b.wss\"", ContextType::Global);
+
+    let mut classes = Vec::new();
+    for (parent, path) in [(&file, "one"), (&other_file, "other")] {
+      let namespace = add_namespace(parent, path);
+      let class = add_child(&namespace, "class: Counter", ContextType::ClassOrStruct);
+      let emitted_name = format!("{path}_Counter");
+
+      class.borrow_mut().mangled_accessor = Some(emitted_name.clone());
+      Context::register_namespaced_symbol(&file, format!("{path}.Counter"), emitted_name);
+      classes.push(class);
+    }
+
+    let function = add_child(&file, "function: run", ContextType::Function);
+    function
+      .borrow_mut()
+      .imports
+      .insert(String::from("Counter"), String::from("other.Counter"));
+
+    assert_eq!(
+      Context::get_ref(&classes[0]).get_qualified_name("Counter"),
+      "one_Counter"
+    );
+
+    let found = Context::find_global_class_declaration(&function, "Counter");
+    assert!(found.is_some_and(|found| Rc::ptr_eq(&found, &classes[1])));
+
+    let found = Context::find_global_compound_declaration(&function, "one_Counter");
+    assert!(found.is_some_and(|found| Rc::ptr_eq(&found, &classes[0])));
+
+    assert!(Context::find_global_class_declaration(&file, "Counter").is_none());
+  }
+}
@@ -1,6 +1,7 @@
 use std::ops::Deref;
 
 pub mod context;
+pub mod span_markers;
 pub mod type_inference;
 
 pub trait Codegen {
@@ -88,3 +89,11 @@ impl Codegen for String {
     write!(output, "{}", self)
   }
 }
+
+impl Codegen for bool {
+  fn emit(&self, _: &context::Context, output: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    use std::io::Write as IoWrite;
+
+    write!(output, "{}", self)
+  }
+}
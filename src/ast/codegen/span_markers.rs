@@ -0,0 +1,51 @@
+//! The nodes write invisible markers holding their spans in the emitted code,
+//! so the lines of the output can be traced back to the source once the code
+//! is emitted. The markers must be removed with [extract_span_markers] before
+//! the code is written anywhere.
+
+use crate::ast::Span;
+
+const MARKER_START: char = '\u{1}';
+const MARKER_END: char = '\u{2}';
+
+pub fn write_span_marker(f: &mut Vec<u8>, span: Span) -> Result<(), std::io::Error> {
+  use std::io::Write as IoWrite;
+
+  write!(f, "{MARKER_START}{}{MARKER_END}", span.0)
+}
+
+/// Removes the span markers from the code, returns the code without them and
+/// for the lines that had some (1-based), the span of the first marker.
+pub fn extract_span_markers(code: &str) -> (String, Vec<(usize, Span)>) {
+  let mut output = String::with_capacity(code.len());
+  let mut spans = Vec::new();
+
+  for (index, line) in code.lines().enumerate() {
+    let mut rest = line;
+    let mut line_span = None;
+
+    while let Some(start) = rest.find(MARKER_START) {
+      let Some(length) = rest[start..].find(MARKER_END) else {
+        break;
+      };
+
+      output.push_str(&rest[..start]);
+
+      let marker = &rest[start + MARKER_START.len_utf8()..start + length];
+      if let (None, Ok(span)) = (line_span, marker.parse()) {
+        line_span = Some(Span(span));
+      }
+
+      rest = &rest[start + length + MARKER_END.len_utf8()..];
+    }
+
+    output.push_str(rest);
+    output.push('\n');
+
+    if let Some(span) = line_span {
+      spans.push((index + 1, span));
+    }
+  }
+
+  (output, spans)
+}
@@ -21,18 +21,22 @@ impl TypeInferenceStore {
     map.insert("float".to_string(), Rc::new(InferedType::Scalar));
     map.insert("string".to_string(), Rc::new(InferedType::Scalar));
     map.insert("name".to_string(), Rc::new(InferedType::Scalar));
+    map.insert("bool".to_string(), Rc::new(InferedType::Scalar));
 
     Self { types: map }
   }
 
-  pub fn register_compound(&mut self, name: String, extends: Option<String>) -> Result<(), String> {
+  pub fn register_compound(
+    &mut self, name: String, extends: Option<String>, is_class: bool
+  ) -> Result<(), String> {
     if self.types.contains_key(&name) {
       return Err(format!("compound type {} was registered twice", &name));
     }
 
     let compound = Rc::new(InferedType::Compound {
       type_inference_map: RefCell::new(HashMap::new()),
-      extends
+      extends,
+      is_class
     });
 
     self.types.insert(name, compound.clone());
@@ -70,8 +74,7 @@ impl TypeInferenceStore {
       .and_modify(|class_type| {
         match &**class_type {
           InferedType::Compound {
-            type_inference_map,
-            extends: _
+            type_inference_map, ..
           } => {
             let mut class = type_inference_map.borrow_mut();
 
@@ -109,7 +112,11 @@ pub enum InferedType {
 
     /// In case the compound type extend another type, this value is set to
     /// `Some(base_type_identifier)`
-    extends: Option<String>
+    extends: Option<String>,
+
+    /// Whether the type is a class, its values are then references to objects
+    /// and can be `NULL`.
+    is_class: bool
   },
 
   Function(Rc<FunctionInferedType>),
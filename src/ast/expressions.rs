@@ -1,8 +1,9 @@
 use std::borrow::Borrow;
 use std::rc::Rc;
 
-use ariadne::{Label, Report};
+use ariadne::ReportKind;
 
+use super::codegen::span_markers::write_span_marker;
 use super::codegen::type_inference::{FunctionInferedType, InferedType, TypeInferenceMap};
 use super::inference::Type;
 use super::*;
@@ -35,7 +36,7 @@ impl Expression {
   pub fn deduce_type(
     &self, current_context: &Rc<RefCell<Context>>, inference_map: &TypeInferenceMap,
     global_inference_map: &TypeInferenceMap, span_manager: &SpanManager
-  ) -> Result<(), Vec<(Report, Span)>> {
+  ) -> Result<(), Vec<(Diagnostic, Span)>> {
     {
       let self_infered_type_name: &Type = &self.infered_type_name.borrow();
       if let Type::Unknown = self_infered_type_name {
@@ -68,6 +69,14 @@ impl Expression {
           self.set_infered_type(Type::Name, infered_type.clone());
         }
       }
+      ExpressionBody::Bool(_) => {
+        if let Some(infered_type) = inference_map.get("bool") {
+          self.set_infered_type(Type::Bool, infered_type.clone());
+        }
+      }
+      ExpressionBody::Null(_) => {
+        self.set_infered_type(Type::Null, Rc::new(InferedType::Unknown));
+      }
       ExpressionBody::Identifier(identifier) => {
         let a: &IdentifierTerm = &identifier.borrow();
 
@@ -81,16 +90,12 @@ impl Expression {
             }
             Err(message) => {
               return Err(vec![(
-                Report::build(
-                  ariadne::ReportKind::Error,
-                  (),
-                  span_manager.get_left(identifier.span)
-                )
-                .with_message(&"Could not infer type for `this`")
-                .with_label(
-                  Label::new(span_manager.get_range(identifier.span)).with_message(&message)
-                )
-                .finish(),
+                Diagnostic::build(ReportKind::Error, span_manager.get_left(identifier.span))
+                  .with_message(&"Could not infer type for `this`")
+                  .with_label(
+                    DiagnosticLabel::new(span_manager.get_range(identifier.span))
+                      .with_message(&message)
+                  ),
                 identifier.span
               )]);
             }
@@ -105,16 +110,12 @@ impl Expression {
             }
             Err(message) => {
               return Err(vec![(
-                Report::build(
-                  ariadne::ReportKind::Error,
-                  (),
-                  span_manager.get_left(identifier.span)
-                )
-                .with_message(&"Could not infer type for `parent`")
-                .with_label(
-                  Label::new(span_manager.get_range(identifier.span)).with_message(&message)
-                )
-                .finish(),
+                Diagnostic::build(ReportKind::Error, span_manager.get_left(identifier.span))
+                  .with_message(&"Could not infer type for `parent`")
+                  .with_label(
+                    DiagnosticLabel::new(span_manager.get_range(identifier.span))
+                      .with_message(&message)
+                  ),
                 identifier.span
               )]);
             }
@@ -130,17 +131,12 @@ impl Expression {
             }
             None => {
               return Err(vec![(
-                Report::build(
-                  ariadne::ReportKind::Error,
-                  (),
-                  span_manager.get_left(identifier.span)
-                )
-                .with_message(&"Unknown local variable")
-                .with_label(
-                  Label::new(span_manager.get_range(identifier.span))
-                    .with_message(&"No variable or property exists with such name")
-                )
-                .finish(),
+                Diagnostic::build(ReportKind::Error, span_manager.get_left(identifier.span))
+                  .with_message(&"Unknown local variable")
+                  .with_label(
+                    DiagnosticLabel::new(span_manager.get_range(identifier.span))
+                      .with_message(&"No variable or property exists with such name")
+                  ),
                 identifier.span
               )]);
             }
@@ -148,7 +144,15 @@ impl Expression {
         }
       }
       ExpressionBody::FunctionCall(function) => {
-        match inference_map.get(&function.accessor.text) {
+        // the calls of the functions declared in namespaces were given the
+        // name the functions are known under
+        let function_name = function
+          .mangled_accessor
+          .borrow()
+          .clone()
+          .unwrap_or_else(|| function.accessor.text.clone());
+
+        match inference_map.get(&function_name) {
           Some(infered_type) => match infered_type.as_ref() {
             crate::ast::codegen::type_inference::InferedType::Function(rc_function) => {
               function
@@ -170,47 +174,42 @@ impl Expression {
             }
             _ => {
               return Err(vec![(
-                Report::build(
-                  ariadne::ReportKind::Error,
-                  (),
+                Diagnostic::build(
+                  ReportKind::Error,
                   span_manager.get_left(function.accessor.span)
                 )
                 .with_message(&"Invalid function call")
                 .with_label(
-                  Label::new(span_manager.get_range(function.accessor.span))
+                  DiagnosticLabel::new(span_manager.get_range(function.accessor.span))
                     .with_message(&format!("{} is not a function.", &function.accessor.text))
-                )
-                .finish(),
+                ),
                 function.accessor.span
               )]);
             }
           },
           None => {
             return Err(vec![(
-              Report::build(
-                ariadne::ReportKind::Warning,
-                (),
+              Diagnostic::build(
+                ReportKind::Warning,
                 span_manager.get_left(function.accessor.span)
               )
               .with_message(&"Call to unknown function")
               .with_label(
-                Label::new(span_manager.get_range(function.accessor.span)).with_message(&format!(
-                  "{} is not a known function.",
-                  &function.accessor.text
-                ))
-              )
-              .finish(),
+                DiagnosticLabel::new(span_manager.get_range(function.accessor.span)).with_message(
+                  &format!("{} is not a known function.", &function.accessor.text)
+                )
+              ),
               function.accessor.span
             )]);
           }
         };
       }
       ExpressionBody::ClassInstantiation(instantiation) => {
-        if let Some(infered_type) = inference_map.get(&instantiation.class_name) {
-          self.set_infered_type(
-            Type::Identifier(instantiation.class_name.clone()),
-            infered_type.clone()
-          );
+        let class_name =
+          Context::get_ref(current_context).resolve_qualified_name(&instantiation.class_name);
+
+        if let Some(infered_type) = inference_map.get(&class_name) {
+          self.set_infered_type(Type::Identifier(class_name), infered_type.clone());
         }
       }
       ExpressionBody::Lambda(lambda) => {
@@ -231,6 +230,15 @@ impl Expression {
 
         self.set_infered_type(the_type, infered_type);
       }
+      ExpressionBody::Operation(
+        _,
+        OperationCode::Comparison(_) | OperationCode::BooleanJoin(_),
+        _
+      ) => {
+        if let Some(infered_type) = inference_map.get("bool") {
+          self.set_infered_type(Type::Bool, infered_type.clone());
+        }
+      }
       ExpressionBody::Operation(left, operation, right) => {
         match &left.body.borrow() {
           // when it starts with a string, it can only be a string
@@ -281,16 +289,13 @@ impl Expression {
                             let span = lambda.span;
 
                             return Err(vec![(
-                              Report::build(
-                                ariadne::ReportKind::Warning,
-                                (),
-                                span_manager.get_left(span)
-                              )
-                              .with_message(&"Unknown return type in lambda")
-                              .with_label(Label::new(span_manager.get_range(span)).with_message(
-                                &format!("The returned type \"{t}\" is not a known type")
-                              ))
-                              .finish(),
+                              Diagnostic::build(ReportKind::Warning, span_manager.get_left(span))
+                                .with_message(&"Unknown return type in lambda")
+                                .with_label(
+                                  DiagnosticLabel::new(span_manager.get_range(span)).with_message(
+                                    &format!("The returned type \"{t}\" is not a known type")
+                                  )
+                                ),
                               span
                             )]);
                           }
@@ -311,22 +316,18 @@ impl Expression {
                   match &**left_infered_type {
                     InferedType::Compound {
                       type_inference_map,
-                      extends
+                      extends,
+                      ..
                     } => (type_inference_map, extends),
                     _ => {
                       let span = left.body.get_span();
 
                       return Err(vec![(
-                        Report::build(
-                          ariadne::ReportKind::Warning,
-                          (),
-                          span_manager.get_left(span)
-                        )
+                        Diagnostic::build(ReportKind::Warning, span_manager.get_left(span))
                         .with_message(&"Invalid nesting")
-                        .with_label(Label::new(span_manager.get_range(span)).with_message(
+                        .with_label(DiagnosticLabel::new(span_manager.get_range(span)).with_message(
                           &"Nesting but left side expression does not result in a compound type."
-                        ))
-                        .finish(),
+                        )),
                         span
                       )]);
                     }
@@ -339,86 +340,72 @@ impl Expression {
                     let span = left.body.get_span();
 
                     return Err(vec![(
-                      Report::build(
-                        ariadne::ReportKind::Warning,
-                        (),
-                        span_manager.get_left(span)
-                      )
-                      .with_message(&"Invalid nesting")
-                      .with_label(
-                        Label::new(span_manager.get_range(span))
-                          .with_message(&"Nesting but left side expression is not an identifier.")
-                      )
-                      .finish(),
+                      Diagnostic::build(ReportKind::Warning, span_manager.get_left(span))
+                        .with_message(&"Invalid nesting")
+                        .with_label(
+                          DiagnosticLabel::new(span_manager.get_range(span)).with_message(
+                            &"Nesting but left side expression is not an identifier."
+                          )
+                        ),
                       span
                     )]);
                   }
                 };
 
                 // now that we know the left side of the nesting is a compound type,
-                let top_most_context = &Context::get_top_most_context(&current_context);
-                let top_most_context: &RefCell<Context> = &top_most_context.borrow();
-                let top_most_context: &Context = &top_most_context.borrow();
-
-                for file_context in &top_most_context.children_contexts {
-                  let context = Context::get_ref(&file_context);
-                  for global_type_context in &context.children_contexts {
-                    let context = Context::get_ref(&global_type_context);
-                    match context.get_compound_name() {
-                      Some(compound_name) => {
-                        if &compound_name == left_type_identifier {
-                          // we need to handle class inheritance for nesting
-                          // on compound types.
-                          let mut used_type_inference_map = left_compound_type_inference_map;
-                          let mut used_extend = left_extended_type;
-
-                          loop {
-                            let result = right.deduce_type(
-                              &global_type_context,
-                              &used_type_inference_map.borrow(),
-                              inference_map,
-                              span_manager
-                            );
+                // its methods and fields are looked for in its context
+                let compound_context =
+                  Context::find_global_compound_declaration(current_context, left_type_identifier);
+
+                if let Some(global_type_context) = &compound_context {
+                  // we need to handle class inheritance for nesting
+                  // on compound types.
+                  let mut used_type_inference_map = left_compound_type_inference_map;
+                  let mut used_extend = left_extended_type;
+
+                  loop {
+                    let result = right.deduce_type(
+                      &global_type_context,
+                      &used_type_inference_map.borrow(),
+                      inference_map,
+                      span_manager
+                    );
+
+                    if result.is_ok() {
+                      break;
+                    }
 
-                            if result.is_ok() {
-                              break;
-                            }
-
-                            match used_extend {
-                              // there is still a type in the inheritance tree
-                              Some(extend_type_identifier) => {
-                                dbg!(&extend_type_identifier);
-                                match global_inference_map.get(extend_type_identifier) {
-                                  Some(base_type) => {
-                                    match base_type.borrow() {
-                                      InferedType::Compound {
-                                        type_inference_map,
-                                        extends
-                                      } => {
-                                        used_type_inference_map = type_inference_map;
-                                        used_extend = extends;
-                                      }
-                                      _ => {
-                                        return result;
-                                      }
-                                    };
-                                  }
-                                  None => return result
-                                };
+                    match used_extend {
+                      // there is still a type in the inheritance tree
+                      Some(extend_type_identifier) => {
+                        dbg!(&extend_type_identifier);
+                        match global_inference_map.get(extend_type_identifier) {
+                          Some(base_type) => {
+                            match base_type.borrow() {
+                              InferedType::Compound {
+                                type_inference_map,
+                                extends,
+                                ..
+                              } => {
+                                used_type_inference_map = type_inference_map;
+                                used_extend = extends;
+                              }
+                              _ => {
+                                return result;
                               }
-                              None => return result
-                            }
+                            };
                           }
-
-                          self.set_infered_type(
-                            right.infered_type_name.borrow().clone(),
-                            right.infered_type.borrow().clone()
-                          );
-                        }
+                          None => return result
+                        };
                       }
-                      None => {}
-                    };
+                      None => return result
+                    }
                   }
+
+                  self.set_infered_type(
+                    right.infered_type_name.borrow().clone(),
+                    right.infered_type.borrow().clone()
+                  );
                 }
               }
               _ => {
@@ -433,6 +420,24 @@ impl Expression {
           self.set_infered_type(Type::Bool, infered_type.clone());
         }
       }
+      ExpressionBody::Negate(expr) => {
+        expr.deduce_type(
+          current_context,
+          inference_map,
+          global_inference_map,
+          span_manager
+        )?;
+
+        self.set_infered_type(
+          expr.infered_type_name.borrow().clone(),
+          expr.infered_type.borrow().clone()
+        );
+      }
+      ExpressionBody::BitwiseNot(_) => {
+        if let Some(infered_type) = inference_map.get("int") {
+          self.set_infered_type(Type::Int, infered_type.clone());
+        }
+      }
       ExpressionBody::List(x) => {
         if let Some(first) = x.first() {
           first.deduce_type(
@@ -453,20 +458,72 @@ impl Expression {
             let span = expr.body.get_span();
 
             return Err(vec![(
-              Report::build(
-                ariadne::ReportKind::Warning,
-                (),
-                span_manager.get_left(span)
-              )
-              .with_message(&"Cast to unknown type")
-              .with_label(
-                Label::new(span_manager.get_range(span))
-                  .with_message(&format!("{} is not a known type.", &type_name))
-              )
-              .finish(),
+              Diagnostic::build(ReportKind::Warning, span_manager.get_left(span))
+                .with_message(&"Cast to unknown type")
+                .with_label(
+                  DiagnosticLabel::new(span_manager.get_range(span))
+                    .with_message(&format!("{} is not a known type.", &type_name))
+                ),
+              span
+            )]);
+          }
+        };
+      }
+      ExpressionBody::Ternary(ternary) => {
+        let branches = [
+          &ternary.condition,
+          &ternary.consequence,
+          &ternary.alternative
+        ];
+
+        // the errors of the branches are reported when they are visited
+        for branch in branches {
+          if branch
+            .deduce_type(
+              current_context,
+              inference_map,
+              global_inference_map,
+              span_manager
+            )
+            .is_err()
+          {
+            return Ok(());
+          }
+        }
+
+        let consequence_type = ternary.consequence.infered_type_name.borrow().clone();
+        let alternative_type = ternary.alternative.infered_type_name.borrow().clone();
+
+        let branch = match consequence_type {
+          Type::Unknown | Type::Null => &ternary.alternative,
+          _ => &ternary.consequence
+        };
+
+        // the type is set even if the branches disagree so the error is
+        // reported only once
+        self.set_infered_type(
+          branch.infered_type_name.borrow().clone(),
+          branch.infered_type.borrow().clone()
+        );
+
+        match (&consequence_type, &alternative_type) {
+          (Type::Unknown, _) | (_, Type::Unknown) => {}
+          (Type::Null, t) | (t, Type::Null) if t.is_nullable(global_inference_map) => {}
+          (a, b) if a.to_string() != b.to_string() => {
+            let span = ternary.span;
+
+            return Err(vec![(
+              Diagnostic::build(ReportKind::Error, span_manager.get_left(span))
+                .with_message(&"Mismatched types in ternary expression")
+                .with_label(
+                  DiagnosticLabel::new(span_manager.get_range(span))
+                    .with_message(&format!("The branches result in a {a} and a {b}"))
+                )
+                .with_help(&"Cast one of the branches with `as` so both have the same type"),
               span
             )]);
           }
+          _ => {}
         };
       }
       ExpressionBody::Group(expr) => {
@@ -485,6 +542,59 @@ impl Expression {
   }
 }
 
+impl Expression {
+  /// Collects the ternaries of the expression that are computed before the
+  /// statement it belongs to. The ternaries in the branches of another one
+  /// are left to it, and the ones in lambdas to the body of the lambda.
+  pub fn collect_ternaries<'a>(&'a self, ternaries: &mut Vec<&'a Ternary>) {
+    match &self.body {
+      ExpressionBody::Ternary(x) => ternaries.push(x),
+      ExpressionBody::Identifier(x) => {
+        for index in &x.indexing {
+          index.collect_ternaries(ternaries);
+        }
+      }
+      ExpressionBody::FunctionCall(x) => {
+        for index in &x.accessor.indexing {
+          index.collect_ternaries(ternaries);
+        }
+
+        for parameter in x.parameters.0.iter().flatten() {
+          parameter.collect_ternaries(ternaries);
+        }
+      }
+      ExpressionBody::Operation(left, _, right) => {
+        left.collect_ternaries(ternaries);
+        right.collect_ternaries(ternaries);
+      }
+      ExpressionBody::Not(x)
+      | ExpressionBody::Negate(x)
+      | ExpressionBody::BitwiseNot(x)
+      | ExpressionBody::Cast(_, x)
+      | ExpressionBody::Group(x) => x.collect_ternaries(ternaries),
+      ExpressionBody::Nesting(x) => {
+        for expression in x {
+          expression.collect_ternaries(ternaries);
+        }
+      }
+      ExpressionBody::List(x) => {
+        for expression in x {
+          expression.collect_ternaries(ternaries);
+        }
+      }
+      ExpressionBody::Integer(_)
+      | ExpressionBody::Float(_)
+      | ExpressionBody::String(_)
+      | ExpressionBody::Name(_)
+      | ExpressionBody::Bool(_)
+      | ExpressionBody::Null(_)
+      | ExpressionBody::ClassInstantiation(_)
+      | ExpressionBody::Lambda(_)
+      | ExpressionBody::Error => {}
+    }
+  }
+}
+
 impl visitor::Visited for Expression {
   fn accept<T: visitor::Visitor>(&self, visitor: &mut T) {
     visitor.visit_expression(self);
@@ -495,6 +605,10 @@ impl visitor::Visited for Expression {
 
 impl Codegen for Expression {
   fn emit(&self, context: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    if let Some(span) = self.body.get_own_span() {
+      write_span_marker(f, span)?;
+    }
+
     self.body.emit(context, f)
   }
 }
@@ -506,6 +620,8 @@ pub enum ExpressionBody {
 
   String(Spanned<String>),
   Name(Spanned<String>),
+  Bool(Spanned<bool>),
+  Null(Span),
 
   Identifier(Box<IdentifierTerm>),
 
@@ -517,10 +633,20 @@ pub enum ExpressionBody {
   Operation(Rc<Expression>, OperationCode, Rc<Expression>),
 
   Not(Rc<Expression>),
+
+  /// The unary minus, `-x`
+  Negate(Rc<Expression>),
+
+  /// `~x`
+  BitwiseNot(Rc<Expression>),
+
   Nesting(Vec<Expression>),
   Cast(String, Rc<Expression>),
   List(Vec<Rc<Expression>>),
 
+  /// A `condition ? consequence : alternative` expression
+  Ternary(Ternary),
+
   /// Expressions surrounded by parenthesis
   Group(Rc<Expression>),
 
@@ -533,7 +659,9 @@ impl visitor::Visited for ExpressionBody {
       ExpressionBody::Integer(_)
       | ExpressionBody::Float(_)
       | ExpressionBody::String(_)
-      | ExpressionBody::Name(_) => {}
+      | ExpressionBody::Name(_)
+      | ExpressionBody::Bool(_)
+      | ExpressionBody::Null(_) => {}
       ExpressionBody::Cast(_, x) => x.accept(visitor),
       ExpressionBody::Identifier(x) => x.accept(visitor),
       ExpressionBody::FunctionCall(x) => x.accept(visitor),
@@ -546,7 +674,10 @@ impl visitor::Visited for ExpressionBody {
       ExpressionBody::Group(x) => x.accept(visitor),
       ExpressionBody::ClassInstantiation(x) => x.accept(visitor),
       ExpressionBody::Not(x) => x.accept(visitor),
+      ExpressionBody::Negate(x) => x.accept(visitor),
+      ExpressionBody::BitwiseNot(x) => x.accept(visitor),
       ExpressionBody::List(x) => x.accept(visitor),
+      ExpressionBody::Ternary(x) => x.accept(visitor),
       ExpressionBody::Lambda(x) => x.accept(visitor)
     }
   }
@@ -561,15 +692,41 @@ impl Codegen for ExpressionBody {
       ExpressionBody::Float(x) => x.emit(context, f),
       ExpressionBody::String(x) => x.emit(context, f),
       ExpressionBody::Name(x) => x.emit(context, f),
+      ExpressionBody::Bool(x) => x.emit(context, f),
+      ExpressionBody::Null(_) => write!(f, "NULL"),
       ExpressionBody::Not(x) => {
         write!(f, "!")?;
         x.emit(context, f)
       }
+      ExpressionBody::Negate(x) => {
+        write!(f, "-")?;
+
+        // `- -a` must not be emitted as the `--` operator
+        if let ExpressionBody::Negate(_) = x.body {
+          write!(f, "(")?;
+          x.emit(context, f)?;
+          return write!(f, ")");
+        }
+
+        x.emit(context, f)
+      }
+      ExpressionBody::BitwiseNot(x) => {
+        write!(f, "~")?;
+        x.emit(context, f)
+      }
       ExpressionBody::Identifier(x) => x.emit(context, f),
       ExpressionBody::FunctionCall(x) => x.emit(context, f),
       ExpressionBody::Operation(left, op, right) => {
         left.emit(context, f)?;
         op.emit(context, f)?;
+
+        // `a - -b` must not be emitted as the `--` operator
+        if let (OperationCode::Add | OperationCode::Sub, ExpressionBody::Negate(_)) =
+          (op, &right.body)
+        {
+          write!(f, " ")?;
+        }
+
         right.emit(context, f)
       }
       ExpressionBody::Error => todo!(),
@@ -592,6 +749,10 @@ impl Codegen for ExpressionBody {
         write!(f, "}}")
       }
       ExpressionBody::Cast(t, x) => {
+        let t = context
+          .resolve_namespaced_name(t)
+          .unwrap_or_else(|| t.clone());
+
         write!(f, "({t})(")?;
         x.emit(context, f)?;
         write!(f, ")")
@@ -602,6 +763,7 @@ impl Codegen for ExpressionBody {
         x.emit(context, f)?;
         write!(f, ")")
       }
+      ExpressionBody::Ternary(x) => x.emit(context, f),
       ExpressionBody::Lambda(x) => x.emit(context, f)
     }
   }
@@ -626,7 +788,7 @@ impl ExpressionBody {
             parent_class_name: _
           } => {
             let class_name = match context.get_class_name() {
-              Some(n) => n,
+              Some(n) => context.get_qualified_name(&n),
               None => {
                 return Err(String::from(
                   "Cannot use `this` outside of a class or a state"
@@ -673,6 +835,8 @@ impl ExpressionBody {
 
     match &parent_context.context_type {
       ContextType::State { parent_class_name } => {
+        let parent_class_name = &parent_context.resolve_qualified_name(parent_class_name);
+
         if inference_map.contains_key(parent_class_name) {
           return Ok(inference::Type::Identifier(parent_class_name.clone()));
         } else {
@@ -689,22 +853,45 @@ impl ExpressionBody {
     };
   }
 
+  /// Returns the span of the expressions that have one of their own, unlike
+  /// `get_span` it does not borrow the span of a child expression.
+  pub fn get_own_span(&self) -> Option<Span> {
+    match self {
+      ExpressionBody::Integer(x)
+      | ExpressionBody::Float(x)
+      | ExpressionBody::String(x)
+      | ExpressionBody::Name(x) => Some(x.span),
+      ExpressionBody::Bool(x) => Some(x.span),
+      ExpressionBody::Null(x) => Some(*x),
+      ExpressionBody::Identifier(x) => Some(x.span),
+      ExpressionBody::FunctionCall(x) => Some(x.span),
+      ExpressionBody::ClassInstantiation(x) => Some(x.span),
+      ExpressionBody::Lambda(x) => Some(x.span),
+      _ => None
+    }
+  }
+
   pub fn get_span(&self) -> Span {
     match &self {
       ExpressionBody::Integer(x) => x.span,
       ExpressionBody::Float(x) => x.span,
       ExpressionBody::String(x) => x.span,
       ExpressionBody::Name(x) => x.span,
+      ExpressionBody::Bool(x) => x.span,
+      ExpressionBody::Null(x) => *x,
       ExpressionBody::Identifier(x) => x.span,
       ExpressionBody::FunctionCall(x) => x.span,
       ExpressionBody::ClassInstantiation(x) => x.span,
       ExpressionBody::Lambda(x) => x.span,
       ExpressionBody::Operation(_, _, x) => x.body.get_span(),
       ExpressionBody::Not(x) => x.body.get_span(),
+      ExpressionBody::Negate(x) => x.body.get_span(),
+      ExpressionBody::BitwiseNot(x) => x.body.get_span(),
       ExpressionBody::Nesting(x) => x.last().unwrap().body.get_span(),
       ExpressionBody::List(x) => x.last().unwrap().body.get_span(),
       ExpressionBody::Cast(_, x) => x.body.get_span(),
       ExpressionBody::Group(x) => x.body.get_span(),
+      ExpressionBody::Ternary(x) => x.span,
       ExpressionBody::Error => todo!()
     }
   }
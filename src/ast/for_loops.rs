@@ -52,25 +52,7 @@ pub struct ForInStatement {
 
 impl Visited for ForInStatement {
   fn accept<T: visitor::Visitor>(&self, visitor: &mut T) {
-    match visitor.visitor_type() {
-      visitor::VisitorType::VariableDeclarationVisitor => {
-        // we generate a new random intermediate indexing variable for this for loop
-        let indexor = format!("idx{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
-        let indexor_variable = TypedIdentifier {
-          names: vec![indexor.clone()],
-          type_declaration: TypeDeclaration::Regular {
-            type_name: "int".to_string(),
-            generic_type_assignment: None,
-            mangled_accessor: RefCell::new(None)
-          }
-        };
-
-        visitor.register_variable_declaration(self.child.clone());
-        visitor.register_variable_declaration(Rc::new(indexor_variable));
-        self.indexor_name.replace(indexor);
-      }
-      _ => {}
-    };
+    visitor.visit_for_in_statement(self);
 
     self.child.accept(visitor);
     self.parent.accept(visitor);
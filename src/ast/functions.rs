@@ -2,6 +2,7 @@ use std::collections::HashSet;
 use std::rc::Rc;
 
 use super::codegen::context::Context;
+use super::codegen::span_markers::write_span_marker;
 use super::codegen::type_inference::FunctionInferedParameterType;
 use super::visitor::Visited;
 use super::*;
@@ -71,6 +72,7 @@ fn emit_function(
 ) -> Result<(), std::io::Error> {
   use std::io::Write as IoWrite;
 
+  write_span_marker(f, this.span_name)?;
   this.function_type.emit(context, f)?;
 
   let generic_variant_suffix_prefix = match generic_variant_suffix.is_empty() {
@@ -169,7 +171,11 @@ pub enum FunctionBodyStatement {
   WhileStatement(WhileStatement),
   DoWhileStatement(DoWhileStatement),
   SwitchStatement(SwitchStatement),
-  Delete(Rc<Expression>)
+  Delete(Rc<Expression>),
+
+  /// A statement that could not be parsed, the syntax error was recorded
+  /// by the parser.
+  Error
 }
 
 impl FunctionBodyStatement {
@@ -200,8 +206,77 @@ impl FunctionBodyStatement {
   }
 }
 
+impl FunctionBodyStatement {
+  /// Returns the expressions the statement evaluates once before anything
+  /// else, the ternaries they contain are computed right before the statement.
+  pub fn get_leading_expressions(&self) -> Vec<&Expression> {
+    match self {
+      FunctionBodyStatement::VariableDeclaration(VariableDeclaration::Explicit {
+        declaration: _,
+        following_expression
+      }) => following_expression.iter().map(|x| x.as_ref()).collect(),
+      FunctionBodyStatement::VariableDeclaration(VariableDeclaration::Implicit {
+        names: _,
+        following_expression
+      }) => vec![following_expression],
+      FunctionBodyStatement::Expression(x) | FunctionBodyStatement::Delete(x) => vec![x],
+      FunctionBodyStatement::Return(x) => x.iter().map(|x| x.as_ref()).collect(),
+      FunctionBodyStatement::Assignement(x) => {
+        vec![&x.variable_name, &x.following_expression]
+      }
+      FunctionBodyStatement::IfStatement(IfStatement::If { condition, .. }) => vec![condition],
+      FunctionBodyStatement::ForStatement(x) => match &x.initialization {
+        Some(VariableDeclarationOrAssignment::Declaration(VariableDeclaration::Explicit {
+          declaration: _,
+          following_expression
+        })) => following_expression.iter().map(|x| x.as_ref()).collect(),
+        Some(VariableDeclarationOrAssignment::Declaration(VariableDeclaration::Implicit {
+          names: _,
+          following_expression
+        })) => vec![following_expression],
+        Some(VariableDeclarationOrAssignment::Assignement(x)) => {
+          vec![&x.variable_name, &x.following_expression]
+        }
+        None => Vec::new()
+      },
+      FunctionBodyStatement::ForInStatement(x) => vec![&x.parent],
+      FunctionBodyStatement::SwitchStatement(x) => vec![&x.compared],
+      _ => Vec::new()
+    }
+  }
+
+  /// Returns the expressions the statement may evaluate again after it
+  /// started, like the conditions of the loops or of the `else if`.
+  pub fn get_reevaluated_expressions(&self) -> Vec<&Expression> {
+    match self {
+      FunctionBodyStatement::IfStatement(IfStatement::If {
+        else_statements, ..
+      }) => else_statements
+        .iter()
+        .filter_map(|statement| match statement.as_ref() {
+          IfStatement::Else {
+            condition: Some(condition),
+            ..
+          } => Some(condition.as_ref()),
+          _ => None
+        })
+        .collect(),
+      FunctionBodyStatement::ForStatement(x) => vec![
+        &x.condition,
+        &x.iteration.variable_name,
+        &x.iteration.following_expression,
+      ],
+      FunctionBodyStatement::WhileStatement(x) => vec![&x.condition],
+      FunctionBodyStatement::DoWhileStatement(x) => vec![&x.condition],
+      _ => Vec::new()
+    }
+  }
+}
+
 impl visitor::Visited for FunctionBodyStatement {
   fn accept<T: visitor::Visitor>(&self, visitor: &mut T) {
+    visitor.visit_function_body_statement(self);
+
     match &self {
       FunctionBodyStatement::VariableDeclaration(x) => x.accept(visitor),
       FunctionBodyStatement::Expression(x) => x.accept(visitor),
@@ -215,7 +290,8 @@ impl visitor::Visited for FunctionBodyStatement {
       FunctionBodyStatement::Delete(x) => x.accept(visitor),
       FunctionBodyStatement::Break => {}
       FunctionBodyStatement::Continue => {}
-      FunctionBodyStatement::ForInStatement(x) => x.accept(visitor)
+      FunctionBodyStatement::ForInStatement(x) => x.accept(visitor),
+      FunctionBodyStatement::Error => {}
     };
   }
 }
@@ -224,6 +300,8 @@ impl Codegen for FunctionBodyStatement {
   fn emit(&self, context: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
     use std::io::Write as IoWrite;
 
+    Ternary::emit_hoisted(&self.get_leading_expressions(), context, f)?;
+
     match self {
       FunctionBodyStatement::VariableDeclaration(x) => {
         x.emit(context, f)?;
@@ -279,6 +357,7 @@ impl Codegen for FunctionBodyStatement {
         x.emit(context, f)?;
         writeln!(f, ";")?;
       }
+      FunctionBodyStatement::Error => {}
     };
 
     Ok(())
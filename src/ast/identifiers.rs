@@ -113,6 +113,8 @@ impl Codegen for TypeDeclaration {
       } => {
         if let Some(mangled_accessor) = mangled_accessor.borrow().as_deref() {
           write!(f, "{}", mangled_accessor)?;
+        } else if let Some(namespaced_name) = context.resolve_namespaced_name(type_name) {
+          write!(f, "{namespaced_name}")?;
         } else {
           context.transform_if_generic_type(f, &type_name)?;
         }
@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use crate::ast::codegen::type_inference::{InferedType, TypeInferenceMap};
+
 #[derive(Debug, Clone)]
 pub enum Type {
   String,
@@ -8,6 +10,9 @@ pub enum Type {
   Int,
   Float,
   Identifier(String),
+
+  /// The type of `NULL`, it can be assigned to any class type
+  Null,
   Void,
   Unknown
 }
@@ -39,6 +44,7 @@ impl Display for Type {
         Type::Int => "int",
         Type::Float => "float",
         Type::Identifier(x) => x,
+        Type::Null => "NULL",
         Type::Void => "void",
         Type::Unknown => "Unknown"
       }
@@ -55,16 +61,33 @@ impl Type {
       Type::Int => other == "int",
       Type::Float => other == "float",
       Type::Identifier(x) => x == other,
+      Type::Null => false,
       Type::Void => other == "void",
       Type::Unknown => false
     }
   }
 
-  pub fn can_auto_cast(&self, other: &str) -> bool {
+  pub fn can_auto_cast(&self, other: &str, types: &TypeInferenceMap) -> bool {
     match (self, other) {
       (Type::Name, "string") => true,
       (Type::Float, "int") => true,
       (Type::Int, "float") => true,
+      (Type::Null, other) => Type::Identifier(other.to_string()).is_nullable(types),
+      _ => false
+    }
+  }
+
+  /// Returns whether the type is a reference to an object, which `NULL` can be
+  /// assigned to. Only the classes are, the type store tells them apart from
+  /// the structs, and the enums and the primitive types are not compound
+  /// types.
+  pub fn is_nullable(&self, types: &TypeInferenceMap) -> bool {
+    match self {
+      Type::Identifier(x) => matches!(
+        types.get(x).map(|t| t.as_ref()),
+        Some(InferedType::Compound { is_class: true, .. })
+      ),
+      Type::Null => true,
       _ => false
     }
   }
@@ -2,6 +2,7 @@ use std::borrow::Borrow;
 use std::collections::HashSet;
 
 use crate::ast::codegen::context::GenericContext;
+use crate::utils::stable_hash;
 
 use super::inference::Type;
 use super::visitor::Visited;
@@ -167,6 +168,10 @@ pub struct Lambda {
   pub body_statements: Vec<FunctionBodyStatement>,
   pub span: Span,
 
+  /// The offset of the lambda in its file, unlike the span it doesn't depend
+  /// on the other files of the compilation.
+  pub offset: usize,
+
   pub mangled_accessor: RefCell<Option<String>>,
   pub captured_variables: RefCell<Vec<(String, Type)>>
 }
@@ -260,7 +265,7 @@ fn emit_lambda(
     // occurences of "this" with the new generated identifier:
     context
       .replace_this_with_self
-      .replace(Some(uuid::Uuid::new_v4().to_string().replace("-", "")));
+      .replace(Some(format!("self_{mangled_suffix}")));
 
     writeln!(f, " {{")?;
     match this.lambda_type {
@@ -325,10 +330,15 @@ impl Visited for Lambda {
 }
 
 impl Codegen for Lambda {
-  fn emit(&self, _: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
+  fn emit(&self, context: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
     use std::io::Write as IoWrite;
 
-    let suffix = format!("wss{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+    // the offset is unique to this lambda in its file and the path of the
+    // context makes sure two files don't mix up their lambdas.
+    let suffix = format!(
+      "wss{}",
+      stable_hash(&[&context.get_path(), &self.offset.to_string()])
+    );
 
     write!(f, "(new lambda_{suffix} in thePlayer).capture(")?;
 
@@ -84,6 +84,18 @@ pub use annotations::Annotation;
 
 // -----------------------------------------------------------------------------
 
+mod namespaces;
+pub use namespaces::{get_mangled_name, get_symbol_path, ImportDeclaration, NamespaceDeclaration};
+
+// -----------------------------------------------------------------------------
+
+mod native_imports;
+pub use native_imports::{
+  ImportedClassBodyStatement, ImportedClassDeclaration, ImportedFunctionDeclaration
+};
+
+// -----------------------------------------------------------------------------
+
 mod functions;
 pub use functions::{
   FunctionBodyStatement, FunctionCallParameters, FunctionDeclaration, FunctionDeclarationParameter,
@@ -127,6 +139,11 @@ pub use expressions::*;
 
 // -----------------------------------------------------------------------------
 
+mod ternary;
+pub use ternary::Ternary;
+
+// -----------------------------------------------------------------------------
+
 mod function_call;
 pub use function_call::FunctionCall;
 
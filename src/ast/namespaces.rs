@@ -0,0 +1,105 @@
+use super::codegen::context::Context;
+use super::visitor::Visited;
+use super::*;
+
+/// A `namespace a.b { ... }` block, or a whole file after a `module a.b;`
+/// declaration. The classes, structs and functions it declares are emitted
+/// with the path of the namespace as a prefix, like `a_b_Name`.
+#[derive(Debug)]
+pub struct NamespaceDeclaration {
+  pub path: Vec<String>,
+  pub statements: Vec<Statement>,
+  pub span_name: Span,
+
+  pub context: Rc<RefCell<Context>>
+}
+
+impl NamespaceDeclaration {
+  pub fn new(path: Vec<String>, statements: Vec<Statement>, span_name: Span) -> Self {
+    let context = Context::new(
+      &format!("namespace: {}", path.join(".")),
+      None,
+      ContextType::Namespace { path: path.clone() }
+    );
+
+    Self {
+      path,
+      statements,
+      span_name,
+      context: Rc::new(RefCell::new(context))
+    }
+  }
+}
+
+impl Visited for NamespaceDeclaration {
+  fn accept<T: visitor::Visitor>(&self, visitor: &mut T) {
+    visitor.visit_namespace_declaration(&self);
+
+    // don't go further, the context building visitor will create a new one
+    // and continue traversing using the new one.
+    match visitor.visitor_type() {
+      visitor::VisitorType::ContextBuildingVisitor => return,
+      _ => {}
+    };
+
+    self.statements.accept(visitor);
+  }
+}
+
+impl Codegen for NamespaceDeclaration {
+  fn emit(&self, _: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    use std::io::Write as IoWrite;
+
+    for statement in &self.statements {
+      statement.emit(&self.context.borrow(), f)?;
+      writeln!(f, "")?;
+      writeln!(f, "")?;
+    }
+
+    Ok(())
+  }
+}
+
+/// An `import a.b.{Foo, bar};` or `import a.b.Foo;` statement, it makes the
+/// names of the namespace usable without their prefix.
+#[derive(Debug)]
+pub struct ImportDeclaration {
+  /// The path of the namespace the names come from
+  pub path: Vec<String>,
+  pub names: Vec<String>,
+  pub span: Span
+}
+
+impl ImportDeclaration {
+  /// Returns the full path of the imported names, like `a.b.Foo`
+  pub fn get_symbol_paths(&self) -> impl Iterator<Item = (&String, String)> {
+    self
+      .names
+      .iter()
+      .map(|name| (name, get_symbol_path(&self.path, name)))
+  }
+}
+
+impl Visited for ImportDeclaration {
+  fn accept<T: visitor::Visitor>(&self, visitor: &mut T) {
+    visitor.visit_import_declaration(&self);
+  }
+}
+
+impl Codegen for ImportDeclaration {
+  fn emit(&self, _: &Context, _: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    // the imports only exist for the compiler
+    Ok(())
+  }
+}
+
+/// Returns the full path of a name declared in a namespace, like `a.b.Foo`
+pub fn get_symbol_path(namespace: &[String], name: &str) -> String {
+  format!("{}.{name}", namespace.join("."))
+}
+
+/// Returns the name a declaration of a namespace is emitted under, like
+/// `a_b_Foo`. WitcherScript has no namespaces so the path becomes a prefix.
+pub fn get_mangled_name(namespace: &[String], name: &str) -> String {
+  format!("{}_{name}", namespace.join("_"))
+}
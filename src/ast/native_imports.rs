@@ -0,0 +1,130 @@
+use super::codegen::context::Context;
+use super::codegen::span_markers::write_span_marker;
+use super::visitor::Visited;
+use super::*;
+
+/// An `import class CR4Player extends CPlayer { ... }` declaration of a class
+/// implemented by the game. It is emitted as is, and only gives its methods to
+/// the static analysis.
+#[derive(Debug)]
+pub struct ImportedClassDeclaration {
+  pub class_type: ClassType,
+  pub name: String,
+  pub extended_class_name: Option<String>,
+  pub body_statements: Vec<ImportedClassBodyStatement>,
+
+  pub span_name: Span,
+
+  pub context: Rc<RefCell<Context>>
+}
+
+impl Visited for ImportedClassDeclaration {
+  fn accept<T: visitor::Visitor>(&self, visitor: &mut T) {
+    visitor.visit_imported_class_declaration(self);
+  }
+}
+
+impl Codegen for ImportedClassDeclaration {
+  fn emit(&self, context: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    use std::io::Write as IoWrite;
+
+    write_span_marker(f, self.span_name)?;
+    write!(f, "import {} {}", self.class_type, self.name)?;
+
+    if let Some(extended_class_name) = &self.extended_class_name {
+      write!(f, " extends {extended_class_name}")?;
+    }
+
+    writeln!(f, " {{")?;
+
+    for statement in &self.body_statements {
+      statement.emit(context, f)?;
+    }
+
+    writeln!(f, "}}")
+  }
+}
+
+#[derive(Debug)]
+pub enum ImportedClassBodyStatement {
+  Property {
+    encapsulation: Option<EncapsulationType>,
+    declaration: TypedIdentifier
+  },
+  Method(ImportedFunctionDeclaration)
+}
+
+impl Codegen for ImportedClassBodyStatement {
+  fn emit(&self, context: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    use std::io::Write as IoWrite;
+
+    match self {
+      ImportedClassBodyStatement::Property {
+        encapsulation,
+        declaration
+      } => {
+        write!(f, "import ")?;
+
+        if let Some(encapsulation) = encapsulation {
+          encapsulation.emit(context, f)?;
+          write!(f, " ")?;
+        }
+
+        write!(f, "var ")?;
+        declaration.emit(context, f)?;
+        writeln!(f, ";")
+      }
+      ImportedClassBodyStatement::Method(x) => x.emit(context, f)
+    }
+  }
+}
+
+/// An `import function` declaration of a function implemented by the game,
+/// either global or in an imported class.
+#[derive(Debug)]
+pub struct ImportedFunctionDeclaration {
+  pub encapsulation: Option<EncapsulationType>,
+  pub is_final: bool,
+  pub function_type: FunctionType,
+  pub name: String,
+  pub parameters: Vec<FunctionDeclarationParameter>,
+  pub type_declaration: Option<TypeDeclaration>,
+
+  pub span_name: Span
+}
+
+impl Visited for ImportedFunctionDeclaration {
+  fn accept<T: visitor::Visitor>(&self, visitor: &mut T) {
+    visitor.visit_imported_function_declaration(self);
+  }
+}
+
+impl Codegen for ImportedFunctionDeclaration {
+  fn emit(&self, context: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    use std::io::Write as IoWrite;
+
+    write_span_marker(f, self.span_name)?;
+    write!(f, "import ")?;
+
+    if let Some(encapsulation) = &self.encapsulation {
+      encapsulation.emit(context, f)?;
+      write!(f, " ")?;
+    }
+
+    if self.is_final {
+      write!(f, "final ")?;
+    }
+
+    self.function_type.emit(context, f)?;
+    write!(f, " {}(", self.name)?;
+    self.parameters.emit_join(context, f, ", ")?;
+    write!(f, ")")?;
+
+    if let Some(t) = &self.type_declaration {
+      write!(f, ": ")?;
+      t.emit(context, f)?;
+    }
+
+    writeln!(f, ";")
+  }
+}
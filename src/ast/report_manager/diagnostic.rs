@@ -0,0 +1,173 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+use ariadne::{Label, Report, ReportKind};
+use serde_json::json;
+
+use crate::preprocessor::offset_map::IncludedFile;
+use crate::utils::LineIndex;
+
+/// A report waiting to be printed. Unlike ariadne's `Report` it keeps its
+/// kind and its labels readable, so the `ReportManager` can count the errors
+/// it holds before printing them.
+pub struct Diagnostic {
+  pub kind: ReportKind,
+  pub offset: usize,
+  pub message: Option<String>,
+  pub labels: Vec<DiagnosticLabel>,
+  pub help: Option<String>,
+  pub note: Option<String>,
+
+  /// The included file the original offsets point to, `None` when they point
+  /// to the file the report is made for.
+  pub included_file: Option<Rc<IncludedFile>>
+}
+
+impl Diagnostic {
+  pub fn build(kind: ReportKind, offset: usize) -> Self {
+    Self {
+      kind,
+      offset,
+      message: None,
+      labels: Vec::new(),
+      help: None,
+      note: None,
+      included_file: None
+    }
+  }
+
+  pub fn with_message<M: ToString>(mut self, message: M) -> Self {
+    self.message = Some(message.to_string());
+    self
+  }
+
+  pub fn with_label(mut self, label: DiagnosticLabel) -> Self {
+    self.labels.push(label);
+    self
+  }
+
+  pub fn with_help<M: ToString>(mut self, help: M) -> Self {
+    self.help = Some(help.to_string());
+    self
+  }
+
+  pub fn with_note<M: ToString>(mut self, note: M) -> Self {
+    self.note = Some(note.to_string());
+    self
+  }
+
+  pub fn with_included_file(mut self, file: Option<Rc<IncludedFile>>) -> Self {
+    self.included_file = file;
+    self
+  }
+
+  pub fn is_error(&self) -> bool {
+    matches!(self.kind, ReportKind::Error)
+  }
+
+  pub fn is_warning(&self) -> bool {
+    matches!(self.kind, ReportKind::Warning)
+  }
+
+  pub fn to_report(&self) -> Report {
+    let mut builder = Report::build(self.kind, (), self.offset);
+
+    if let Some(message) = &self.message {
+      builder.set_message(message);
+    }
+
+    if let Some(help) = &self.help {
+      builder.set_help(help);
+    }
+
+    if let Some(note) = &self.note {
+      builder.set_note(note);
+    }
+
+    for label in &self.labels {
+      let mut ariadne_label = Label::new(label.range.clone());
+
+      if let Some(message) = &label.message {
+        ariadne_label = ariadne_label.with_message(message);
+      }
+
+      builder.add_label(ariadne_label);
+    }
+
+    builder.finish()
+  }
+
+  /// Serializes the diagnostic for the file at `path`, `content` being the
+  /// source the offsets point to.
+  pub fn to_json(&self, path: &str, content: &str) -> serde_json::Value {
+    let severity = match self.kind {
+      ReportKind::Error => "error",
+      ReportKind::Warning => "warning",
+      ReportKind::Advice => "advice",
+      ReportKind::Custom(name, _) => name
+    };
+
+    let labels = self
+      .labels
+      .iter()
+      .map(|label| {
+        json!({
+          "message": label.message,
+          "range": range_to_json(&label.range, content)
+        })
+      })
+      .collect::<Vec<_>>();
+
+    // the range of the first label is the most precise location, the offset of
+    // the report is used for the reports without labels.
+    let range = self
+      .labels
+      .first()
+      .map(|label| label.range.clone())
+      .unwrap_or(self.offset..self.offset);
+
+    json!({
+      "severity": severity,
+      "message": self.message,
+      "file": path,
+      "range": range_to_json(&range, content),
+      "labels": labels,
+      "help": self.help,
+      "note": self.note
+    })
+  }
+}
+
+fn range_to_json(range: &Range<usize>, content: &str) -> serde_json::Value {
+  let line_index = LineIndex::new(content);
+  let (start_line, start_column) = line_index.get_line_and_column(content, range.start);
+  let (end_line, end_column) = line_index.get_line_and_column(content, range.end);
+
+  json!({
+    "start": range.start,
+    "end": range.end,
+    "start_line": start_line,
+    "start_column": start_column,
+    "end_line": end_line,
+    "end_column": end_column
+  })
+}
+
+pub struct DiagnosticLabel {
+  pub range: Range<usize>,
+  pub message: Option<String>
+}
+
+impl DiagnosticLabel {
+  pub fn new(range: Range<usize>) -> Self {
+    Self {
+      range,
+      message: None
+    }
+  }
+
+  pub fn with_message<M: ToString>(mut self, message: M) -> Self {
+    self.message = Some(message.to_string());
+    self
+  }
+}
@@ -1,70 +1,195 @@
-use core::panic;
+use std::path::PathBuf;
 
-use ariadne::{Report, Source};
+use ariadne::Source;
 
+use super::Diagnostic;
 use crate::ast::{Span, SpanManager};
-use crate::preprocessor::types::PreprocessorOutput;
+use crate::error::CompileError;
+use crate::preprocessor::offset_map::OffsetMap;
+use crate::preprocessor::types::{PreprocessorOutput, ProcessedFile};
 
 pub struct ReportManager {
-  reports: Vec<(Report, Span)>
+  reports: Vec<(Diagnostic, Span)>,
+
+  /// The number of errors and warnings pushed since the manager was created,
+  /// they are not reset when the reports are consumed.
+  pub error_count: usize,
+  pub warning_count: usize,
+
+  /// Makes any reported warning fail the compilation
+  pub warnings_as_errors: bool,
+
+  pub message_format: MessageFormat,
+
+  /// When set, the consumed reports are moved into `collected_reports`
+  /// instead of being printed. Used by the language server which publishes
+  /// them to the editor. The offsets of the collected reports point to the
+  /// files the user wrote.
+  pub collect_reports: bool,
+  pub collected_reports: Vec<(Diagnostic, Span)>
 }
 
 impl ReportManager {
   pub fn new() -> Self {
     Self {
-      reports: Vec::new()
+      reports: Vec::new(),
+      error_count: 0,
+      warning_count: 0,
+      warnings_as_errors: false,
+      message_format: MessageFormat::Human,
+      collect_reports: false,
+      collected_reports: Vec::new()
     }
   }
 
-  pub fn push(&mut self, report: Report, span: Span) {
+  pub fn push(&mut self, report: Diagnostic, span: Span) {
+    self.count(&report);
     self.reports.push((report, span));
   }
 
-  pub fn push_many(&mut self, reports: Vec<(Report, Span)>) {
+  /// Prints or collects a report whose offsets already point to the file the
+  /// user wrote, like the reports of the preprocessor.
+  pub fn push_original(
+    &mut self, report: Diagnostic, span: Span, path: &str, file: &ProcessedFile
+  ) -> Result<(), CompileError> {
+    self.count(&report);
+    self.emit_report(report, span, path, file)
+  }
+
+  fn count(&mut self, report: &Diagnostic) {
+    if report.is_error() {
+      self.error_count += 1;
+    } else if report.is_warning() {
+      self.warning_count += 1;
+    }
+  }
+
+  pub fn push_many(&mut self, reports: Vec<(Diagnostic, Span)>) {
     for pair in reports {
       self.push(pair.0, pair.1);
     }
   }
 
+  /// Returns whether the reported diagnostics should stop the compilation
+  /// before any code is emitted.
+  pub fn has_errors(&self) -> bool {
+    self.error_count > 0 || (self.warnings_as_errors && self.warning_count > 0)
+  }
+
   pub fn flush_reports(&mut self) {
     self.reports.clear();
   }
 
-  pub fn consume(&mut self, content: &str) {
-    for (report, _) in &self.reports {
-      if let Err(err) = report.print(Source::from(&content)) {
-        panic!("{}", err);
-      }
+  pub fn consume(&mut self, path: &str, file: &ProcessedFile) -> Result<(), CompileError> {
+    let reports = std::mem::take(&mut self.reports);
+
+    for (report, span) in reports {
+      self.consume_report(report, span, path, file)?;
     }
 
-    self.flush_reports();
+    Ok(())
   }
 
-  pub fn consume_multiple_sources<'a>(
-    &mut self, span_manager: &'a mut SpanManager, preprocessor_output: &PreprocessorOutput
-  ) {
-    for (report, span) in &self.reports {
-      let source: &String = span_manager.get_source(span);
-      let source_content = if let Some(s) = preprocessor_output.source_files_content.get(source) {
-        s.content.borrow()
-      } else {
-        let mut output = None;
-
-        for (_name, value) in preprocessor_output.dependencies_files_content.iter() {
-          if let Some(s) = value.get(source) {
-            output = Some(s.content.borrow())
-          }
-        }
-
-        match output {
-          Some(s) => s,
-          None => panic!("attempt at reporting error but source {source} from span {} does not exist in neither the dependencies and the sources", span.0)
-        }
+  pub fn consume_multiple_sources(
+    &mut self, span_manager: &mut SpanManager, preprocessor_output: &PreprocessorOutput
+  ) -> Result<(), CompileError> {
+    let reports = std::mem::take(&mut self.reports);
+
+    for (report, span) in reports {
+      let source: &String = span_manager.get_source(&span);
+      let Some(file) = preprocessor_output.get_file(source) else {
+        return Err(CompileError::UnknownReportSource(source.clone()));
       };
 
-      if let Err(err) = report.print(Source::from(source_content.as_str())) {
-        panic!("{}", err);
+      self.consume_report(report, span, source, file)?;
+    }
+
+    Ok(())
+  }
+
+  /// Prints or collects the report once its offsets are translated to the
+  /// file the user wrote.
+  fn consume_report(
+    &mut self, report: Diagnostic, span: Span, path: &str, file: &ProcessedFile
+  ) -> Result<(), CompileError> {
+    let report = translate_report(report, &file.offset_map.borrow());
+
+    self.emit_report(report, span, path, file)
+  }
+
+  fn emit_report(
+    &mut self, report: Diagnostic, span: Span, path: &str, file: &ProcessedFile
+  ) -> Result<(), CompileError> {
+    if self.collect_reports {
+      self.collected_reports.push((report, span));
+
+      return Ok(());
+    }
+
+    let included_file = report.included_file.clone();
+    let (path, content) = match &included_file {
+      Some(included_file) => (included_file.path.as_str(), &included_file.content),
+      None => (path, &file.original_content)
+    };
+
+    match self.message_format {
+      MessageFormat::Human => report
+        .to_report()
+        .print(Source::from(content))
+        .map_err(|error| CompileError::Io {
+          path: PathBuf::from(path),
+          error
+        }),
+      MessageFormat::Json => {
+        println!("{}", report.to_json(path, content));
+
+        Ok(())
       }
     }
   }
 }
+
+/// Moves the offsets of the report from the preprocessed content to the
+/// original content, or to the included file the reported code comes from,
+/// and adds a note if the reported code comes from a macro.
+fn translate_report(mut report: Diagnostic, offset_map: &OffsetMap) -> Diagnostic {
+  let location = report
+    .labels
+    .first()
+    .map(|label| label.range.clone())
+    .unwrap_or(report.offset..report.offset);
+  let location = offset_map.get_original_range(location);
+  let expanded_from = location.expanded_from;
+  report.included_file = location.file;
+
+  report.offset = offset_map
+    .get_original_range(report.offset..report.offset)
+    .range
+    .start;
+
+  for label in &mut report.labels {
+    label.range = offset_map.get_original_range(label.range.clone()).range;
+  }
+
+  if let Some(macro_name) = expanded_from {
+    let expansion_note = format!("expanded from macro {macro_name}!");
+
+    report.note = Some(match report.note {
+      Some(note) => format!("{note}\n{expansion_note}"),
+      None => expansion_note
+    });
+  }
+
+  report
+}
+
+/// How the diagnostics are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+  /// Pretty reports with the source code of the diagnostics
+  Human,
+
+  /// One JSON object per diagnostic and per line, for the tools that parse
+  /// the output of the compiler.
+  Json
+}
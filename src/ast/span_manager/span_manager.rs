@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 
 use super::SpanMaker;
@@ -10,44 +10,57 @@ pub type FilePath = String;
 pub type FilePathRef = usize;
 
 pub struct SpanRange {
-  #[allow(dead_code)]
   source_ref: FilePathRef,
 
   left: usize,
   right: usize
 }
 
+#[derive(Default)]
 pub struct SpanManager {
   /// Stores the path for the sources
   pub paths: Vec<FilePath>,
 
-  pub spans: Vec<SpanRange>
+  pub spans: Vec<SpanRange>,
+
+  /// The paths and the spans of the removed sources, the next ones take their
+  /// place so the manager doesn't grow between two compilations.
+  free_paths: Vec<FilePathRef>,
+  free_spans: Vec<usize>
 }
 
 impl SpanManager {
   pub fn new() -> Self {
     Self {
       paths: Vec::new(),
-      spans: Vec::new()
+      spans: Vec::new(),
+      free_paths: Vec::new(),
+      free_spans: Vec::new()
     }
   }
 
   pub fn new_span(&mut self, source_ref: FilePathRef, left: usize, right: usize) -> Span {
-    let i = self.spans.len();
-
-    self.spans.push(SpanRange {
+    let span = SpanRange {
       source_ref,
       left,
       right
-    });
+    };
+
+    if let Some(i) = self.free_spans.pop() {
+      self.spans[i] = span;
+
+      return Span(i);
+    }
+
+    let i = self.spans.len();
+
+    self.spans.push(span);
 
     Span(i)
   }
 
   pub fn add_source(&mut self, source: FilePath) -> SpanMaker {
-    let source_ref = self.paths.len();
-
-    self.paths.push(source);
+    let source_ref = self.push_path(source);
 
     SpanMaker {
       parent: self,
@@ -57,9 +70,7 @@ impl SpanManager {
   }
 
   pub fn add_fake_source(&mut self) -> SpanMaker {
-    let source_ref = self.paths.len();
-
-    self.paths.push(String::new());
+    let source_ref = self.push_path(String::new());
 
     SpanMaker {
       parent: self,
@@ -68,6 +79,38 @@ impl SpanManager {
     }
   }
 
+  fn push_path(&mut self, source: FilePath) -> FilePathRef {
+    if let Some(source_ref) = self.free_paths.pop() {
+      self.paths[source_ref] = source;
+
+      return source_ref;
+    }
+
+    self.paths.push(source);
+
+    self.paths.len() - 1
+  }
+
+  /// Removes every source but the given ones along with their spans, which
+  /// must no longer be used.
+  pub fn retain_sources(&mut self, sources: &HashSet<FilePathRef>) {
+    self.free_paths = (0..self.paths.len())
+      .filter(|source_ref| !sources.contains(source_ref))
+      .collect();
+
+    for source_ref in &self.free_paths {
+      self.paths[*source_ref].clear();
+    }
+
+    self.free_spans = self
+      .spans
+      .iter()
+      .enumerate()
+      .filter(|(_, span)| !sources.contains(&span.source_ref))
+      .map(|(i, _)| i)
+      .collect();
+  }
+
   pub fn get_left(&self, source_ref: Span) -> usize {
     self.spans[source_ref.0].left
   }
@@ -86,3 +129,34 @@ impl SpanManager {
     &self.paths[span.source_ref]
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reuses_the_place_of_the_removed_sources() {
+    let mut manager = SpanManager::new();
+
+    let mut kept_source = manager.add_source(String::from("a.wss"));
+    let kept = kept_source.span(1, 2, "test");
+    let kept_source = kept_source.source_ref;
+
+    let mut removed_source = manager.add_source(String::from("b.wss"));
+    removed_source.span(3, 4, "test");
+    removed_source.span(5, 6, "test");
+
+    manager.retain_sources(&HashSet::from([kept_source]));
+
+    let mut added = manager.add_source(String::from("c.wss"));
+    let added_span = added.span(7, 8, "test");
+    added.span(9, 10, "test");
+
+    assert_eq!(manager.paths.len(), 2);
+    assert_eq!(manager.spans.len(), 3);
+    assert_eq!(manager.get_source(&kept), "a.wss");
+    assert_eq!(manager.get_range(kept), 1..2);
+    assert_eq!(manager.get_source(&added_span), "c.wss");
+    assert_eq!(manager.get_range(added_span), 7..8);
+  }
+}
@@ -7,7 +7,15 @@ pub enum Statement {
   ClassDeclaration(ClassDeclaration),
   StructDeclaration(StructDeclaration),
   EnumDeclaration(EnumDeclaration),
-  Annotation(Annotation)
+  Annotation(Annotation),
+  NamespaceDeclaration(NamespaceDeclaration),
+  ImportDeclaration(ImportDeclaration),
+  ImportedClassDeclaration(ImportedClassDeclaration),
+  ImportedFunctionDeclaration(ImportedFunctionDeclaration),
+
+  /// A statement that could not be parsed, the syntax error was recorded
+  /// by the parser.
+  Error
 }
 
 impl visitor::Visited for Statement {
@@ -18,7 +26,12 @@ impl visitor::Visited for Statement {
       Statement::ClassDeclaration(x) => x.accept(visitor),
       Statement::StructDeclaration(x) => x.accept(visitor),
       Statement::EnumDeclaration(x) => x.accept(visitor),
-      Statement::Annotation(x) => x.accept(visitor)
+      Statement::Annotation(x) => x.accept(visitor),
+      Statement::NamespaceDeclaration(x) => x.accept(visitor),
+      Statement::ImportDeclaration(x) => x.accept(visitor),
+      Statement::ImportedClassDeclaration(x) => x.accept(visitor),
+      Statement::ImportedFunctionDeclaration(x) => x.accept(visitor),
+      Statement::Error => {}
     }
   }
 }
@@ -31,7 +44,12 @@ impl Codegen for Statement {
       Statement::ClassDeclaration(x) => x.emit(context, f),
       Statement::StructDeclaration(x) => x.emit(context, f),
       Statement::EnumDeclaration(x) => x.emit(context, f),
-      Statement::Annotation(x) => x.emit(context, f)
+      Statement::Annotation(x) => x.emit(context, f),
+      Statement::NamespaceDeclaration(x) => x.emit(context, f),
+      Statement::ImportDeclaration(x) => x.emit(context, f),
+      Statement::ImportedClassDeclaration(x) => x.emit(context, f),
+      Statement::ImportedFunctionDeclaration(x) => x.emit(context, f),
+      Statement::Error => Ok(())
     }
   }
 }
@@ -1,3 +1,4 @@
+use super::codegen::span_markers::write_span_marker;
 use super::visitor::Visited;
 use super::*;
 
@@ -30,7 +31,12 @@ impl Codegen for StructDeclaration {
   fn emit(&self, _: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
     use std::io::Write as IoWrite;
 
-    writeln!(f, "struct {} {{", self.name)?;
+    write_span_marker(f, self.span_name)?;
+
+    match &self.context.borrow().mangled_accessor {
+      Some(mangled_accessor) => writeln!(f, "struct {} {{", mangled_accessor)?,
+      None => writeln!(f, "struct {} {{", self.name)?
+    };
 
     for statement in &self.body_statements {
       statement.emit(&self.context.borrow(), f)?;
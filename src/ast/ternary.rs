@@ -0,0 +1,120 @@
+use super::visitor::Visited;
+use super::*;
+
+/// A `condition ? consequence : alternative` expression. WitcherScript has no
+/// such operator, so the value is computed in a temporary variable of the
+/// function by an if/else emitted before the statement that uses it.
+#[derive(Debug)]
+pub struct Ternary {
+  pub condition: Rc<Expression>,
+  pub consequence: Rc<Expression>,
+  pub alternative: Rc<Expression>,
+  pub span: Span,
+
+  /// The name of the temporary variable holding the value, it is set by the
+  /// `TernaryVisitor`.
+  pub variable_name: RefCell<Option<String>>
+}
+
+impl Ternary {
+  pub fn new(
+    condition: Rc<Expression>, consequence: Rc<Expression>, alternative: Rc<Expression>, span: Span
+  ) -> Self {
+    Self {
+      condition,
+      consequence,
+      alternative,
+      span,
+      variable_name: RefCell::new(None)
+    }
+  }
+
+  /// Returns the name of the type both branches result in, or the type of the
+  /// first branch that is cast if the types could not be deduced.
+  pub fn get_type_name(&self) -> Option<String> {
+    [&self.consequence, &self.alternative]
+      .iter()
+      .find_map(|branch| match &*branch.infered_type_name.borrow() {
+        inference::Type::Unknown | inference::Type::Void | inference::Type::Null => None,
+        t => Some(t.to_string())
+      })
+      .or_else(|| {
+        [&self.consequence, &self.alternative]
+          .iter()
+          .find_map(|branch| match &branch.body {
+            ExpressionBody::Cast(t, _) => Some(t.clone()),
+            _ => None
+          })
+      })
+  }
+
+  /// Emits the if/else statements that compute the value of the ternaries
+  /// found in the expressions, they must be emitted right before the
+  /// statement the expressions belong to.
+  pub fn emit_hoisted(
+    expressions: &[&Expression], context: &Context, f: &mut Vec<u8>
+  ) -> Result<(), std::io::Error> {
+    let mut ternaries = Vec::new();
+
+    for expression in expressions {
+      expression.collect_ternaries(&mut ternaries);
+    }
+
+    for ternary in ternaries {
+      ternary.emit_assignment(context, f)?;
+    }
+
+    Ok(())
+  }
+
+  fn emit_assignment(&self, context: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    use std::io::Write as IoWrite;
+
+    let variable_name = self.get_variable_name()?;
+
+    Self::emit_hoisted(&[&self.condition], context, f)?;
+    write!(f, "if (")?;
+    self.condition.emit(context, f)?;
+    writeln!(f, ") {{")?;
+
+    // the nested ternaries are computed only in the branch that uses them
+    Self::emit_hoisted(&[&self.consequence], context, f)?;
+    write!(f, "{variable_name} = ")?;
+    self.consequence.emit(context, f)?;
+    writeln!(f, ";")?;
+    writeln!(f, "}}")?;
+    writeln!(f, "else {{")?;
+    Self::emit_hoisted(&[&self.alternative], context, f)?;
+    write!(f, "{variable_name} = ")?;
+    self.alternative.emit(context, f)?;
+    writeln!(f, ";")?;
+    writeln!(f, "}}")?;
+
+    Ok(())
+  }
+
+  fn get_variable_name(&self) -> Result<String, std::io::Error> {
+    self.variable_name.borrow().clone().ok_or_else(|| {
+      std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "A ternary expression was not given a temporary variable."
+      )
+    })
+  }
+}
+
+impl Visited for Ternary {
+  fn accept<T: visitor::Visitor>(&self, visitor: &mut T) {
+    self.condition.accept(visitor);
+    self.consequence.accept(visitor);
+    self.alternative.accept(visitor);
+  }
+}
+
+impl Codegen for Ternary {
+  fn emit(&self, _: &Context, f: &mut Vec<u8>) -> Result<(), std::io::Error> {
+    use std::io::Write as IoWrite;
+
+    write!(f, "{}", self.get_variable_name()?)
+  }
+}
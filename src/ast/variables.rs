@@ -90,6 +90,7 @@ impl Codegen for VariableDeclaration {
     use std::io::Write as IoWrite;
     match context.context_type {
       ContextType::Global
+      | ContextType::Namespace { .. }
       | ContextType::ClassOrStruct
       | ContextType::State {
         parent_class_name: _
@@ -117,7 +118,10 @@ impl Codegen for VariableDeclaration {
             names: _,
             following_expression: _
           } => {
-            panic!("The compiler does not support implicit types for class attributes, please write the types of your attributes explicitly.");
+            return Err(std::io::Error::new(
+              std::io::ErrorKind::InvalidInput,
+              "The compiler does not support implicit types for class attributes, please write the types of your attributes explicitly."
+            ));
           }
         };
       }
@@ -0,0 +1,59 @@
+use std::rc::Rc;
+
+use crate::ast::codegen::type_inference::InferedType;
+use crate::ast::inference::Type;
+use crate::ast::{Expression, ExpressionBody, FunctionCall};
+
+/// Clears what the analysis stored in the nodes of an AST and in their
+/// contexts, so the AST of a file that did not change since the previous
+/// compilation can be analyzed again without being parsed again.
+pub struct AnalysisResetVisitor;
+
+impl super::Visitor for AnalysisResetVisitor {
+  fn visitor_type(&self) -> super::VisitorType {
+    super::VisitorType::AnalysisResetVisitor
+  }
+
+  fn visit_class_declaration(&mut self, node: &crate::ast::ClassDeclaration) {
+    node.context.borrow_mut().clear_analysis();
+  }
+
+  fn visit_function_declaration(&mut self, node: &crate::ast::FunctionDeclaration) {
+    node.context.borrow_mut().clear_analysis();
+  }
+
+  fn visit_struct_declaration(&mut self, node: &crate::ast::StructDeclaration) {
+    node.context.borrow_mut().clear_analysis();
+  }
+
+  fn visit_namespace_declaration(&mut self, node: &crate::ast::NamespaceDeclaration) {
+    node.context.borrow_mut().clear_analysis();
+  }
+
+  fn visit_imported_class_declaration(&mut self, node: &crate::ast::ImportedClassDeclaration) {
+    let mut context = node.context.borrow_mut();
+
+    // the context building visitor creates the contexts of the imported
+    // methods again
+    context.clear_analysis();
+    context.children_contexts.clear();
+  }
+
+  fn visit_function_call(&mut self, node: &FunctionCall) {
+    node.mangled_accessor.replace(None);
+    node.infered_function_type.replace(None);
+  }
+
+  fn visit_lambda(&mut self, node: &crate::ast::Lambda) {
+    node.mangled_accessor.replace(None);
+    node.captured_variables.replace(Vec::new());
+  }
+
+  fn visit_expression(&mut self, node: &Expression) {
+    node.set_infered_type(Type::Unknown, Rc::new(InferedType::Unknown));
+
+    if let ExpressionBody::Ternary(ternary) = &node.body {
+      ternary.variable_name.replace(None);
+    }
+  }
+}
@@ -1,16 +1,45 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::ast::codegen::context::Context;
+use crate::ast::codegen::context::{Context, ContextType};
 use crate::ast::visitor::Visited;
+use crate::ast::{get_mangled_name, get_symbol_path, ImportedClassBodyStatement};
 
 pub struct ContextBuildingVisitor {
   pub current_context: Rc<RefCell<Context>>
 }
 
+impl ContextBuildingVisitor {
+  /// Gives the declaration of a namespace the name it is emitted under, and
+  /// registers it so the other files can import it.
+  fn register_namespaced_declaration(&self, context: &Rc<RefCell<Context>>, name: &str) {
+    let Some(path) = self.current_context.borrow().get_namespace_path().cloned() else {
+      return;
+    };
+
+    // the declarations of the libraries already have a mangled name
+    let emitted_name = {
+      let mut context = context.borrow_mut();
+
+      if !context.is_library {
+        context.mangled_accessor = Some(get_mangled_name(&path, name));
+      }
+
+      context.mangled_accessor.clone().unwrap_or_default()
+    };
+
+    Context::register_namespaced_symbol(
+      &self.current_context,
+      get_symbol_path(&path, name),
+      emitted_name
+    );
+  }
+}
+
 impl super::Visitor for ContextBuildingVisitor {
   fn visit_function_declaration(&mut self, node: &crate::ast::FunctionDeclaration) {
     Context::set_parent_context(&node.context, &self.current_context);
+    self.register_namespaced_declaration(&node.context, &node.name);
 
     // then make a new context building visitor for the context of the
     // FunctionDeclaration node.
@@ -22,7 +51,9 @@ impl super::Visitor for ContextBuildingVisitor {
   }
 
   fn visit_class_declaration(&mut self, node: &crate::ast::ClassDeclaration) {
+    node.context.borrow_mut().extended_class_name = node.extended_class_name.clone();
     Context::set_parent_context(&node.context, &self.current_context);
+    self.register_namespaced_declaration(&node.context, &node.name);
 
     // then make a new context building visitor for the context of the
     // ClassDeclaration node.
@@ -35,6 +66,7 @@ impl super::Visitor for ContextBuildingVisitor {
 
   fn visit_struct_declaration(&mut self, node: &crate::ast::StructDeclaration) {
     Context::set_parent_context(&node.context, &self.current_context);
+    self.register_namespaced_declaration(&node.context, &node.name);
 
     // then make a new context building visitor for the context of the
     // StructDeclaration node.
@@ -45,6 +77,56 @@ impl super::Visitor for ContextBuildingVisitor {
     node.body_statements.accept(&mut new_context_visitor);
   }
 
+  fn visit_imported_class_declaration(&mut self, node: &crate::ast::ImportedClassDeclaration) {
+    // the classes of the game are never renamed
+    node.context.borrow_mut().extended_class_name = node.extended_class_name.clone();
+    Context::set_parent_context(&node.context, &self.current_context);
+
+    // the imported methods have no body, their contexts only tell the class
+    // declares them.
+    for statement in &node.body_statements {
+      if let ImportedClassBodyStatement::Method(method) = statement {
+        let method_context = Rc::new(RefCell::new(Context::new(
+          &format!("method: {}", method.name),
+          None,
+          ContextType::Function
+        )));
+
+        Context::set_parent_context(&method_context, &node.context);
+      }
+    }
+  }
+
+  fn visit_namespace_declaration(&mut self, node: &crate::ast::NamespaceDeclaration) {
+    // a namespace in another namespace adds its path to the one of its parent
+    let mut path = self
+      .current_context
+      .borrow()
+      .get_namespace_path()
+      .cloned()
+      .unwrap_or_default();
+    path.extend(node.path.iter().cloned());
+
+    node.context.borrow_mut().context_type = ContextType::Namespace { path };
+    Context::set_parent_context(&node.context, &self.current_context);
+
+    // then make a new context building visitor for the context of the
+    // NamespaceDeclaration node.
+    let mut new_context_visitor = Self {
+      current_context: node.context.clone()
+    };
+
+    node.statements.accept(&mut new_context_visitor);
+  }
+
+  fn visit_import_declaration(&mut self, node: &crate::ast::ImportDeclaration) {
+    let mut context = self.current_context.borrow_mut();
+
+    for (name, symbol_path) in node.get_symbol_paths() {
+      context.imports.insert(name.clone(), symbol_path);
+    }
+  }
+
   fn visitor_type(&self) -> super::VisitorType {
     super::VisitorType::ContextBuildingVisitor
   }
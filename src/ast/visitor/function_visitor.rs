@@ -27,7 +27,8 @@ impl super::Visitor for FunctionVisitor<'_> {
   fn visit_function_declaration_parameter(
     &mut self, node: &crate::ast::FunctionDeclarationParameter
   ) {
-    let type_string_representation = node.typed_identifier.type_declaration.to_string();
+    let type_string_representation = Context::get_ref(&self.current_context)
+      .resolve_qualified_name(&node.typed_identifier.type_declaration.to_string());
 
     for parameter_name in &node.typed_identifier.names {
       self
@@ -21,12 +21,28 @@ pub use lambda_declaration_visitor::LambdaDeclarationVisitor;
 mod type_inference_visitor;
 pub use type_inference_visitor::*;
 
+mod symbols_visitor;
+pub use symbols_visitor::SymbolsVisitor;
+
+mod namespace_visitor;
+pub use namespace_visitor::NamespaceVisitor;
+
+mod ternary_visitor;
+pub use ternary_visitor::TernaryVisitor;
+
+mod analysis_reset_visitor;
+pub use analysis_reset_visitor::AnalysisResetVisitor;
+
 pub mod implementations;
 
 pub trait Visitor {
   fn visit_function_declaration(&mut self, _: &FunctionDeclaration) {}
   fn visit_class_declaration(&mut self, _: &ClassDeclaration) {}
   fn visit_struct_declaration(&mut self, _: &StructDeclaration) {}
+  fn visit_namespace_declaration(&mut self, _: &NamespaceDeclaration) {}
+  fn visit_import_declaration(&mut self, _: &ImportDeclaration) {}
+  fn visit_imported_class_declaration(&mut self, _: &ImportedClassDeclaration) {}
+  fn visit_imported_function_declaration(&mut self, _: &ImportedFunctionDeclaration) {}
   fn visit_generic_function_call(&mut self, _: &FunctionCall) {}
   fn visit_function_call(&mut self, _: &FunctionCall) {}
   fn visit_generic_variable_declaration(&mut self, _: &TypeDeclaration) {}
@@ -34,7 +50,9 @@ pub trait Visitor {
   fn visit_generic_class_instantiation(&mut self, _: &ClassInstantiation) {}
   fn visit_lambda_declaration(&mut self, _: &LambdaDeclaration) {}
   fn visit_lambda(&mut self, _: &Lambda) {}
+  fn visit_for_in_statement(&mut self, _: &ForInStatement) {}
   fn visit_expression(&mut self, _: &Expression) {}
+  fn visit_function_body_statement(&mut self, _: &FunctionBodyStatement) {}
   fn visit_function_declaration_parameter(&mut self, _: &FunctionDeclarationParameter) {}
   fn register_variable_declaration(&mut self, _: Rc<TypedIdentifier>) {}
 
@@ -53,5 +71,9 @@ pub enum VisitorType {
   VariableDeclarationVisitor,
   LambdaDeclarationVisitor,
   ClosureExpressionVisitor,
-  TypeInferenceVisitor
+  TypeInferenceVisitor,
+  SymbolsVisitor,
+  NamespaceVisitor,
+  TernaryVisitor,
+  AnalysisResetVisitor
 }
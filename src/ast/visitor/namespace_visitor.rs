@@ -0,0 +1,158 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use ariadne::ReportKind;
+
+use crate::ast::codegen::context::Context;
+use crate::ast::{
+  Diagnostic, DiagnosticLabel, Expression, ExpressionBody, FunctionCall, ImportDeclaration,
+  OperationCode, ReportManager, SpanManager
+};
+
+/// Replaces the calls of the functions declared in namespaces with the names
+/// they are emitted under, and reports the imports of symbols that don't
+/// exist. It must run once the contexts of every file are built.
+pub struct NamespaceVisitor<'a> {
+  pub current_context: Rc<RefCell<Context>>,
+  pub report_manager: &'a mut ReportManager,
+  pub span_manager: &'a mut SpanManager,
+
+  /// The calls on the right side of a `.`, they are method calls
+  method_calls: HashSet<*const FunctionCall>
+}
+
+impl<'a> NamespaceVisitor<'a> {
+  pub fn new(
+    current_context: Rc<RefCell<Context>>, report_manager: &'a mut ReportManager,
+    span_manager: &'a mut SpanManager
+  ) -> Self {
+    Self {
+      current_context,
+      report_manager,
+      span_manager,
+      method_calls: HashSet::new()
+    }
+  }
+
+  /// Returns whether the enclosing class, or one of the classes it extends,
+  /// declares a method with the given name, a call to it without `this.`
+  /// must not be replaced. The classes of the game that are not imported are
+  /// unknown, the walk stops at them.
+  fn is_method_of_current_class(&self, name: &str) -> bool {
+    let method_name = format!("method: {name}");
+    let mut context = Some(self.current_context.clone());
+    let mut class_context = None;
+
+    while let Some(current) = context {
+      if Context::get_ref(&current).get_class_name().is_some() {
+        class_context = Some(current);
+
+        break;
+      }
+
+      context = Context::get_ref(&current).parent_context.clone();
+    }
+
+    // the names of the classes already visited, in case of a cycle
+    let mut visited = HashSet::new();
+
+    while let Some(class) = class_context {
+      let current = Context::get_ref(&class);
+
+      if !visited.insert(current.name.clone()) {
+        return false;
+      }
+
+      if current
+        .children_contexts
+        .iter()
+        .any(|child| Context::get_ref(child).name == method_name)
+      {
+        return true;
+      }
+
+      class_context = current
+        .extended_class_name
+        .as_ref()
+        .and_then(|extended| Context::find_global_class_declaration(&class, extended));
+    }
+
+    false
+  }
+}
+
+impl super::Visitor for NamespaceVisitor<'_> {
+  fn visitor_type(&self) -> super::VisitorType {
+    super::VisitorType::NamespaceVisitor
+  }
+
+  /// Update the current context with the latest context met in the AST
+  fn visit_class_declaration(&mut self, node: &crate::ast::ClassDeclaration) {
+    self.current_context = node.context.clone();
+  }
+
+  /// Update the current context with the latest context met in the AST
+  fn visit_function_declaration(&mut self, node: &crate::ast::FunctionDeclaration) {
+    self.current_context = node.context.clone();
+  }
+
+  /// Update the current context with the latest context met in the AST
+  fn visit_struct_declaration(&mut self, node: &crate::ast::StructDeclaration) {
+    self.current_context = node.context.clone();
+  }
+
+  /// Update the current context with the latest context met in the AST
+  fn visit_namespace_declaration(&mut self, node: &crate::ast::NamespaceDeclaration) {
+    self.current_context = node.context.clone();
+  }
+
+  fn visit_import_declaration(&mut self, node: &ImportDeclaration) {
+    let context = Context::get_ref(&self.current_context);
+
+    for (name, symbol_path) in node.get_symbol_paths() {
+      if context.find_namespaced_symbol(&symbol_path).is_some() {
+        continue;
+      }
+
+      let span = node.span;
+      let namespace = node.path.join(".");
+
+      self.report_manager.push(
+        Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
+          .with_message(format!("Unresolved import {symbol_path}"))
+          .with_label(
+            DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(format!(
+              "no class, struct or function {name} in the namespace {namespace}"
+            ))
+          ),
+        span
+      );
+    }
+  }
+
+  fn visit_expression(&mut self, node: &Expression) {
+    if let ExpressionBody::Operation(_, OperationCode::Nesting, right) = &node.body {
+      if let ExpressionBody::FunctionCall(call) = &right.body {
+        self.method_calls.insert(call as *const FunctionCall);
+      }
+    }
+  }
+
+  fn visit_function_call(&mut self, node: &FunctionCall) {
+    if self.method_calls.contains(&(node as *const FunctionCall)) {
+      return;
+    }
+
+    let name = node.get_function_name();
+    if node.mangled_accessor.borrow().is_some() || self.is_method_of_current_class(&name) {
+      return;
+    }
+
+    let emitted_name = Context::get_ref(&self.current_context).resolve_namespaced_name(&name);
+
+    if emitted_name.is_some() {
+      node.mangled_accessor.replace(emitted_name);
+    }
+  }
+}
@@ -0,0 +1,69 @@
+use crate::ast::inference::Type;
+use crate::ast::{
+  ClassDeclaration, Expression, ExpressionBody, FunctionDeclaration, Span, StructDeclaration
+};
+
+/// Collects the declarations and the typed expressions of a program, so the
+/// language server can find what is under the cursor.
+pub struct SymbolsVisitor {
+  /// The name of the declared classes, structs and functions along with the
+  /// span of their names.
+  pub declarations: Vec<(String, Span)>,
+
+  pub expressions: Vec<ExpressionSymbol>
+}
+
+pub struct ExpressionSymbol {
+  pub span: Span,
+
+  /// The name of the variable, for the expressions that are identifiers
+  pub identifier: Option<String>,
+
+  pub infered_type: Type
+}
+
+impl SymbolsVisitor {
+  pub fn new() -> Self {
+    Self {
+      declarations: Vec::new(),
+      expressions: Vec::new()
+    }
+  }
+}
+
+impl super::Visitor for SymbolsVisitor {
+  fn visitor_type(&self) -> super::VisitorType {
+    super::VisitorType::SymbolsVisitor
+  }
+
+  fn visit_class_declaration(&mut self, node: &ClassDeclaration) {
+    self.declarations.push((node.name.clone(), node.span_name));
+  }
+
+  fn visit_struct_declaration(&mut self, node: &StructDeclaration) {
+    self.declarations.push((node.name.clone(), node.span_name));
+  }
+
+  fn visit_function_declaration(&mut self, node: &FunctionDeclaration) {
+    self.declarations.push((node.name.clone(), node.span_name));
+  }
+
+  fn visit_expression(&mut self, node: &Expression) {
+    // only the expressions with a span of their own are collected, the others
+    // borrow the span of one of their children.
+    let Some(span) = node.body.get_own_span() else {
+      return;
+    };
+
+    let identifier = match &node.body {
+      ExpressionBody::Identifier(identifier) => Some(identifier.text.clone()),
+      _ => None
+    };
+
+    self.expressions.push(ExpressionSymbol {
+      span,
+      identifier,
+      infered_type: node.infered_type_name.borrow().clone()
+    });
+  }
+}
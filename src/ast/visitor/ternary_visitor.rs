@@ -0,0 +1,222 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use ariadne::ReportKind;
+
+use crate::ast::codegen::context::{Context, ContextType};
+use crate::ast::codegen::type_inference::TypeInferenceStore;
+use crate::ast::{
+  Diagnostic, DiagnosticLabel, Expression, ExpressionBody, FunctionBodyStatement, OperationCode,
+  ReportManager, SpanManager, Ternary, TypeDeclaration, TypedIdentifier
+};
+use crate::utils::stable_hash;
+
+/// Gives every ternary expression the temporary variable its value is
+/// computed in, and declares it in the function that uses it. It must run
+/// once the types of the expressions are deduced.
+pub struct TernaryVisitor<'a> {
+  pub current_context: Rc<RefCell<Context>>,
+  pub inference_store: &'a TypeInferenceStore,
+  pub report_manager: &'a mut ReportManager,
+  pub span_manager: &'a mut SpanManager,
+
+  /// The ternaries that cannot be computed before their statement, along
+  /// with the reason why.
+  unsupported_ternaries: HashMap<*const Ternary, &'static str>,
+
+  /// How many ternaries each function has, by path of its context. The count
+  /// gives the temporary variables names that don't depend on the other
+  /// files of the compilation.
+  ternary_counts: HashMap<String, usize>
+}
+
+impl<'a> TernaryVisitor<'a> {
+  pub fn new(
+    current_context: Rc<RefCell<Context>>, inference_store: &'a TypeInferenceStore,
+    report_manager: &'a mut ReportManager, span_manager: &'a mut SpanManager
+  ) -> Self {
+    Self {
+      current_context,
+      inference_store,
+      report_manager,
+      span_manager,
+      unsupported_ternaries: HashMap::new(),
+      ternary_counts: HashMap::new()
+    }
+  }
+
+  fn report(&mut self, ternary: &Ternary, message: &str, label: &str) {
+    let span = ternary.span;
+
+    self.report_manager.push(
+      Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
+        .with_message(message)
+        .with_label(DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(label)),
+      span
+    );
+  }
+}
+
+impl super::Visitor for TernaryVisitor<'_> {
+  fn visitor_type(&self) -> super::VisitorType {
+    super::VisitorType::TernaryVisitor
+  }
+
+  /// Update the current context with the latest context met in the AST
+  fn visit_class_declaration(&mut self, node: &crate::ast::ClassDeclaration) {
+    self.current_context = node.context.clone();
+  }
+
+  /// Update the current context with the latest context met in the AST
+  fn visit_function_declaration(&mut self, node: &crate::ast::FunctionDeclaration) {
+    self.current_context = node.context.clone();
+  }
+
+  /// Update the current context with the latest context met in the AST
+  fn visit_struct_declaration(&mut self, node: &crate::ast::StructDeclaration) {
+    self.current_context = node.context.clone();
+  }
+
+  /// Update the current context with the latest context met in the AST
+  fn visit_namespace_declaration(&mut self, node: &crate::ast::NamespaceDeclaration) {
+    self.current_context = node.context.clone();
+  }
+
+  fn visit_function_body_statement(&mut self, node: &FunctionBodyStatement) {
+    let mut ternaries = Vec::new();
+
+    for expression in node.get_reevaluated_expressions() {
+      expression.collect_ternaries(&mut ternaries);
+    }
+
+    for ternary in ternaries {
+      self.unsupported_ternaries.insert(
+        ternary as *const Ternary,
+        "A ternary cannot be used in the condition of a loop or of an `else if`"
+      );
+    }
+  }
+
+  fn visit_lambda(&mut self, node: &crate::ast::Lambda) {
+    use super::Visited;
+
+    let mut visitor = LambdaTernariesVisitor {
+      ternaries: Vec::new()
+    };
+
+    node.body_statements.accept(&mut visitor);
+
+    for ternary in visitor.ternaries {
+      self
+        .unsupported_ternaries
+        .insert(ternary, "A ternary cannot be used in a lambda");
+    }
+  }
+
+  fn visit_expression(&mut self, node: &Expression) {
+    // the right side of `&&` and `||` is skipped when the left side decides
+    // the result, while its ternaries would be computed before the statement
+    if let ExpressionBody::Operation(_, OperationCode::BooleanJoin(_), right) = &node.body {
+      let mut ternaries = Vec::new();
+      right.collect_ternaries(&mut ternaries);
+
+      for ternary in ternaries {
+        self
+          .unsupported_ternaries
+          .entry(ternary as *const Ternary)
+          .or_insert("A ternary cannot be used on the right side of `&&` or `||`");
+      }
+    }
+
+    let ExpressionBody::Ternary(ternary) = &node.body else {
+      return;
+    };
+
+    if ternary.variable_name.borrow().is_some() {
+      return;
+    }
+
+    if let Some(reason) = self
+      .unsupported_ternaries
+      .get(&(ternary as *const Ternary))
+      .copied()
+    {
+      self.report(ternary, "Unsupported ternary expression", reason);
+
+      return;
+    }
+
+    if !matches!(
+      Context::get_ref(&self.current_context).context_type,
+      ContextType::Function
+    ) {
+      self.report(
+        ternary,
+        "Unsupported ternary expression",
+        "A ternary can only be used in the body of a function"
+      );
+
+      return;
+    }
+
+    // the errors were already reported by the type inference
+    let _ = node.deduce_type(
+      &self.current_context,
+      &self.inference_store.types,
+      &self.inference_store.types,
+      self.span_manager
+    );
+
+    let Some(type_name) = ternary.get_type_name() else {
+      let span = ternary.span;
+
+      self.report_manager.push(
+        Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
+          .with_message(&"Cannot infer the type of the ternary expression")
+          .with_label(
+            DiagnosticLabel::new(self.span_manager.get_range(span))
+              .with_message(&"The type of the temporary variable holding its value is unknown")
+          )
+          .with_help(&"Cast one of the branches with `as`"),
+        span
+      );
+
+      return;
+    };
+
+    let mut context = self.current_context.borrow_mut();
+    let path = context.get_path();
+    let count = self.ternary_counts.entry(path.clone()).or_default();
+    let variable_name = format!("ternary{}", stable_hash(&[&path, &count.to_string()]));
+    *count += 1;
+
+    context.variable_declarations.push(Rc::new(TypedIdentifier {
+      names: vec![variable_name.clone()],
+      type_declaration: TypeDeclaration::Regular {
+        type_name,
+        generic_type_assignment: None,
+        mangled_accessor: RefCell::new(None)
+      }
+    }));
+
+    ternary.variable_name.replace(Some(variable_name));
+  }
+}
+
+/// Collects the ternaries found in the body of a lambda
+struct LambdaTernariesVisitor {
+  ternaries: Vec<*const Ternary>
+}
+
+impl super::Visitor for LambdaTernariesVisitor {
+  fn visitor_type(&self) -> super::VisitorType {
+    super::VisitorType::TernaryVisitor
+  }
+
+  fn visit_expression(&mut self, node: &Expression) {
+    if let ExpressionBody::Ternary(ternary) = &node.body {
+      self.ternaries.push(ternary as *const Ternary);
+    }
+  }
+}
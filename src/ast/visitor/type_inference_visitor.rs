@@ -2,14 +2,15 @@ use std::borrow::Borrow;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use ariadne::{Label, Report, ReportKind};
+use ariadne::ReportKind;
 
 use crate::ast::codegen::context::{Context, ContextType};
 use crate::ast::codegen::type_inference::{InferedType, TypeInferenceStore};
 use crate::ast::inference::Type;
 use crate::ast::{
-  Expression, FunctionDeclarationParameter, ReportManager, SpanManager, TypeDeclaration,
-  TypedIdentifier
+  Diagnostic, DiagnosticLabel, Expression, FunctionDeclarationParameter,
+  ImportedClassBodyStatement, ImportedClassDeclaration, ImportedFunctionDeclaration, ReportManager,
+  SpanManager, TypeDeclaration, TypedIdentifier
 };
 
 use super::lambda_declaration_visitor::ClosureVisitor;
@@ -44,18 +45,27 @@ impl super::Visitor for CompoundTypesVisitor<'_> {
 
   /// Update the current context with the latest context met in the AST
   fn visit_class_declaration(&mut self, node: &crate::ast::ClassDeclaration) {
+    let (name, extended_class_name) = {
+      let context = Context::get_ref(&node.context);
+      let extended_class_name = node
+        .extended_class_name
+        .as_ref()
+        .map(|extended| context.resolve_qualified_name(extended));
+
+      (context.get_qualified_name(&node.name), extended_class_name)
+    };
+
     let result = self
       .inference_store
-      .register_compound(node.name.clone(), node.extended_class_name.clone());
+      .register_compound(name, extended_class_name, true);
 
     if let Err(reason) = result {
       let span = node.span_name;
 
       self.report_manager.push(
-        Report::build(ReportKind::Error, (), self.span_manager.get_left(span))
+        Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
           .with_message(&"Invalid class definition")
-          .with_label(Label::new(self.span_manager.get_range(span)).with_message(reason))
-          .finish(),
+          .with_label(DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(reason)),
         span
       );
     }
@@ -74,8 +84,20 @@ impl super::Visitor for CompoundTypesVisitor<'_> {
       ContextType::Global
     };
 
-    let parameters =
+    let mut parameters =
       FunctionDeclarationParameter::to_function_infered_parameter_types(&node.parameters);
+    let return_type = {
+      let context = Context::get_ref(&node.context);
+
+      for parameter in &mut parameters {
+        parameter.infered_type = context.resolve_qualified_name(&parameter.infered_type);
+      }
+
+      node
+        .type_declaration
+        .as_ref()
+        .map(|decl| context.resolve_qualified_name(&decl.to_string()))
+    };
 
     // we try to see if the function is inside a struct or class or if
     // it is a global function.
@@ -86,17 +108,30 @@ impl super::Visitor for CompoundTypesVisitor<'_> {
       } => {
         if let Some(parent_context) = parent_context {
           let parent_context = Context::get_ref(&parent_context);
-          let compound_parent_name = parent_context.get_class_name()
-            .expect("could not get the name of the parent compound type while analysing a method definition");
+          let Some(compound_parent_name) = parent_context
+            .get_class_name()
+            .map(|name| parent_context.get_qualified_name(&name))
+          else {
+            let span = node.span_name;
+
+            self.report_manager.push(
+              Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
+                .with_message(&"Invalid method definition")
+                .with_label(
+                  DiagnosticLabel::new(self.span_manager.get_range(span))
+                    .with_message("Could not get the name of the parent compound type")
+                ),
+              span
+            );
+
+            return;
+          };
 
           let result = self.inference_store.register_method(
             compound_parent_name,
             node.name.clone(),
             parameters,
-            match &node.type_declaration {
-              Some(decl) => Some(decl.to_string()),
-              None => None
-            },
+            return_type,
             node.span_name
           );
 
@@ -104,10 +139,11 @@ impl super::Visitor for CompoundTypesVisitor<'_> {
             let span = node.span_name;
 
             self.report_manager.push(
-              Report::build(ReportKind::Error, (), self.span_manager.get_left(span))
+              Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
                 .with_message(&"Invalid method definition")
-                .with_label(Label::new(self.span_manager.get_range(span)).with_message(reason))
-                .finish(),
+                .with_label(
+                  DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(reason)
+                ),
               span
             );
           }
@@ -115,12 +151,9 @@ impl super::Visitor for CompoundTypesVisitor<'_> {
       }
       _ => {
         let result = self.inference_store.register_function(
-          node.name.clone(),
+          Context::get_ref(&node.context).get_qualified_name(&node.name),
           parameters,
-          match &node.type_declaration {
-            Some(decl) => Some(decl.to_string()),
-            None => None
-          },
+          return_type,
           node.span_name
         );
 
@@ -128,10 +161,11 @@ impl super::Visitor for CompoundTypesVisitor<'_> {
           let span = node.span_name;
 
           self.report_manager.push(
-            Report::build(ReportKind::Error, (), self.span_manager.get_left(span))
+            Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
               .with_message(&"Invalid function definition")
-              .with_label(Label::new(self.span_manager.get_range(span)).with_message(reason))
-              .finish(),
+              .with_label(
+                DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(reason)
+              ),
             span
           );
         }
@@ -139,20 +173,92 @@ impl super::Visitor for CompoundTypesVisitor<'_> {
     };
   }
 
+  fn visit_imported_class_declaration(&mut self, node: &ImportedClassDeclaration) {
+    let result = self.inference_store.register_compound(
+      node.name.clone(),
+      node.extended_class_name.clone(),
+      true
+    );
+
+    if let Err(reason) = result {
+      let span = node.span_name;
+
+      self.report_manager.push(
+        Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
+          .with_message(&"Invalid imported class definition")
+          .with_label(DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(reason)),
+        span
+      );
+
+      return;
+    }
+
+    for statement in &node.body_statements {
+      let ImportedClassBodyStatement::Method(method) = statement else {
+        continue;
+      };
+
+      let result = self.inference_store.register_method(
+        node.name.clone(),
+        method.name.clone(),
+        FunctionDeclarationParameter::to_function_infered_parameter_types(&method.parameters),
+        method
+          .type_declaration
+          .as_ref()
+          .map(|decl| decl.to_string()),
+        method.span_name
+      );
+
+      if let Err(reason) = result {
+        let span = method.span_name;
+
+        self.report_manager.push(
+          Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
+            .with_message(&"Invalid imported method definition")
+            .with_label(
+              DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(reason)
+            ),
+          span
+        );
+      }
+    }
+  }
+
+  fn visit_imported_function_declaration(&mut self, node: &ImportedFunctionDeclaration) {
+    let result = self.inference_store.register_function(
+      node.name.clone(),
+      FunctionDeclarationParameter::to_function_infered_parameter_types(&node.parameters),
+      node.type_declaration.as_ref().map(|decl| decl.to_string()),
+      node.span_name
+    );
+
+    if let Err(reason) = result {
+      let span = node.span_name;
+
+      self.report_manager.push(
+        Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
+          .with_message(&"Invalid imported function definition")
+          .with_label(DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(reason)),
+        span
+      );
+    }
+  }
+
   /// Update the current context with the latest context met in the AST
   fn visit_struct_declaration(&mut self, node: &crate::ast::StructDeclaration) {
-    let result = self
-      .inference_store
-      .register_compound(node.name.clone(), None);
+    let result = self.inference_store.register_compound(
+      Context::get_ref(&node.context).get_qualified_name(&node.name),
+      None,
+      false
+    );
 
     if let Err(reason) = result {
       let span = node.span_name;
 
       self.report_manager.push(
-        Report::build(ReportKind::Error, (), self.span_manager.get_left(span))
+        Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
           .with_message(&"Invalid struct definition")
-          .with_label(Label::new(self.span_manager.get_range(span)).with_message(reason))
-          .finish(),
+          .with_label(DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(reason)),
         span
       );
     }
@@ -232,7 +338,8 @@ impl super::Visitor for ExpressionTypeInferenceVisitor<'_> {
         following_expression: _
       } => {
         for variable_name in &declaration.names {
-          let type_declaration_string = declaration.type_declaration.to_string();
+          let type_declaration_string = Context::get_ref(&self.current_context)
+            .resolve_qualified_name(&declaration.type_declaration.to_string());
 
           self
             .current_context
@@ -265,13 +372,28 @@ impl super::Visitor for ExpressionTypeInferenceVisitor<'_> {
             let span = following_expression.body.get_span();
 
             self.report_manager.push(
-              Report::build(ReportKind::Error, (), self.span_manager.get_left(span))
+              Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
                 .with_message(&"Cannot infer variable type")
                 .with_label(
-                  Label::new(self.span_manager.get_range(span))
+                  DiagnosticLabel::new(self.span_manager.get_range(span))
                     .with_message(&"Implicit variable declaration but resulting type is void")
+                ),
+              span
+            );
+
+            return;
+          }
+          crate::ast::inference::Type::Null => {
+            let span = following_expression.body.get_span();
+
+            self.report_manager.push(
+              Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
+                .with_message(&"Cannot infer variable type")
+                .with_label(
+                  DiagnosticLabel::new(self.span_manager.get_range(span))
+                    .with_message(&"Implicit variable declaration but resulting type is NULL")
                 )
-                .finish(),
+                .with_help(&"Prefer an explicit type annotation here"),
               span
             );
 
@@ -281,13 +403,14 @@ impl super::Visitor for ExpressionTypeInferenceVisitor<'_> {
             let span = following_expression.body.get_span();
 
             self.report_manager.push(
-              Report::build(ReportKind::Error, (), self.span_manager.get_left(span))
+              Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
                 .with_message(&"Cannot infer variable type")
-                .with_label(Label::new(self.span_manager.get_range(span)).with_message(
-                  &"Implicit variable declaration but resulting type is unknown at the time"
-                ))
-                .with_help(&"Prefer an explicit type annotation here")
-                .finish(),
+                .with_label(
+                  DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(
+                    &"Implicit variable declaration but resulting type is unknown at the time"
+                  )
+                )
+                .with_help(&"Prefer an explicit type annotation here"),
               span
             );
 
@@ -473,32 +596,29 @@ impl super::Visitor for FunctionsCallsCheckerVisitor<'_> {
             // the parameter is not optional but None was passed
             if some_supplied.is_none() {
               self.report_manager.push(
-                Report::build(
-                  ariadne::ReportKind::Error,
-                  (),
+                Diagnostic::build(
+                  ReportKind::Error,
                   self.span_manager.get_left(node.accessor.span)
                 )
                 .with_message(&"Missing required parameter")
                 .with_label(
-                  Label::new(self.span_manager.get_range(node.accessor.span)).with_message(
-                    &format!("Parameter n° {count} is required but is missing from function call")
-                  )
-                )
-                .finish(),
+                  DiagnosticLabel::new(self.span_manager.get_range(node.accessor.span))
+                    .with_message(&format!(
+                      "Parameter n° {count} is required but is missing from function call"
+                    ))
+                ),
                 node.accessor.span
               );
 
               self.report_manager.push(
-                Report::build(
-                  ariadne::ReportKind::Advice,
-                  (),
+                Diagnostic::build(
+                  ReportKind::Advice,
                   self.span_manager.get_left(expected.span)
                 )
                 .with_label(
-                  Label::new(self.span_manager.get_range(expected.span))
+                  DiagnosticLabel::new(self.span_manager.get_range(expected.span))
                     .with_message("Try passing a parameter of the following type")
-                )
-                .finish(),
+                ),
                 expected.span
               );
 
@@ -512,39 +632,34 @@ impl super::Visitor for FunctionsCallsCheckerVisitor<'_> {
             if let Some(supplied) = &some_supplied {
               let supplied_type = supplied.infered_type_name.borrow();
               if !supplied_type.equals_string(&expected.infered_type)
-                && !supplied_type.can_auto_cast(&expected.infered_type)
+                && !supplied_type.can_auto_cast(&expected.infered_type, &self.inference_store.types)
               {
                 let span = supplied.body.get_span();
 
                 self.report_manager.push(
-                  Report::build(
-                    ariadne::ReportKind::Error,
-                    (),
-                    self.span_manager.get_left(span)
-                  )
-                  .with_message(&"Parameter type mismatch")
-                  .with_label(
-                    Label::new(self.span_manager.get_range(span)).with_message(&format!(
-                      "Parameter n°{count} is expected to be a {} but a {} was passed",
-                      &expected.infered_type,
-                      supplied_type.to_string()
-                    ))
-                  )
-                  .finish(),
+                  Diagnostic::build(ReportKind::Error, self.span_manager.get_left(span))
+                    .with_message(&"Parameter type mismatch")
+                    .with_label(
+                      DiagnosticLabel::new(self.span_manager.get_range(span)).with_message(
+                        &format!(
+                          "Parameter n°{count} is expected to be a {} but a {} was passed",
+                          &expected.infered_type,
+                          supplied_type.to_string()
+                        )
+                      )
+                    ),
                   span
                 );
 
                 self.report_manager.push(
-                  Report::build(
-                    ariadne::ReportKind::Advice,
-                    (),
+                  Diagnostic::build(
+                    ReportKind::Advice,
                     self.span_manager.get_left(expected.span)
                   )
                   .with_label(
-                    Label::new(self.span_manager.get_range(expected.span))
+                    DiagnosticLabel::new(self.span_manager.get_range(expected.span))
                       .with_message("Try passing a parameter of the following type")
-                  )
-                  .finish(),
+                  ),
                   expected.span
                 );
 
@@ -1,14 +1,21 @@
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::ast::codegen::context::{Context, ContextType};
-use crate::ast::{ProgramInformation, TypedIdentifier};
+use crate::ast::{ForInStatement, ProgramInformation, TypeDeclaration, TypedIdentifier};
+use crate::utils::stable_hash;
 
 /// Looks variable declarations and register them to the context of the current
 /// function. Allows for variable declarations anywhere in function bodies.
 pub struct VariableDeclarationVisitor<'a> {
   pub program_information: &'a ProgramInformation,
-  pub current_context: Rc<RefCell<Context>>
+  pub current_context: Rc<RefCell<Context>>,
+
+  /// How many `for in` loops each function has, by path of its context. The
+  /// count gives the indexing variables names that are unique in the function
+  /// and don't depend on the other files of the compilation.
+  for_in_counts: HashMap<String, usize>
 }
 
 impl<'a> VariableDeclarationVisitor<'a> {
@@ -19,7 +26,8 @@ impl<'a> VariableDeclarationVisitor<'a> {
         "empty",
         None,
         ContextType::Global
-      )))
+      ))),
+      for_in_counts: HashMap::new()
     }
   }
 }
@@ -60,16 +68,37 @@ impl super::Visitor for VariableDeclarationVisitor<'_> {
     };
   }
 
+  fn visit_for_in_statement(&mut self, node: &ForInStatement) {
+    // we generate an intermediate indexing variable for this for loop
+    let path = self.current_context.borrow().get_path();
+    let count = self.for_in_counts.entry(path.clone()).or_default();
+    let indexor = format!("idx{}", stable_hash(&[&path, &count.to_string()]));
+    *count += 1;
+
+    let indexor_variable = TypedIdentifier {
+      names: vec![indexor.clone()],
+      type_declaration: TypeDeclaration::Regular {
+        type_name: "int".to_string(),
+        generic_type_assignment: None,
+        mangled_accessor: RefCell::new(None)
+      }
+    };
+
+    self.register_variable_declaration(node.child.clone());
+    self.register_variable_declaration(Rc::new(indexor_variable));
+    node.indexor_name.replace(indexor);
+  }
+
   fn register_variable_declaration(&mut self, declaration: Rc<TypedIdentifier>) {
+    let type_name = Context::get_ref(&self.current_context)
+      .resolve_qualified_name(&declaration.type_declaration.to_string());
+
     for variable_name in &declaration.names {
       self
         .current_context
         .borrow_mut()
         .local_variables_inference
-        .insert(
-          variable_name.clone(),
-          declaration.type_declaration.to_string()
-        );
+        .insert(variable_name.clone(), type_name.clone());
     }
 
     self
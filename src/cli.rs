@@ -0,0 +1,293 @@
+use crate::ast::MessageFormat;
+use crate::config::DefineValue;
+use crate::error::CompileError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+  /// Compiles the project and emits the `.ws` files in the dist directory
+  Build,
+
+  /// Runs the preprocessor, the parser and the static analysis and only
+  /// prints the diagnostics, the dist directory is not touched.
+  Check,
+
+  /// Removes the dist directory
+  Clean,
+
+  /// Starts a language server speaking over stdio
+  Lsp,
+
+  /// Prints the `.wss` location a location in an emitted `.ws` file comes
+  /// from, using the source maps.
+  Map
+}
+
+#[derive(Debug)]
+pub struct Arguments {
+  pub command: Command,
+
+  /// The directory that contains the `cahirc.toml` file
+  pub project_directory: String,
+
+  /// The location to resolve with the map command, like `dist/foo.ws:812`
+  pub location: Option<String>,
+
+  pub warnings_as_errors: bool,
+
+  /// How the diagnostics are printed
+  pub message_format: MessageFormat,
+
+  /// Keeps the compiler running and compiles the project again whenever one
+  /// of its `.wss` files changes.
+  pub watch: bool,
+
+  /// The `[profile.NAME]` section of the config to use
+  pub profile: Option<String>,
+
+  /// The `-D NAME=value` options, they replace the defines of the config
+  pub defines: Vec<(String, DefineValue)>
+}
+
+impl Arguments {
+  /// Parses the command line, in the form of:
+  /// ```
+  /// cahirc [build|check|clean|lsp] [project_directory] [--warnings-as-errors] [--watch]
+  ///        [--message-format=human|json] [--profile=NAME|--release] [-D NAME[=value]]...
+  /// cahirc map dist/foo.ws:812
+  /// ```
+  pub fn from_env() -> Result<Self, CompileError> {
+    Self::parse(std::env::args().skip(1))
+  }
+
+  pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, CompileError> {
+    let mut command = None;
+    let mut project_directory = None;
+    let mut location = None;
+    let mut warnings_as_errors = false;
+    let mut watch = false;
+    let mut message_format = MessageFormat::Human;
+    let mut profile = None;
+    let mut defines = Vec::new();
+
+    while let Some(arg) = args.next() {
+      if arg.starts_with("-") {
+        match arg.as_str() {
+          "--warnings-as-errors" => warnings_as_errors = true,
+          "--watch" => watch = true,
+          "--message-format=human" => message_format = MessageFormat::Human,
+          "--message-format=json" => message_format = MessageFormat::Json,
+          "--release" => profile = Some(String::from("release")),
+          "-D" => {
+            let define = args.next().ok_or_else(|| {
+              CompileError::Arguments(String::from("-D expects a define like NAME=value"))
+            })?;
+
+            defines.push(parse_define(&define)?);
+          }
+          _ if arg.starts_with("--profile=") => {
+            profile = Some(arg["--profile=".len()..].to_string())
+          }
+          _ if arg.starts_with("-D") => defines.push(parse_define(&arg[2..])?),
+          _ => return Err(CompileError::Arguments(format!("unknown option {arg}")))
+        };
+
+        continue;
+      }
+
+      let subcommand = match arg.as_str() {
+        "build" => Some(Command::Build),
+        "check" => Some(Command::Check),
+        "clean" => Some(Command::Clean),
+        "lsp" => Some(Command::Lsp),
+        "map" => Some(Command::Map),
+        _ => None
+      };
+
+      match (subcommand, &command, &project_directory) {
+        (Some(subcommand), None, None) => command = Some(subcommand),
+        (_, Some(Command::Map), _) if location.is_none() => location = Some(arg),
+        (_, _, None) => project_directory = Some(arg),
+        (_, _, Some(_)) => {
+          return Err(CompileError::Arguments(format!(
+            "unexpected argument {arg}"
+          )))
+        }
+      };
+    }
+
+    let command = command.unwrap_or(Command::Build);
+
+    if command == Command::Map && location.is_none() {
+      return Err(CompileError::Arguments(String::from(
+        "the map command expects a location like dist/foo.ws:812"
+      )));
+    }
+
+    if watch && matches!(command, Command::Clean | Command::Lsp | Command::Map) {
+      return Err(CompileError::Arguments(String::from(
+        "--watch can only be used with the build and check commands"
+      )));
+    }
+
+    Ok(Self {
+      command,
+      project_directory: project_directory.unwrap_or_else(|| String::from(".")),
+      location,
+      warnings_as_errors,
+      message_format,
+      watch,
+      profile,
+      defines
+    })
+  }
+}
+
+/// Parses a `NAME=value` define, a define without a value is `true`
+fn parse_define(define: &str) -> Result<(String, DefineValue), CompileError> {
+  let (name, value) = match define.split_once('=') {
+    Some((name, value)) => (name, DefineValue::parse(value)),
+    None => (define, DefineValue::Bool(true))
+  };
+
+  let is_valid_name =
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+  if !is_valid_name {
+    return Err(CompileError::Arguments(format!(
+      "invalid define {define}, expected NAME=value"
+    )));
+  }
+
+  Ok((name.to_string(), value))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &[&str]) -> Result<Arguments, CompileError> {
+    Arguments::parse(args.iter().map(|arg| arg.to_string()))
+  }
+
+  fn parse_error(args: &[&str]) -> String {
+    match parse(args) {
+      Err(CompileError::Arguments(message)) => message,
+      result => panic!("expected an arguments error, got {result:?}")
+    }
+  }
+
+  #[test]
+  fn builds_the_current_directory_by_default() {
+    let arguments = parse(&[]).unwrap();
+
+    assert_eq!(arguments.command, Command::Build);
+    assert_eq!(arguments.project_directory, ".");
+    assert!(!arguments.watch && !arguments.warnings_as_errors);
+    assert_eq!(arguments.message_format, MessageFormat::Human);
+    assert_eq!(arguments.profile, None);
+    assert!(arguments.defines.is_empty());
+  }
+
+  #[test]
+  fn reads_the_command_the_directory_and_the_options() {
+    let arguments = parse(&[
+      "check",
+      "--watch",
+      "mods/foo",
+      "--warnings-as-errors",
+      "--message-format=json",
+      "--profile=nightly"
+    ])
+    .unwrap();
+
+    assert_eq!(arguments.command, Command::Check);
+    assert_eq!(arguments.project_directory, "mods/foo");
+    assert!(arguments.watch && arguments.warnings_as_errors);
+    assert_eq!(arguments.message_format, MessageFormat::Json);
+    assert_eq!(arguments.profile.as_deref(), Some("nightly"));
+
+    assert_eq!(
+      parse(&["--release"]).unwrap().profile.as_deref(),
+      Some("release")
+    );
+  }
+
+  #[test]
+  fn reads_a_directory_named_like_a_command() {
+    let arguments = parse(&["build", "check"]).unwrap();
+
+    assert_eq!(arguments.command, Command::Build);
+    assert_eq!(arguments.project_directory, "check");
+  }
+
+  #[test]
+  fn reads_the_defines_in_both_forms() {
+    let arguments = parse(&["-D", "DEBUG", "-DVERSION=4", "-D", "NAME=\"geralt\""]).unwrap();
+
+    assert_eq!(
+      arguments.defines,
+      vec![
+        (String::from("DEBUG"), DefineValue::Bool(true)),
+        (String::from("VERSION"), DefineValue::Integer(4)),
+        (
+          String::from("NAME"),
+          DefineValue::String(String::from("geralt"))
+        )
+      ]
+    );
+  }
+
+  #[test]
+  fn reads_the_location_of_the_map_command() {
+    let arguments = parse(&["map", "dist/foo.ws:812"]).unwrap();
+
+    assert_eq!(arguments.command, Command::Map);
+    assert_eq!(arguments.location.as_deref(), Some("dist/foo.ws:812"));
+    assert_eq!(
+      parse_error(&["map"]),
+      "the map command expects a location like dist/foo.ws:812"
+    );
+  }
+
+  #[test]
+  fn rejects_the_invalid_arguments() {
+    assert_eq!(parse_error(&["--verbose"]), "unknown option --verbose");
+    assert_eq!(parse_error(&["a", "b"]), "unexpected argument b");
+    assert_eq!(parse_error(&["-D"]), "-D expects a define like NAME=value");
+    assert_eq!(
+      parse_error(&["clean", "--watch"]),
+      "--watch can only be used with the build and check commands"
+    );
+  }
+
+  #[test]
+  fn parses_the_defines() {
+    assert_eq!(
+      parse_define("A").unwrap(),
+      (String::from("A"), DefineValue::Bool(true))
+    );
+    assert_eq!(
+      parse_define("A=false").unwrap(),
+      (String::from("A"), DefineValue::Bool(false))
+    );
+    assert_eq!(
+      parse_define("A_1=1.5").unwrap(),
+      (String::from("A_1"), DefineValue::Float(1.5))
+    );
+    assert_eq!(
+      parse_define("A=a=b").unwrap(),
+      (String::from("A"), DefineValue::String(String::from("a=b")))
+    );
+
+    for define in ["", "=1", "A-B=1", "A B"] {
+      let Err(CompileError::Arguments(message)) = parse_define(define) else {
+        panic!("{define} should be rejected");
+      };
+
+      assert_eq!(
+        message,
+        format!("invalid define {define}, expected NAME=value")
+      );
+    }
+  }
+}
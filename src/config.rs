@@ -1,49 +1,169 @@
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 
 use serde::Deserialize;
 
+use crate::error::CompileError;
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
   pub package: ConfigPackage,
-  pub dependencies: HashMap<String, String>
+  pub dependencies: BTreeMap<String, String>,
+
+  /// The features to enable or disable, an enabled feature is defined as a
+  /// macro constant
+  #[serde(default)]
+  pub features: BTreeMap<String, bool>,
+
+  /// The macro constants defined for every file of the project
+  #[serde(default)]
+  pub defines: BTreeMap<String, DefineValue>,
+
+  /// The `[profile.debug]` and `[profile.release]` sections, or any other
+  /// profile, that extend the features and the defines
+  #[serde(default)]
+  pub profile: BTreeMap<String, ConfigProfile>
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ConfigPackage {
   pub name: String,
+
+  /// The version of the package, used by the `__VERSION__!` macro
+  pub version: Option<String>,
+
   pub src: String,
   pub dist: String,
-  pub static_analysis: Option<bool>
+  pub static_analysis: Option<bool>,
+
+  /// The length of the longest chain of macros that can expand into each
+  /// other, see `DEFAULT_MAX_MACRO_DEPTH`
+  pub max_macro_depth: Option<usize>
 }
 
-pub fn read_config() -> std::io::Result<Config> {
-  let default_path = ".".to_string();
-  let args: Vec<String> = std::env::args().collect();
-  let first_arg = args
-    .iter()
-    .skip(1)
-    .find(|arg| !arg.starts_with("-"))
-    .unwrap_or(&default_path);
+#[derive(Deserialize, Debug, Default)]
+pub struct ConfigProfile {
+  #[serde(default)]
+  pub features: BTreeMap<String, bool>,
 
-  let cwd = Path::new(first_arg);
-  let config_path = cwd.join("cahirc.toml");
-  let content = std::fs::read_to_string(config_path)?;
+  #[serde(default)]
+  pub defines: BTreeMap<String, DefineValue>
+}
 
-  let mut config: Config = toml::from_str(&content)?;
+/// The value of a macro constant defined outside of the sources
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum DefineValue {
+  Bool(bool),
+  Integer(i64),
+  Float(f64),
+  String(String)
+}
 
-  config.package.src = cwd.join(config.package.src).to_str().unwrap().to_string();
-  config.package.dist = cwd.join(config.package.dist).to_str().unwrap().to_string();
+impl DefineValue {
+  /// Parses the value of a `-D NAME=value` option the way a TOML value is
+  /// read, any value that is not a boolean nor a number is a string.
+  pub fn parse(value: &str) -> Self {
+    if let Ok(boolean) = value.parse() {
+      return DefineValue::Bool(boolean);
+    }
 
-  let keys: Vec<String> = config.dependencies.keys().map(String::to_string).collect();
-  for dep_name in keys {
-    let dep_path = config.dependencies.get(&dep_name).unwrap();
+    if let Ok(integer) = value.parse() {
+      return DefineValue::Integer(integer);
+    }
 
-    config.dependencies.insert(
-      dep_name.to_string(),
-      cwd.join(dep_path).to_str().unwrap().to_string()
-    );
+    if let Ok(float) = value.parse() {
+      return DefineValue::Float(float);
+    }
+
+    let unquoted = value
+      .strip_prefix('"')
+      .and_then(|value| value.strip_suffix('"'))
+      .unwrap_or(value);
+
+    DefineValue::String(unquoted.to_string())
+  }
+}
+
+/// Displays the value as the code the macro constant expands to, the strings
+/// become string literals.
+impl Display for DefineValue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      DefineValue::Bool(boolean) => write!(f, "{boolean}"),
+      DefineValue::Integer(integer) => write!(f, "{integer}"),
+      DefineValue::Float(float) => write!(f, "{float:?}"),
+      DefineValue::String(string) => write!(f, "\"{string}\"")
+    }
+  }
+}
+
+/// The profile used when none is given on the command line
+pub const DEFAULT_PROFILE: &str = "debug";
+
+pub const DEFAULT_MAX_MACRO_DEPTH: usize = 64;
+
+/// Returns the path of the config of the project in the directory
+pub fn get_config_path(cwd: &Path) -> PathBuf {
+  cwd.join("cahirc.toml")
+}
+
+pub fn read_config(cwd: &Path) -> Result<Config, CompileError> {
+  let config_path = get_config_path(cwd);
+  let content = std::fs::read_to_string(&config_path).map_err(|error| CompileError::Io {
+    path: config_path.clone(),
+    error
+  })?;
+
+  let mut config: Config = toml::from_str(&content)
+    .map_err(|error| CompileError::Config(format!("{}: {error}", config_path.display())))?;
+
+  config.package.src = cwd.join(config.package.src).to_string_lossy().to_string();
+  config.package.dist = cwd.join(config.package.dist).to_string_lossy().to_string();
+
+  for dep_path in config.dependencies.values_mut() {
+    *dep_path = cwd.join(&dep_path).to_string_lossy().to_string();
   }
 
   Ok(config)
 }
+
+impl Config {
+  /// Adds the features and the defines of the profile to the ones of the
+  /// config, the values of the profile replace the others. The `debug` and
+  /// `release` profiles are optional, any other profile must be in the config.
+  pub fn apply_profile(&mut self, name: &str) -> Result<(), CompileError> {
+    let profile = match self.profile.remove(name) {
+      Some(profile) => profile,
+      None if name == "debug" || name == "release" => ConfigProfile::default(),
+      None => {
+        return Err(CompileError::Config(format!(
+          "unknown profile {name}, the cahirc.toml file has no [profile.{name}] section"
+        )))
+      }
+    };
+
+    self.features.extend(profile.features);
+    self.defines.extend(profile.defines);
+
+    Ok(())
+  }
+
+  /// Returns the macro constants the preprocessor defines before it reads the
+  /// sources: the enabled features and the defines.
+  pub fn get_macro_constants(&self) -> BTreeMap<String, String> {
+    let features = self
+      .features
+      .iter()
+      .filter(|(_, enabled)| **enabled)
+      .map(|(name, _)| (name.clone(), String::from("true")));
+
+    let defines = self
+      .defines
+      .iter()
+      .map(|(name, value)| (name.clone(), value.to_string()));
+
+    features.chain(defines).collect()
+  }
+}
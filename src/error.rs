@@ -0,0 +1,289 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use ariadne::ReportKind;
+use lalrpop_util::lexer::Token;
+use lalrpop_util::ParseError;
+
+use crate::ast::{Diagnostic, DiagnosticLabel};
+use crate::preprocessor::types::ProcessedFile;
+
+/// Every failure that can stop the compilation pipeline. Errors that come
+/// from the user's code are reported as diagnostics before the matching
+/// variant is returned, so the variants only carry what is needed to print
+/// a final summary.
+#[derive(Debug)]
+pub enum CompileError {
+  /// The command line arguments are invalid
+  Arguments(String),
+
+  /// The `cahirc.toml` file could not be read or is malformed
+  Config(String),
+
+  /// A file or a directory could not be read or written
+  Io {
+    path: PathBuf,
+    error: std::io::Error
+  },
+
+  /// A dependency uses a source the compiler cannot fetch
+  UnsupportedDependency { name: String, source: String },
+
+  /// The preprocessor found a malformed directive or macro call
+  Preprocessor {
+    path: PathBuf,
+    line: usize,
+    column: usize,
+    message: String
+  },
+
+  /// One or more files could not be parsed, the syntax errors were already
+  /// reported.
+  Syntax { files: Vec<PathBuf> },
+
+  /// The static analysis reported errors, or warnings while they are treated
+  /// as errors.
+  Diagnostics { errors: usize, warnings: usize },
+
+  /// A file context that should have been built during the first pass is
+  /// missing from the global context.
+  MissingContext(String),
+
+  /// A diagnostic points to a file that is neither a source nor a dependency
+  /// file.
+  UnknownReportSource(String),
+
+  /// Code could not be emitted for the given output file
+  Codegen {
+    path: PathBuf,
+    error: std::io::Error
+  },
+
+  /// The connection with the editor failed
+  LanguageServer(String)
+}
+
+impl Display for CompileError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CompileError::Arguments(message) => write!(f, "invalid arguments: {message}"),
+      CompileError::Config(message) => write!(f, "invalid configuration: {message}"),
+      CompileError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+      CompileError::UnsupportedDependency { name, source } => {
+        write!(f, "dependency {name} uses an unsupported source: {source}")
+      }
+      CompileError::Preprocessor {
+        path,
+        line,
+        column,
+        message
+      } => write!(f, "{}:{line}:{column}: {message}", path.display()),
+      CompileError::Syntax { files } => {
+        write!(f, "could not parse {} file(s)", files.len())
+      }
+      CompileError::Diagnostics { errors, warnings } => write!(
+        f,
+        "compilation stopped with {errors} error(s) and {warnings} warning(s)"
+      ),
+      CompileError::MissingContext(name) => {
+        write!(f, "missing context in 2nd compilation pass for {name}")
+      }
+      CompileError::UnknownReportSource(source) => {
+        write!(f, "cannot report a diagnostic in unknown file {source}")
+      }
+      CompileError::Codegen { path, error } => {
+        write!(f, "failed to emit code for {}: {error}", path.display())
+      }
+      CompileError::LanguageServer(message) => write!(f, "language server error: {message}")
+    }
+  }
+}
+
+impl std::error::Error for CompileError {}
+
+/// An error raised by an action of the grammar, located at the offset of the
+/// code it is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarError {
+  pub location: usize,
+  pub message: &'static str
+}
+
+/// Converts the given parse error into a diagnostic for the file, the message
+/// names the included file the error is in if it comes from one.
+pub fn parse_error_diagnostic(
+  error: ParseError<usize, Token, GrammarError>, file: &ProcessedFile
+) -> Diagnostic {
+  let (message, range, label) = match error {
+    ParseError::InvalidToken { location } => (
+      "Invalid token",
+      location..location.checked_add(1).unwrap_or(location),
+      String::from("The invalid token")
+    ),
+    ParseError::UnrecognizedEOF { location, expected } => (
+      "Unexpected end of file",
+      location..location,
+      format_expected_tokens(&expected)
+    ),
+    ParseError::UnrecognizedToken { token, expected } => (
+      "Unrecognized token",
+      token.0..token.2,
+      format_expected_tokens(&expected)
+    ),
+    ParseError::ExtraToken { token } => (
+      "Extra token",
+      token.0..token.2,
+      String::from("Expected the end of the file")
+    ),
+    ParseError::User { error } => (
+      "Invalid syntax",
+      error.location..error.location,
+      error.message.to_string()
+    )
+  };
+
+  let included_file = file
+    .offset_map
+    .borrow()
+    .get_original_range(range.clone())
+    .file;
+  let file_path = match &included_file {
+    Some(included_file) => PathBuf::from(&included_file.path),
+    None => file.path.clone()
+  };
+
+  let absolute_path = std::env::current_dir()
+    .and_then(|cwd| dunce::canonicalize(cwd.join(&file_path)))
+    .unwrap_or(file_path);
+  let file_url = format!(
+    "file://{}",
+    absolute_path.to_string_lossy().replace("\\", "/")
+  );
+
+  Diagnostic::build(ReportKind::Error, range.start)
+    .with_message(format!("{message} in {file_url}"))
+    .with_label(DiagnosticLabel::new(range).with_message(label))
+}
+
+/// Turns the tokens the parser expected into a sentence, with the names of
+/// the tokens as the user would write them rather than the grammar's names.
+fn format_expected_tokens(expected: &[String]) -> String {
+  let mut names: Vec<String> = Vec::new();
+
+  for token in expected {
+    let name = get_token_name(token);
+
+    if !names.contains(&name) {
+      names.push(name);
+    }
+  }
+
+  match names.split_last() {
+    None => String::from("Unexpected token"),
+    Some((last, [])) => format!("Expected {last}"),
+    Some((last, others)) => format!("Expected {} or {last}", others.join(", "))
+  }
+}
+
+/// The keywords of the grammar along with the text they are written with, as
+/// their names don't always match the text (`KeywordNull` is `NULL`).
+const KEYWORDS: &[(&str, &str)] = &[
+  ("KeywordFunction", "function"),
+  ("KeywordVar", "var"),
+  ("KeywordReturn", "return"),
+  ("KeywordIf", "if"),
+  ("KeywordElse", "else"),
+  ("KeywordFor", "for"),
+  ("KeywordWhile", "while"),
+  ("KeywordDo", "do"),
+  ("KeywordClass", "class"),
+  ("KeywordStatemachine", "statemachine"),
+  ("KeywordAbstract", "abstract"),
+  ("KeywordState", "state"),
+  ("KeywordExtends", "extends"),
+  ("KeywordPrivate", "private"),
+  ("KeywordPublic", "public"),
+  ("KeywordProtected", "protected"),
+  ("KeywordDefault", "default"),
+  ("KeywordLatent", "latent"),
+  ("KeywordStruct", "struct"),
+  ("KeywordEnum", "enum"),
+  ("KeywordTimer", "timer"),
+  ("KeywordEvent", "event"),
+  ("KeywordQuest", "quest"),
+  ("KeywordIn", "in"),
+  ("KeywordEntry", "entry"),
+  ("KeywordNew", "new"),
+  ("KeywordAs", "as"),
+  ("KeywordOptional", "optional"),
+  ("KeywordOut", "out"),
+  ("KeywordCase", "case"),
+  ("KeywordSwitch", "switch"),
+  ("KeywordBreak", "break"),
+  ("KeywordContinue", "continue"),
+  ("KeywordExec", "exec"),
+  ("KeywordDelete", "delete"),
+  ("KeywordFn", "fn"),
+  ("KeywordSaved", "saved"),
+  ("KeywordWrapMethod", "wrapMethod"),
+  ("KeywordReplaceMethod", "replaceMethod"),
+  ("KeywordAddMethod", "addMethod"),
+  ("KeywordAddField", "addField"),
+  ("KeywordEditable", "editable"),
+  ("KeywordHint", "hint"),
+  ("KeywordNamespace", "namespace"),
+  ("KeywordModule", "module"),
+  ("KeywordImport", "import"),
+  ("KeywordTrue", "true"),
+  ("KeywordFalse", "false"),
+  ("KeywordNull", "NULL"),
+  ("KeywordFinal", "final")
+];
+
+fn get_token_name(token: &str) -> String {
+  if let Some((_, text)) = KEYWORDS.iter().find(|(name, _)| *name == token) {
+    return format!("`{text}`");
+  }
+
+  match token {
+    "IdentifierRegex" => return String::from("an identifier"),
+    r##"r#"\"[^\"]*\""#"## => return String::from("a string"),
+    r##"r#"'[^']*'"#"## => return String::from("a name"),
+    r##"r#"[0-9]+"#"## => return String::from("a number"),
+    r##"r#"\\.[0-9]*"#"## => return String::from("a decimal part"),
+    _ => {}
+  };
+
+  match token
+    .strip_prefix('"')
+    .and_then(|token| token.strip_suffix('"'))
+  {
+    Some(literal) => format!("`{}`", literal.replace("\\\"", "\"")),
+    None => token.to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn names_every_keyword_of_the_grammar() {
+    let grammar = include_str!("parser.lalrpop");
+    let mut grammar_keywords: Vec<(&str, &str)> = grammar
+      .lines()
+      .filter_map(|line| {
+        let (text, name) = line.trim().trim_end_matches(',').split_once(" => ")?;
+        let text = text.strip_prefix('"')?.strip_suffix('"')?;
+
+        name.starts_with("Keyword").then_some((name, text))
+      })
+      .collect();
+    let mut keywords = KEYWORDS.to_vec();
+
+    grammar_keywords.sort();
+    keywords.sort();
+
+    assert_eq!(keywords, grammar_keywords);
+  }
+}
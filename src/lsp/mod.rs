@@ -0,0 +1,544 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use ariadne::ReportKind;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+  DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+  Notification as _, PublishDiagnostics, ShowMessage
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+  CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+  DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+  DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+  HoverParams, HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind,
+  MessageType, OneOf, PublishDiagnosticsParams, ServerCapabilities, ShowMessageParams,
+  TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+  TextDocumentSyncSaveOptions, Url
+};
+
+use crate::ast::codegen::type_inference::{FunctionInferedType, InferedType, TypeInferenceStore};
+use crate::ast::inference::Type;
+use crate::ast::visitor::{SymbolsVisitor, Visited};
+use crate::ast::{Diagnostic, ReportManager, Span};
+use crate::config::{read_config, DEFAULT_PROFILE};
+use crate::error::CompileError;
+use crate::{analyze_project, ParseCache, ParsedFile, ProjectAnalysis};
+
+mod positions;
+use positions::*;
+
+/// Starts a language server speaking over stdio, for the project in
+/// `project_directory` unless the editor sends a workspace of its own.
+pub fn run(project_directory: &Path) -> Result<(), CompileError> {
+  let (connection, io_threads) = Connection::stdio();
+
+  let capabilities = ServerCapabilities {
+    text_document_sync: Some(TextDocumentSyncCapability::Options(
+      TextDocumentSyncOptions {
+        open_close: Some(true),
+        change: Some(TextDocumentSyncKind::FULL),
+        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+        ..Default::default()
+      }
+    )),
+    definition_provider: Some(OneOf::Left(true)),
+    hover_provider: Some(HoverProviderCapability::Simple(true)),
+    completion_provider: Some(CompletionOptions {
+      trigger_characters: Some(vec![String::from(".")]),
+      ..Default::default()
+    }),
+    ..Default::default()
+  };
+
+  let initialize_params = serde_json::to_value(capabilities)
+    .map_err(|error| error.to_string())
+    .and_then(|capabilities| {
+      connection
+        .initialize(capabilities)
+        .map_err(|error| error.to_string())
+    })
+    .and_then(|params| {
+      serde_json::from_value::<InitializeParams>(params).map_err(|error| error.to_string())
+    })
+    .map_err(CompileError::LanguageServer)?;
+
+  let mut server = LanguageServer {
+    connection,
+    project_directory: get_workspace_directory(&initialize_params)
+      .unwrap_or_else(|| project_directory.to_path_buf()),
+    documents: HashMap::new(),
+    analysis: None,
+    files_with_diagnostics: HashSet::new()
+  };
+
+  server.analyze()?;
+  server.run()?;
+
+  // the connection must be dropped first, so the writer thread can stop
+  drop(server);
+
+  io_threads
+    .join()
+    .map_err(|error| CompileError::LanguageServer(error.to_string()))
+}
+
+#[allow(deprecated)]
+fn get_workspace_directory(params: &InitializeParams) -> Option<PathBuf> {
+  let workspace_uri = params
+    .workspace_folders
+    .as_ref()
+    .and_then(|folders| folders.first())
+    .map(|folder| &folder.uri)
+    .or(params.root_uri.as_ref())?;
+
+  workspace_uri.to_file_path().ok()
+}
+
+struct LanguageServer {
+  connection: Connection,
+  project_directory: PathBuf,
+
+  /// The content of the documents opened in the editor, as they are being
+  /// edited.
+  documents: HashMap<Url, String>,
+
+  /// The result of the last successful analysis, it is only updated when a
+  /// document is saved.
+  analysis: Option<ProjectAnalysis>,
+
+  /// The files that received diagnostics from the last analysis, to clear them
+  /// once they are fixed.
+  files_with_diagnostics: HashSet<Url>
+}
+
+impl LanguageServer {
+  fn run(&mut self) -> Result<(), CompileError> {
+    while let Ok(message) = self.connection.receiver.recv() {
+      match message {
+        Message::Request(request) => {
+          let is_shutdown = self
+            .connection
+            .handle_shutdown(&request)
+            .map_err(|error| CompileError::LanguageServer(error.to_string()))?;
+
+          if is_shutdown {
+            return Ok(());
+          }
+
+          let response = self.handle_request(request);
+          self.send(Message::Response(response))?;
+        }
+        Message::Notification(notification) => self.handle_notification(notification)?,
+        Message::Response(_) => {}
+      };
+    }
+
+    Ok(())
+  }
+
+  fn send(&self, message: Message) -> Result<(), CompileError> {
+    self
+      .connection
+      .sender
+      .send(message)
+      .map_err(|error| CompileError::LanguageServer(error.to_string()))
+  }
+
+  fn handle_request(&self, request: Request) -> Response {
+    let result = match request.method.as_str() {
+      GotoDefinition::METHOD => serde_json::from_value(request.params)
+        .map(|params| serde_json::to_value(self.goto_definition(params))),
+      HoverRequest::METHOD => serde_json::from_value(request.params)
+        .map(|params| serde_json::to_value(self.hover(params))),
+      Completion::METHOD => serde_json::from_value(request.params)
+        .map(|params| serde_json::to_value(self.completion(params))),
+      _ => {
+        return Response::new_err(
+          request.id,
+          ErrorCode::MethodNotFound as i32,
+          format!("unsupported request {}", request.method)
+        )
+      }
+    };
+
+    match result {
+      Ok(Ok(value)) => Response::new_ok(request.id, value),
+      Ok(Err(error)) => Response::new_err(
+        request.id,
+        ErrorCode::InternalError as i32,
+        error.to_string()
+      ),
+      Err(error) => Response::new_err(
+        request.id,
+        ErrorCode::InvalidParams as i32,
+        error.to_string()
+      )
+    }
+  }
+
+  fn handle_notification(&mut self, notification: Notification) -> Result<(), CompileError> {
+    match notification.method.as_str() {
+      DidOpenTextDocument::METHOD => {
+        if let Ok(params) = serde_json::from_value::<DidOpenTextDocumentParams>(notification.params)
+        {
+          self
+            .documents
+            .insert(params.text_document.uri, params.text_document.text);
+        }
+      }
+      DidChangeTextDocument::METHOD => {
+        if let Ok(params) =
+          serde_json::from_value::<DidChangeTextDocumentParams>(notification.params)
+        {
+          // the changes are always sent in full, the last one is the content
+          // of the document.
+          if let Some(change) = params.content_changes.into_iter().last() {
+            self.documents.insert(params.text_document.uri, change.text);
+          }
+        }
+      }
+      DidCloseTextDocument::METHOD => {
+        if let Ok(params) =
+          serde_json::from_value::<DidCloseTextDocumentParams>(notification.params)
+        {
+          self.documents.remove(&params.text_document.uri);
+        }
+      }
+      DidSaveTextDocument::METHOD => self.analyze()?,
+      _ => {}
+    };
+
+    Ok(())
+  }
+
+  /// Analyzes the project again and publishes the diagnostics it reported.
+  fn analyze(&mut self) -> Result<(), CompileError> {
+    let mut report_manager = ReportManager::new();
+    report_manager.collect_reports = true;
+
+    let analysis = read_config(&self.project_directory).and_then(|mut config| {
+      config.apply_profile(DEFAULT_PROFILE)?;
+
+      analyze_project(
+        &config,
+        true,
+        &mut report_manager,
+        &mut ParseCache::default()
+      )
+    });
+
+    let analysis = match analysis {
+      Ok(analysis) => analysis,
+      Err(error) => {
+        // the previous analysis is kept so the requests can still be answered
+        let params = ShowMessageParams {
+          typ: MessageType::ERROR,
+          message: error.to_string()
+        };
+
+        return self.send(Message::Notification(Notification::new(
+          ShowMessage::METHOD.to_string(),
+          params
+        )));
+      }
+    };
+
+    let mut diagnostics_per_file: HashMap<Url, Vec<lsp_types::Diagnostic>> = HashMap::new();
+
+    // the offsets of the collected reports already point to the original
+    // files
+    for (diagnostic, span) in &report_manager.collected_reports {
+      let filename = analysis.span_manager.get_source(span);
+      let Some(file) = analysis.preprocessed_content.get_file(filename) else {
+        continue;
+      };
+
+      let (path, content) = match &diagnostic.included_file {
+        Some(included_file) => (included_file.path.as_str(), &included_file.content),
+        None => (filename.as_str(), &file.original_content)
+      };
+
+      let Some(uri) = filename_to_uri(path) else {
+        continue;
+      };
+
+      diagnostics_per_file
+        .entry(uri)
+        .or_default()
+        .push(to_lsp_diagnostic(diagnostic, content));
+    }
+
+    // the files that no longer have diagnostics are sent an empty list to
+    // clear the previous ones.
+    for uri in self.files_with_diagnostics.drain() {
+      diagnostics_per_file.entry(uri).or_default();
+    }
+
+    for (uri, diagnostics) in diagnostics_per_file {
+      if !diagnostics.is_empty() {
+        self.files_with_diagnostics.insert(uri.clone());
+      }
+
+      self.send(Message::Notification(Notification::new(
+        PublishDiagnostics::METHOD.to_string(),
+        PublishDiagnosticsParams::new(uri, diagnostics, None)
+      )))?;
+    }
+
+    self.analysis = Some(analysis);
+
+    Ok(())
+  }
+
+  fn goto_definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+    let analysis = self.analysis.as_ref()?;
+    let position = params.text_document_position_params;
+    let content = self.get_document_content(&position.text_document.uri)?;
+    let offset = position_to_offset(&content, position.position);
+    let name = identifier_at(&content, offset)?;
+
+    let mut locations = Vec::new();
+
+    for parsed_file in analysis
+      .dependency_ast_list
+      .iter()
+      .chain(&analysis.ast_list)
+    {
+      let mut visitor = SymbolsVisitor::new();
+      parsed_file.ast.accept(&mut visitor);
+
+      for (_, span) in visitor.declarations.iter().filter(|(n, _)| n == name) {
+        if let Some(location) = span_to_location(analysis, span) {
+          locations.push(location);
+        }
+      }
+    }
+
+    Some(GotoDefinitionResponse::Array(locations))
+  }
+
+  fn hover(&self, params: HoverParams) -> Option<Hover> {
+    let analysis = self.analysis.as_ref()?;
+    let position = params.text_document_position_params;
+    let (filename, parsed_file) = find_parsed_file(analysis, &position.text_document.uri)?;
+    let file = analysis.preprocessed_content.get_file(&filename)?;
+    let original_offset = position_to_offset(&file.original_content, position.position);
+
+    // the spans point to the preprocessed content, code that comes from a
+    // macro expansion has no hover.
+    let offset = file
+      .offset_map
+      .borrow()
+      .get_processed_offset(original_offset)?;
+
+    let mut visitor = SymbolsVisitor::new();
+    parsed_file.ast.accept(&mut visitor);
+
+    // the smallest expression under the cursor is the most precise one
+    let symbol = visitor
+      .expressions
+      .iter()
+      .filter(|symbol| {
+        analysis
+          .span_manager
+          .get_range(symbol.span)
+          .contains(&offset)
+      })
+      .min_by_key(|symbol| analysis.span_manager.get_range(symbol.span).len())?;
+
+    if let Type::Unknown = symbol.infered_type {
+      return None;
+    }
+
+    let (file, original_range) = analysis.get_original_range(symbol.span)?;
+    let (_, content) = original_range.get_source(&filename, &file.original_content);
+
+    Some(Hover {
+      contents: HoverContents::Markup(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: format!("```\n{}\n```", symbol.infered_type)
+      }),
+      range: Some(range_to_lsp_range(content, original_range.range.clone()))
+    })
+  }
+
+  /// Completes the methods of the variable before the `.` under the cursor
+  fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+    let analysis = self.analysis.as_ref()?;
+    let position = params.text_document_position;
+    let uri = &position.text_document.uri;
+    let content = self.get_document_content(uri)?;
+    let offset = position_to_offset(&content, position.position);
+
+    let dot_offset = identifier_start(&content, offset).checked_sub(1)?;
+    if content.as_bytes().get(dot_offset) != Some(&b'.') {
+      return None;
+    }
+
+    let variable_name = identifier_at(&content, dot_offset)?;
+
+    // the document may have changed since the last analysis, the closest
+    // typed occurence of the variable before the cursor is the best guess.
+    let (_, parsed_file) = find_parsed_file(analysis, uri)?;
+    let mut visitor = SymbolsVisitor::new();
+    parsed_file.ast.accept(&mut visitor);
+
+    let variable_type = visitor
+      .expressions
+      .iter()
+      .filter(|symbol| symbol.identifier.as_deref() == Some(variable_name))
+      .filter_map(|symbol| match &symbol.infered_type {
+        Type::Identifier(type_name) => Some((symbol.span, type_name)),
+        _ => None
+      })
+      .filter_map(|(span, type_name)| {
+        let (_, original_range) = analysis.get_original_range(span)?;
+
+        Some((original_range.range.start, type_name))
+      })
+      .min_by_key(|(start, _)| start.abs_diff(offset))
+      .map(|(_, type_name)| type_name)?;
+
+    let items = get_compound_methods(&analysis.inference_store, variable_type)
+      .into_iter()
+      .map(|(name, method)| CompletionItem {
+        label: name,
+        kind: Some(CompletionItemKind::METHOD),
+        detail: Some(format_method_signature(&method)),
+        ..Default::default()
+      })
+      .collect();
+
+    Some(CompletionResponse::Array(items))
+  }
+
+  /// Returns the content of the document as it is in the editor, or as it
+  /// was during the last analysis.
+  fn get_document_content(&self, uri: &Url) -> Option<String> {
+    if let Some(content) = self.documents.get(uri) {
+      return Some(content.clone());
+    }
+
+    let analysis = self.analysis.as_ref()?;
+    let (filename, _) = find_parsed_file(analysis, uri)?;
+
+    analysis
+      .preprocessed_content
+      .get_file(&filename)
+      .map(|file| file.original_content.clone())
+  }
+}
+
+fn filename_to_uri(filename: &str) -> Option<Url> {
+  let path = dunce::canonicalize(filename).unwrap_or_else(|_| PathBuf::from(filename));
+
+  Url::from_file_path(path).ok()
+}
+
+fn find_parsed_file<'a>(
+  analysis: &'a ProjectAnalysis, uri: &Url
+) -> Option<(String, &'a ParsedFile)> {
+  analysis
+    .ast_list
+    .iter()
+    .chain(&analysis.dependency_ast_list)
+    .find(|parsed_file| filename_to_uri(&parsed_file.filename).as_ref() == Some(uri))
+    .map(|parsed_file| (parsed_file.filename.clone(), parsed_file))
+}
+
+fn span_to_location(analysis: &ProjectAnalysis, span: &Span) -> Option<Location> {
+  let filename = analysis.span_manager.get_source(span);
+  let (file, original_range) = analysis.get_original_range(*span)?;
+  let (path, content) = original_range.get_source(filename, &file.original_content);
+
+  Some(Location::new(
+    filename_to_uri(path)?,
+    range_to_lsp_range(content, original_range.range.clone())
+  ))
+}
+
+fn to_lsp_diagnostic(diagnostic: &Diagnostic, content: &str) -> lsp_types::Diagnostic {
+  let range = diagnostic
+    .labels
+    .first()
+    .map(|label| label.range.clone())
+    .unwrap_or(diagnostic.offset..diagnostic.offset);
+
+  let severity = match diagnostic.kind {
+    ReportKind::Error => DiagnosticSeverity::ERROR,
+    ReportKind::Warning => DiagnosticSeverity::WARNING,
+    ReportKind::Advice => DiagnosticSeverity::HINT,
+    _ => DiagnosticSeverity::INFORMATION
+  };
+
+  let mut message = diagnostic.message.clone().unwrap_or_default();
+
+  let details = diagnostic
+    .labels
+    .iter()
+    .filter_map(|label| label.message.as_ref())
+    .chain(&diagnostic.help)
+    .chain(&diagnostic.note);
+
+  for detail in details {
+    message.push_str(&format!("\n{detail}"));
+  }
+
+  lsp_types::Diagnostic {
+    range: range_to_lsp_range(content, range),
+    severity: Some(severity),
+    source: Some(String::from("cahirc")),
+    message,
+    ..Default::default()
+  }
+}
+
+/// Returns the methods of the compound type and of the types it extends.
+fn get_compound_methods(
+  store: &TypeInferenceStore, type_name: &str
+) -> Vec<(String, std::rc::Rc<FunctionInferedType>)> {
+  let mut methods = Vec::new();
+  let mut visited_types = HashSet::new();
+  let mut current_type = Some(type_name.to_string());
+
+  while let Some(type_name) = current_type.take() {
+    // protects against classes that extend each other
+    if !visited_types.insert(type_name.clone()) {
+      break;
+    }
+
+    if let Some(InferedType::Compound {
+      type_inference_map,
+      extends,
+      ..
+    }) = store.types.get(&type_name).map(|t| t.as_ref())
+    {
+      for (name, infered_type) in type_inference_map.borrow().iter() {
+        if let InferedType::Function(function) = infered_type.as_ref() {
+          methods.push((name.clone(), function.clone()));
+        }
+      }
+
+      current_type = extends.clone();
+    }
+  }
+
+  methods.sort_by(|a, b| a.0.cmp(&b.0));
+  methods
+}
+
+fn format_method_signature(method: &FunctionInferedType) -> String {
+  let parameters = method
+    .parameters
+    .iter()
+    .map(|parameter| parameter.infered_type.as_str())
+    .collect::<Vec<&str>>()
+    .join(", ");
+
+  match &method.return_type {
+    Some(return_type) => format!("({parameters}): {return_type}"),
+    None => format!("({parameters})")
+  }
+}
@@ -0,0 +1,93 @@
+use lsp_types::{Position, Range};
+
+/// Converts a byte offset in `content` into an LSP position, whose character
+/// is counted in UTF-16 code units.
+pub fn offset_to_position(content: &str, offset: usize) -> Position {
+  let offset = offset.min(content.len());
+  let mut line = 0;
+  let mut line_start = 0;
+
+  for (index, character) in content.char_indices() {
+    if index >= offset {
+      break;
+    }
+
+    if character == '\n' {
+      line += 1;
+      line_start = index + 1;
+    }
+  }
+
+  let character = content[line_start..offset]
+    .chars()
+    .map(char::len_utf16)
+    .sum::<usize>();
+
+  Position::new(line, character as u32)
+}
+
+pub fn range_to_lsp_range(content: &str, range: std::ops::Range<usize>) -> Range {
+  Range::new(
+    offset_to_position(content, range.start),
+    offset_to_position(content, range.end)
+  )
+}
+
+/// Converts an LSP position into a byte offset in `content`. Positions past
+/// the end of their line are clamped to it.
+pub fn position_to_offset(content: &str, position: Position) -> usize {
+  let mut line_start = 0;
+
+  for _ in 0..position.line {
+    match content[line_start..].find('\n') {
+      Some(index) => line_start += index + 1,
+      None => return content.len()
+    };
+  }
+
+  let line = content[line_start..].split('\n').next().unwrap_or("");
+  let mut utf16_count = 0;
+
+  for (index, character) in line.char_indices() {
+    if utf16_count >= position.character as usize {
+      return line_start + index;
+    }
+
+    utf16_count += character.len_utf16();
+  }
+
+  line_start + line.len()
+}
+
+fn is_identifier_byte(byte: u8) -> bool {
+  byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// Returns the start of the identifier that ends at `offset`, or `offset` if
+/// there is none.
+pub fn identifier_start(content: &str, offset: usize) -> usize {
+  let bytes = content.as_bytes();
+  let mut start = offset.min(bytes.len());
+
+  while start > 0 && is_identifier_byte(bytes[start - 1]) {
+    start -= 1;
+  }
+
+  start
+}
+
+/// Returns the identifier under the given offset
+pub fn identifier_at(content: &str, offset: usize) -> Option<&str> {
+  let bytes = content.as_bytes();
+  let start = identifier_start(content, offset);
+  let mut end = offset.min(bytes.len());
+
+  while end < bytes.len() && is_identifier_byte(bytes[end]) {
+    end += 1;
+  }
+
+  match start < end {
+    true => Some(&content[start..end]),
+    false => None
+  }
+}
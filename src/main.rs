@@ -1,41 +1,271 @@
 use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::rc::Rc;
 
 mod ast;
+mod cli;
 mod config;
+mod error;
+mod lsp;
 mod preprocessor;
+mod source_map;
 mod utils;
+mod watch;
 
 extern crate lalrpop_util;
 
-use ariadne::Source;
+use ast::codegen::span_markers::extract_span_markers;
 use ast::codegen::type_inference::TypeInferenceStore;
-use ast::span_manager::SpanManager;
+use ast::span_manager::{FilePathRef, SpanManager};
 use ast::visitor::FunctionsInferenceVisitor;
-use ast::{Program, ProgramInformation, ReportManager};
-use config::{read_config, Config};
+use ast::{Program, ProgramInformation, ReportManager, Span};
+use cli::{Arguments, Command};
+use config::{get_config_path, read_config, Config, DEFAULT_PROFILE};
+use error::{parse_error_diagnostic, CompileError};
 use lalrpop_util::lalrpop_mod;
+use preprocessor::offset_map::OriginalRange;
+use preprocessor::types::{PreprocessorOutput, ProcessedFile};
+use source_map::{get_source_map_path, SourceMap};
 
 use crate::ast::codegen::context::{Context, ContextType};
 use crate::ast::visitor::{
-  CompoundTypesVisitor, ContextBuildingVisitor, ExpressionTypeInferenceVisitor, FunctionVisitor,
-  FunctionsCallsCheckerVisitor, LambdaDeclarationVisitor, LibraryEmitterVisitor,
+  AnalysisResetVisitor, CompoundTypesVisitor, ContextBuildingVisitor,
+  ExpressionTypeInferenceVisitor, FunctionVisitor, FunctionsCallsCheckerVisitor,
+  LambdaDeclarationVisitor, LibraryEmitterVisitor, NamespaceVisitor, TernaryVisitor,
   VariableDeclarationVisitor
 };
-use crate::utils::strip_pragmas;
+use crate::utils::stable_hash;
 
 lalrpop_mod!(pub parser);
 
-fn main() {
-  let config = read_config().expect("Could not read the config cahirc.toml file");
+fn main() -> ExitCode {
+  let result = Arguments::from_env().and_then(|arguments| {
+    // the language server reads the configuration of the workspace the editor
+    // opens, which may not be the current directory.
+    if arguments.command == Command::Lsp {
+      return lsp::run(Path::new(&arguments.project_directory));
+    }
+
+    if let (Command::Map, Some(location)) = (arguments.command, &arguments.location) {
+      return source_map::print_source_location(location);
+    }
+
+    let config = load_config(&arguments)?;
+
+    match arguments.command {
+      Command::Build | Command::Check if arguments.watch => watch::watch(
+        &get_config_path(Path::new(&arguments.project_directory)),
+        config,
+        || load_config(&arguments),
+        |config, cache| compile_source_directory(config, &arguments, cache)
+      ),
+      Command::Build | Command::Check => {
+        compile_source_directory(&config, &arguments, &mut ParseCache::default())
+      }
+      Command::Clean => clean_dist_directory(&config),
+      Command::Lsp | Command::Map => {
+        unreachable!("the lsp and map commands are handled before reading the config")
+      }
+    }
+  });
+
+  match result {
+    Ok(_) => ExitCode::SUCCESS,
+    Err(error) => {
+      eprintln!("error: {error}");
 
-  compile_source_directory(&config).expect("main error");
+      ExitCode::FAILURE
+    }
+  }
 }
 
-fn compile_source_directory(config: &Config) -> std::io::Result<()> {
-  let preprocessed_content = preprocessor::preprocess(&config.package.src, &config.dependencies)?;
+/// Reads the config of the project, with the profile and the macros of the
+/// command line applied.
+fn load_config(arguments: &Arguments) -> Result<Config, CompileError> {
+  let mut config = read_config(Path::new(&arguments.project_directory))?;
+  config.apply_profile(arguments.profile.as_deref().unwrap_or(DEFAULT_PROFILE))?;
+  config.defines.extend(arguments.defines.iter().cloned());
+
+  Ok(config)
+}
+
+/// Compiles the project, the ASTs of the `cache` are reused for the files that
+/// did not change and the ASTs of this compilation are stored in it.
+fn compile_source_directory(
+  config: &Config, arguments: &Arguments, cache: &mut ParseCache
+) -> Result<(), CompileError> {
+  // the check command always runs the static analysis, since printing the
+  // diagnostics is all it does.
+  let static_analysis =
+    config.package.static_analysis.unwrap_or(false) || arguments.command == Command::Check;
+
+  let mut report_manager = ReportManager::new();
+  report_manager.warnings_as_errors = arguments.warnings_as_errors;
+  report_manager.message_format = arguments.message_format;
+
+  let analysis = analyze_project(config, static_analysis, &mut report_manager, cache)?;
+  let result = emit_analyzed_project(config, arguments, &analysis, &report_manager);
+
+  cache.store(analysis);
+
+  result
+}
+
+fn emit_analyzed_project(
+  config: &Config, arguments: &Arguments, analysis: &ProjectAnalysis,
+  report_manager: &ReportManager
+) -> Result<(), CompileError> {
+  // nothing is emitted if the code contains errors, so the previous output
+  // stays untouched.
+  if !analysis.unparsed_files.is_empty() {
+    return Err(CompileError::Syntax {
+      files: analysis.unparsed_files.clone()
+    });
+  }
+
+  if report_manager.has_errors() {
+    return Err(CompileError::Diagnostics {
+      errors: report_manager.error_count,
+      warnings: report_manager.warning_count
+    });
+  }
+
+  if arguments.command == Command::Check {
+    return Ok(());
+  }
+
+  let outputs = emit_project(config, analysis)?;
+
+  write_dist_directory(Path::new(&config.package.dist), &outputs)
+}
+
+/// Everything the analysis of a project produced: the ASTs of its files and
+/// the contexts and types collected from them.
+pub struct ProjectAnalysis {
+  pub preprocessed_content: PreprocessorOutput,
+  pub span_manager: SpanManager,
+  pub global_context: Rc<RefCell<Context>>,
+  pub inference_store: TypeInferenceStore,
+  pub dependency_ast_list: Vec<ParsedFile>,
+  pub ast_list: Vec<ParsedFile>,
+
+  /// The files that could not be parsed, their syntax errors were already
+  /// reported.
+  pub unparsed_files: Vec<PathBuf>
+}
+
+impl ProjectAnalysis {
+  /// Returns the range of the file the user wrote that the span comes from,
+  /// along with the file the span is in. The range is in the included file it
+  /// names, if any, otherwise in the original content of that file.
+  pub fn get_original_range(&self, span: Span) -> Option<(&ProcessedFile, OriginalRange)> {
+    let file = self
+      .preprocessed_content
+      .get_file(self.span_manager.get_source(&span))?;
+    let range = file
+      .offset_map
+      .borrow()
+      .get_original_range(self.span_manager.get_range(span));
+
+    Some((file, range))
+  }
+}
+
+/// The ASTs of a previous compilation, the watch mode gives them to the next
+/// one so the files that did not change are not parsed again.
+#[derive(Default)]
+pub struct ParseCache {
+  /// The span manager the spans of the ASTs point into
+  span_manager: SpanManager,
+
+  /// The ASTs of the files by name, along with the preprocessed content they
+  /// were parsed from and the source their spans point to.
+  files: HashMap<String, (String, Program, FilePathRef)>,
+
+  /// The files the `#include` directives of the previous compilation read
+  pub included_files: BTreeSet<PathBuf>
+}
+
+impl ParseCache {
+  /// Keeps the ASTs of the analysis for the next compilation
+  pub fn store(&mut self, analysis: ProjectAnalysis) {
+    let ProjectAnalysis {
+      preprocessed_content,
+      span_manager,
+      dependency_ast_list,
+      ast_list,
+      ..
+    } = analysis;
+
+    self.span_manager = span_manager;
+    self.included_files = preprocessed_content.included_files.clone();
+
+    for parsed_file in dependency_ast_list.into_iter().chain(ast_list) {
+      if let Some(file) = preprocessed_content.get_file(&parsed_file.filename) {
+        let content = file.content.borrow().to_string();
+
+        self.files.insert(
+          parsed_file.filename,
+          (content, parsed_file.ast, parsed_file.source_ref)
+        );
+      }
+    }
+  }
+
+  /// Returns the AST of the file if its content is the same as in the
+  /// previous compilation, along with the source its spans point to. It is
+  /// reset so it can be analyzed again.
+  fn take(&mut self, filename: &str, file: &ProcessedFile) -> Option<(Program, FilePathRef)> {
+    let (content, ast, source_ref) = self.files.remove(filename)?;
+
+    if content != *file.content.borrow() {
+      return None;
+    }
+
+    use ast::visitor::Visited;
+    ast.accept(&mut AnalysisResetVisitor);
+
+    Some((ast, source_ref))
+  }
+
+  /// Drops the ASTs of the files that changed or were deleted since the
+  /// previous compilation, then the sources that no AST uses anymore are
+  /// removed from the span manager. So it doesn't grow with every compilation.
+  fn drop_changed_files(&mut self, preprocessed_content: &PreprocessorOutput) {
+    self.files.retain(|filename, (content, _, _)| {
+      preprocessed_content
+        .get_file(filename)
+        .is_some_and(|file| *content == *file.content.borrow())
+    });
+
+    let sources = self
+      .files
+      .values()
+      .map(|(_, _, source_ref)| *source_ref)
+      .collect();
+
+    self.span_manager.retain_sources(&sources);
+  }
+}
+
+/// Runs the preprocessor, the parser and the visitors over the project. The
+/// diagnostics are sent to the `report_manager` as they are found.
+///
+/// The preprocessor always runs over every file, as the macros of a file can
+/// change the content of any other one. Only the files whose content changed
+/// since the compilation that filled the `cache` are parsed, but the visitors
+/// run over all of them since a change in a file can change the names and the
+/// types the others use.
+pub fn analyze_project(
+  config: &Config, static_analysis: bool, report_manager: &mut ReportManager,
+  cache: &mut ParseCache
+) -> Result<ProjectAnalysis, CompileError> {
+  // the cache is emptied first, so it stays consistent if the analysis fails
+  let mut previous_cache = std::mem::take(cache);
+  let mut preprocessed_content = preprocessor::preprocess(config)?;
 
   let program_information = ProgramInformation::new();
   let global_context = Rc::new(RefCell::new(Context::new(
@@ -44,115 +274,94 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
     ContextType::Global
   )));
 
+  previous_cache.drop_changed_files(&preprocessed_content);
+  let mut sources_span_manager = std::mem::take(&mut previous_cache.span_manager);
+
+  for (filename, diagnostic) in std::mem::take(&mut preprocessed_content.diagnostics) {
+    let Some(file) = preprocessed_content.get_file(&filename) else {
+      continue;
+    };
+
+    let span = sources_span_manager.add_source(filename.clone()).span(
+      diagnostic.offset,
+      diagnostic.offset,
+      "preprocessor"
+    );
+
+    report_manager.push_original(diagnostic, span, &filename, file)?;
+  }
+
   // 1.
   // Build the list of AST from the files
-  let mut sources_span_manager = SpanManager::new();
   let mut dependency_ast_list = Vec::new();
   let mut ast_list = Vec::new();
+  let mut unparsed_files = Vec::new();
 
   // starting with the dependencies
-  for (_name, value) in preprocessed_content.dependencies_files_content.iter() {
+  for (dependency_name, value) in preprocessed_content.dependencies_files_content.iter() {
     for (filename, file) in value.iter() {
-      let content = strip_pragmas(&file.content.borrow());
-      let mut span_maker = sources_span_manager.add_source(filename.clone());
-
-      if file
-        .content
-        .borrow()
-        .contains("#pragma cahirc-preprocessor-print")
-      {
-        println!("{}", &file.content.borrow());
-      }
+      let ast = match previous_cache.take(filename, file) {
+        Some(ast) => Some(ast),
+        None => parse_file(
+          &program_information,
+          &mut sources_span_manager,
+          filename,
+          file,
+          report_manager
+        )?
+      };
+      let Some((expr, source_ref)) = ast else {
+        unparsed_files.push(file.path.clone());
 
-      let expr = parser::ProgramParser::new()
-        .parse(&program_information, &mut span_maker, &content)
-        .unwrap();
+        continue;
+      };
+
+      // the path is relative to the dependency's directory, so the mangled names
+      // don't depend on where the dependency is on the disk.
+      let relative_path = config
+        .dependencies
+        .get(dependency_name)
+        .and_then(|directory| file.path.strip_prefix(directory).ok())
+        .unwrap_or(&file.path)
+        .to_string_lossy()
+        .replace("\\", "/");
 
       dependency_ast_list.push(ParsedFile {
         ast: expr,
         file_path: file.path.clone(),
-        filename: filename.clone()
+        filename: filename.clone(),
+        source_ref,
+        library_path: Some(format!(
+          "{}/{dependency_name}/{relative_path}",
+          config.package.name
+        ))
       });
     }
   }
 
   for (filename, file) in preprocessed_content.source_files_content.iter() {
-    use ariadne::{ColorGenerator, Label, Report, ReportKind};
-
-    let content = strip_pragmas(&file.content.borrow());
-    let mut span_maker = sources_span_manager.add_source(filename.clone());
-
-    if file
-      .content
-      .borrow()
-      .contains("#pragma cahirc-preprocessor-print")
-    {
-      println!("{}", &file.content.borrow());
-    }
-
-    let expr = parser::ProgramParser::new().parse(&program_information, &mut span_maker, &content);
-    let expr = match expr {
-      Ok(expr) => expr,
-      Err(error) => {
-        match error {
-          lalrpop_util::ParseError::InvalidToken { location } => {
-            let mut colors = ColorGenerator::new();
-            let a = colors.next();
-            let absolute_path =
-              dunce::canonicalize(std::env::current_dir().unwrap().join(&file.path)).unwrap();
-
-            Report::build(ReportKind::Error, (), location)
-              .with_message(&format!(
-                "Invalid token in file://{}",
-                absolute_path.to_str().unwrap().replace("\\", "/")
-              ))
-              .with_label(
-                Label::new(location..location.checked_add(1).unwrap_or(location))
-                  .with_message("The invalid token")
-                  .with_color(a)
-              )
-              .finish()
-              .print(Source::from(&content))
-              .unwrap();
-          }
-          lalrpop_util::ParseError::UnrecognizedEOF {
-            location: _,
-            expected: _
-          } => {
-            println!("Unrecognized EOF in {}", filename);
-          }
-          lalrpop_util::ParseError::UnrecognizedToken { token, expected } => {
-            let mut colors = ColorGenerator::new();
-            let a = colors.next();
-            let absolute_path =
-              dunce::canonicalize(std::env::current_dir().unwrap().join(&file.path)).unwrap();
-
-            Report::build(ReportKind::Error, (), token.0)
-              .with_message(&format!(
-                "Unrecognized token in file://{}",
-                absolute_path.to_str().unwrap().replace("\\", "/")
-              ))
-              .with_label(
-                Label::new(token.0..token.2)
-                  .with_message(format!("Expected {}", expected.join(" | ")))
-                  .with_color(a)
-              )
-              .finish()
-              .print(Source::from(&content))
-              .unwrap();
-          }
-          lalrpop_util::ParseError::ExtraToken { token: _ } => todo!(),
-          lalrpop_util::ParseError::User { error: _ } => todo!()
-        };
+    let ast = match previous_cache.take(filename, file) {
+      Some(ast) => Some(ast),
+      None => parse_file(
+        &program_information,
+        &mut sources_span_manager,
+        filename,
+        file,
+        report_manager
+      )?
+    };
+    let Some((expr, source_ref)) = ast else {
+      unparsed_files.push(file.path.clone());
 
-        continue;
-      }
+      continue;
     };
 
     ast_list.push(ParsedFile {
       ast: expr,
       file_path: file.path.clone(),
-      filename: filename.clone()
+      filename: filename.clone(),
+      source_ref,
+      library_path: None
     });
   }
 
@@ -170,7 +379,9 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
       ContextType::Global
     )));
 
-    file_context.borrow_mut().set_as_library();
+    if let Some(library_path) = &parsed_file.library_path {
+      file_context.borrow_mut().set_as_library(library_path);
+    }
 
     Context::set_parent_context(&file_context, &global_context);
 
@@ -190,7 +401,6 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
     parsed_file.ast.accept(&mut variable_declaration_visitor);
   }
 
-  let mut report_manager = ReportManager::new();
   // perform a first pass to build the contexts
   for parsed_file in &ast_list {
     let file_context_name = format!("file: {:#?}", parsed_file.file_path.file_name().unwrap());
@@ -211,6 +421,20 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
     parsed_file.ast.accept(&mut context_builder);
   }
 
+  // the namespaced names can be resolved once the contexts of every file are
+  // built
+  for parsed_file in dependency_ast_list.iter().chain(&ast_list) {
+    let mut namespace_visitor = NamespaceVisitor::new(
+      global_context.clone(),
+      report_manager,
+      &mut sources_span_manager
+    );
+
+    use ast::visitor::Visited;
+    parsed_file.ast.accept(&mut namespace_visitor);
+    report_manager.consume_multiple_sources(&mut sources_span_manager, &preprocessed_content)?;
+  }
+
   // then perform the pass of visitors
   for parsed_file in &ast_list {
     let file_context_name = format!("file: {:#?}", parsed_file.file_path.file_name().unwrap());
@@ -222,7 +446,7 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
       .iter()
       .find(|c| c.borrow().name == file_context_name)
     else {
-      panic!("Missing context in 2nd compilation pass for file {file_context_name}");
+      return Err(CompileError::MissingContext(file_context_name));
     };
 
     let mut variable_declaration_visitor = VariableDeclarationVisitor::new(&program_information);
@@ -237,11 +461,11 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
     parsed_file.ast.accept(&mut function_visitor);
     parsed_file.ast.accept(&mut variable_declaration_visitor);
 
-    if config.package.static_analysis.unwrap_or(false) {
+    if static_analysis {
       let mut compound_types_visitor = CompoundTypesVisitor::new(
         file_context.clone(),
         &mut inference_store,
-        &mut report_manager,
+        report_manager,
         &mut sources_span_manager
       );
       parsed_file.ast.accept(&mut compound_types_visitor);
@@ -249,21 +473,21 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
         .source_files_content
         .get(&parsed_file.filename);
       if let Some(file) = file {
-        report_manager.consume(&file.content.borrow());
+        report_manager.consume(&parsed_file.filename, file)?;
       }
     }
   }
 
   // 2.1
   // do a second pass for the type inference
-  if config.package.static_analysis.unwrap_or(false) {
+  if static_analysis {
     for parsed_file in &ast_list {
       use ast::visitor::Visited;
 
       let mut expression_inference_visitor = ExpressionTypeInferenceVisitor::new(
         global_context.clone(),
         &mut inference_store,
-        &mut report_manager,
+        report_manager,
         &mut sources_span_manager
       );
 
@@ -273,43 +497,113 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
         .source_files_content
         .get(&parsed_file.filename);
       if let Some(file) = file {
-        report_manager.consume(&file.content.borrow());
+        report_manager.consume(&parsed_file.filename, file)?;
       }
 
       let mut functions_inference_visitor = FunctionsInferenceVisitor::new(
         global_context.clone(),
         &mut inference_store,
-        &mut report_manager,
+        report_manager,
         &mut sources_span_manager
       );
 
       parsed_file.ast.accept(&mut functions_inference_visitor);
-      report_manager.consume_multiple_sources(&mut sources_span_manager, &preprocessed_content);
+      report_manager.consume_multiple_sources(&mut sources_span_manager, &preprocessed_content)?;
 
       let mut function_call_checker_visitor = FunctionsCallsCheckerVisitor::new(
         global_context.clone(),
         &mut inference_store,
-        &mut report_manager,
+        report_manager,
         &mut sources_span_manager
       );
 
       parsed_file.ast.accept(&mut function_call_checker_visitor);
-      report_manager.consume_multiple_sources(&mut sources_span_manager, &preprocessed_content);
+      report_manager.consume_multiple_sources(&mut sources_span_manager, &preprocessed_content)?;
     }
   }
 
+  // the ternaries are given their temporary variables once the types of the
+  // expressions are known
+  for parsed_file in dependency_ast_list.iter().chain(&ast_list) {
+    let mut ternary_visitor = TernaryVisitor::new(
+      global_context.clone(),
+      &inference_store,
+      report_manager,
+      &mut sources_span_manager
+    );
+
+    use ast::visitor::Visited;
+    parsed_file.ast.accept(&mut ternary_visitor);
+    report_manager.consume_multiple_sources(&mut sources_span_manager, &preprocessed_content)?;
+  }
+
+  Ok(ProjectAnalysis {
+    preprocessed_content,
+    span_manager: sources_span_manager,
+    global_context,
+    inference_store,
+    dependency_ast_list,
+    ast_list,
+    unparsed_files
+  })
+}
+
+/// Parses the file, the parser recovers from the syntax errors so all of them
+/// are reported at once. Returns `None` if the file contains any, or the AST
+/// along with the source its spans point to.
+fn parse_file(
+  program_information: &ProgramInformation, span_manager: &mut SpanManager, filename: &str,
+  file: &ProcessedFile, report_manager: &mut ReportManager
+) -> Result<Option<(Program, FilePathRef)>, CompileError> {
+  let content = file.content.borrow();
+  let mut span_maker = span_manager.add_source(filename.to_string());
+  let mut errors = Vec::new();
+
+  let result =
+    parser::ProgramParser::new().parse(program_information, &mut span_maker, &mut errors, &content);
+
+  let mut syntax_errors: Vec<_> = errors.into_iter().map(|recovery| recovery.error).collect();
+  let program = match result {
+    Ok(program) => Some(program),
+    Err(error) => {
+      syntax_errors.push(error);
+
+      None
+    }
+  };
+
+  if syntax_errors.is_empty() {
+    return Ok(program.map(|program| (program, span_maker.source_ref)));
+  }
+
+  for error in syntax_errors {
+    let diagnostic = parse_error_diagnostic(error, file);
+    let span = span_maker.span(diagnostic.offset, diagnostic.offset, "syntax error");
+
+    report_manager.push(diagnostic, span);
+  }
+
+  report_manager.consume(filename, file)?;
+
+  Ok(None)
+}
+
+/// Emits the code of the analyzed project, returns the path of every output
+/// file along with its content.
+fn emit_project(
+  config: &Config, analysis: &ProjectAnalysis
+) -> Result<Vec<(PathBuf, String)>, CompileError> {
+  let global_context = &analysis.global_context;
+
   // 3.
   // Emit code using the information we collected in the previous step
-  if let Err(_) = std::fs::remove_dir_all(&config.package.dist) {}
+  let mut outputs = Vec::new();
 
-  for parsed_file in &ast_list {
+  for parsed_file in &analysis.ast_list {
     let new_path = parsed_file
       .file_path
       .strip_prefix(&config.package.src)
-      .expect(&format!(
-        "could not form the path to {:?}'s output file",
-        &parsed_file.file_path,
-      ))
+      .unwrap_or(&parsed_file.file_path)
       .to_path_buf();
 
     let mut new_path = Path::new(&config.package.dist).join(new_path);
@@ -321,19 +615,17 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
     parsed_file
       .ast
       .emit(&global_context.borrow(), &mut output_code)
-      .expect("failed to emit code");
+      .map_err(|error| CompileError::Codegen {
+        path: parsed_file.file_path.clone(),
+        error
+      })?;
 
-    std::fs::create_dir_all(&new_path.parent().unwrap())
-      .expect("failed to recursively make the output directories");
+    let code = std::str::from_utf8(&output_code).map_err(|error| CompileError::Codegen {
+      path: parsed_file.file_path.clone(),
+      error: std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    })?;
 
-    match std::str::from_utf8(&output_code) {
-      Ok(s) => {
-        if !s.trim().is_empty() {
-          fs::write(new_path, format_code(s)).expect("failed to write output file")
-        }
-      }
-      Err(e) => println!("{}", e)
-    };
+    push_emitted_code(&mut outputs, new_path, code, analysis)?;
 
     // (*global_context).borrow().print(0);
   }
@@ -342,42 +634,122 @@ fn compile_source_directory(config: &Config) -> std::io::Result<()> {
   // emit code for the libraries code, especially the generic functions that
   // were used.
   let generated_code_file = Path::new(&config.package.dist)
-    .join(uuid::Uuid::new_v4().to_string())
+    .join(format!("wss{}", stable_hash(&[&config.package.name])))
     .with_extension("ws");
 
   let mut file_content = Vec::new();
 
-  std::fs::create_dir_all(&generated_code_file.parent().unwrap())
-    .expect("failed to recursively make the output directories");
-
-  for parsed_file in &dependency_ast_list {
+  for parsed_file in &analysis.dependency_ast_list {
     use ast::visitor::Visited;
-    let mut visitor = LibraryEmitterVisitor::new(&global_context, &mut file_content);
+    let mut visitor = LibraryEmitterVisitor::new(global_context, &mut file_content);
     parsed_file.ast.accept(&mut visitor);
 
     let mut visitor = LambdaDeclarationVisitor::new(&mut file_content);
     parsed_file.ast.accept(&mut visitor);
   }
 
-  for parsed_file in &ast_list {
+  for parsed_file in &analysis.ast_list {
     use ast::visitor::Visited;
 
     let mut visitor = LambdaDeclarationVisitor::new(&mut file_content);
     parsed_file.ast.accept(&mut visitor);
   }
 
-  match std::str::from_utf8(&file_content) {
-    Ok(s) => {
-      if !s.trim().is_empty() {
-        fs::write(&generated_code_file, format_code(s)).expect("failed to write output file")
-      }
+  let code = std::str::from_utf8(&file_content).map_err(|error| CompileError::Codegen {
+    path: generated_code_file.clone(),
+    error: std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+  })?;
+
+  push_emitted_code(&mut outputs, generated_code_file, code, analysis)?;
+
+  Ok(outputs)
+}
+
+/// Removes the span markers from the emitted code then adds it to the outputs
+/// along with its source map.
+fn push_emitted_code(
+  outputs: &mut Vec<(PathBuf, String)>, path: PathBuf, code: &str, analysis: &ProjectAnalysis
+) -> Result<(), CompileError> {
+  let (code, spans) = extract_span_markers(code);
+
+  if code.trim().is_empty() {
+    return Ok(());
+  }
+
+  let code = format_code(&code);
+  let source_map = SourceMap::new(&spans, code.lines().count(), analysis)
+    .to_json()
+    .map_err(|error| CompileError::Codegen {
+      path: path.clone(),
+      error: error.into()
+    })?;
+
+  outputs.push((get_source_map_path(&path), source_map));
+  outputs.push((path, code));
+
+  Ok(())
+}
+
+/// Writes the emitted files into the dist directory. The files whose content
+/// did not change are left untouched and the files that are no longer emitted
+/// are removed, so only the affected outputs are rewritten between two builds.
+fn write_dist_directory(dist: &Path, outputs: &[(PathBuf, String)]) -> Result<(), CompileError> {
+  let emitted_paths: HashSet<&Path> = outputs.iter().map(|(path, _)| path.as_path()).collect();
+  let stale_files = walkdir::WalkDir::new(dist)
+    .into_iter()
+    .filter_map(Result::ok)
+    .filter(|entry| entry.file_type().is_file() && !emitted_paths.contains(entry.path()));
+
+  for entry in stale_files {
+    fs::remove_file(entry.path()).map_err(|error| CompileError::Io {
+      path: entry.path().to_path_buf(),
+      error
+    })?;
+  }
+
+  for (path, content) in outputs {
+    let is_unchanged = fs::read_to_string(path)
+      .map(|existing_content| &existing_content == content)
+      .unwrap_or(false);
+
+    if is_unchanged {
+      continue;
     }
-    Err(e) => println!("{}", e)
-  };
+
+    if let Some(parent) = path.parent() {
+      create_output_directory(parent)?;
+    }
+
+    write_output_file(path, content)?;
+  }
 
   Ok(())
 }
 
+fn clean_dist_directory(config: &Config) -> Result<(), CompileError> {
+  match std::fs::remove_dir_all(&config.package.dist) {
+    Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(CompileError::Io {
+      path: PathBuf::from(&config.package.dist),
+      error
+    }),
+    _ => Ok(())
+  }
+}
+
+fn create_output_directory(path: &Path) -> Result<(), CompileError> {
+  std::fs::create_dir_all(path).map_err(|error| CompileError::Io {
+    path: path.to_path_buf(),
+    error
+  })
+}
+
+fn write_output_file(path: &Path, content: &str) -> Result<(), CompileError> {
+  fs::write(path, content).map_err(|error| CompileError::Io {
+    path: path.to_path_buf(),
+    error
+  })
+}
+
 fn format_code(origin: &str) -> String {
   let mut lines: Vec<String> = origin.lines().map(|s| s.to_string()).collect();
   let mut depth = 0;
@@ -399,8 +771,225 @@ fn format_code(origin: &str) -> String {
   lines.join("\n")
 }
 
-struct ParsedFile {
-  file_path: PathBuf,
-  ast: Program,
-  filename: String
+pub struct ParsedFile {
+  pub file_path: PathBuf,
+  pub ast: Program,
+  pub filename: String,
+
+  /// The source the spans of the AST point to in the span manager
+  pub source_ref: FilePathRef,
+
+  /// For the files coming from a dependency, the package, the dependency and
+  /// the path of the file in the dependency. Used to mangle the names of its
+  /// declarations.
+  pub library_path: Option<String>
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A project in the system's temporary directory, removed once the test
+  /// ends.
+  struct TestProject(PathBuf);
+
+  impl TestProject {
+    fn new(name: &str, files: &[(&str, &str)]) -> Self {
+      let path = std::env::temp_dir().join(format!("cahirc-compile-{name}-{}", std::process::id()));
+      fs::create_dir_all(&path).unwrap();
+
+      for (file, content) in files {
+        fs::write(path.join(file), content).unwrap();
+      }
+
+      Self(path)
+    }
+
+    /// Analyzes the project with the static analysis, returns the emitted code
+    /// of its files and the reports. Like in a build, nothing is emitted when
+    /// an error is reported.
+    fn compile(&self) -> (String, ReportManager) {
+      let source_directory = self.0.to_string_lossy();
+      let config: Config = toml::from_str(&format!(
+        r#"
+          [package]
+          name = "test"
+          src = {source_directory:?}
+          dist = "dist"
+
+          [dependencies]
+        "#
+      ))
+      .unwrap();
+
+      let mut report_manager = ReportManager::new();
+      report_manager.collect_reports = true;
+
+      let analysis = analyze_project(
+        &config,
+        true,
+        &mut report_manager,
+        &mut ParseCache::default()
+      )
+      .unwrap();
+
+      if report_manager.has_errors() {
+        return (String::new(), report_manager);
+      }
+
+      let code = emit_project(&config, &analysis)
+        .unwrap()
+        .into_iter()
+        .filter(|(path, _)| path.extension().is_some_and(|extension| extension == "ws"))
+        .map(|(_, code)| code)
+        .collect();
+
+      (code, report_manager)
+    }
+  }
+
+  impl Drop for TestProject {
+    fn drop(&mut self) {
+      let _ = fs::remove_dir_all(&self.0);
+    }
+  }
+
+  #[test]
+  fn gives_each_for_in_loop_of_a_function_its_own_index() {
+    let project = TestProject::new(
+      "for-in",
+      &[(
+        "main.wss",
+        "function run(list: array<int>) {
+          for a: int in list {}
+          for a: int in list {}
+        }"
+      )]
+    );
+
+    let (code, _) = project.compile();
+    let mut indexes: Vec<&str> = code
+      .split(|c: char| !c.is_alphanumeric())
+      .filter(|word| word.starts_with("idx"))
+      .collect();
+    indexes.sort();
+    indexes.dedup();
+
+    assert_eq!(indexes.len(), 2, "{code}");
+  }
+
+  #[test]
+  fn separates_the_nested_negations() {
+    let project = TestProject::new(
+      "negate",
+      &[(
+        "main.wss",
+        "function run(a: int): int {
+          return - -a - - -a;
+        }"
+      )]
+    );
+
+    let (code, _) = project.compile();
+
+    assert!(code.contains("return -(-a)- -(-a);"), "{code}");
+  }
+
+  #[test]
+  fn rejects_the_ternaries_on_the_right_side_of_a_boolean_join() {
+    let project = TestProject::new(
+      "ternary-join",
+      &[(
+        "main.wss",
+        "function run(a: bool, b: bool): bool {
+          var left: bool = (b ? a : b) && a;
+          var right: bool = a || (b ? a : b);
+
+          return left && right;
+        }"
+      )]
+    );
+
+    let (_, report_manager) = project.compile();
+    let messages: Vec<_> = report_manager
+      .collected_reports
+      .iter()
+      .filter_map(|(diagnostic, _)| diagnostic.labels.first()?.message.as_deref())
+      .collect();
+
+    assert_eq!(
+      messages,
+      vec!["A ternary cannot be used on the right side of `&&` or `||`"]
+    );
+  }
+
+  /// Returns the messages of the errors the project reports
+  fn get_error_messages(report_manager: &ReportManager) -> Vec<String> {
+    report_manager
+      .collected_reports
+      .iter()
+      .filter(|(diagnostic, _)| diagnostic.kind == ariadne::ReportKind::Error)
+      .filter_map(|(diagnostic, _)| diagnostic.labels.first()?.message.clone())
+      .collect()
+  }
+
+  #[test]
+  fn accepts_null_for_the_classes_only() {
+    let project = TestProject::new(
+      "null",
+      &[(
+        "main.wss",
+        "class Item {}
+        struct Position { var x: int; }
+        enum Direction { North, South }
+
+        function use_item(item: Item) {}
+        function use_position(position: Position) {}
+        function use_direction(direction: Direction) {}
+        function use_number(number: int) {}
+
+        function run() {
+          use_item(NULL);
+          use_position(NULL);
+          use_direction(NULL);
+          use_number(NULL);
+        }"
+      )]
+    );
+
+    let (_, report_manager) = project.compile();
+
+    assert_eq!(
+      get_error_messages(&report_manager),
+      vec![
+        "Parameter n°1 is expected to be a Position but a NULL was passed",
+        "Parameter n°1 is expected to be a Direction but a NULL was passed",
+        "Parameter n°1 is expected to be a int but a NULL was passed"
+      ]
+    );
+  }
+
+  #[test]
+  fn rejects_null_in_a_ternary_of_structs() {
+    let project = TestProject::new(
+      "null-ternary",
+      &[(
+        "main.wss",
+        "class Item {}
+        struct Position { var x: int; }
+
+        function run(a: bool, item: Item, position: Position) {
+          var b: Item = a ? item : NULL;
+          var c: Position = a ? position : NULL;
+        }"
+      )]
+    );
+
+    let (_, report_manager) = project.compile();
+
+    assert_eq!(
+      get_error_messages(&report_manager),
+      vec!["The branches result in a Position and a NULL"]
+    );
+  }
 }
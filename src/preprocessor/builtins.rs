@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::lexer::{tokenize, TokenKind};
+use super::macro_registry::FileOrigin;
+use super::offset_map::OffsetMap;
+use super::types::{DependencyName, PreprocessorError, ProcessedFile};
+use crate::config::Config;
+use crate::utils::LineIndex;
+
+/// The macros the preprocessor provides, their names are reserved
+pub const BUILTIN_MACROS: [&str; 6] = [
+  "__FILE__",
+  "__LINE__",
+  "__FUNCTION__",
+  "__PACKAGE__",
+  "__VERSION__",
+  "__BUILD_TIMESTAMP__"
+];
+
+/// The values of the built-in macros that don't depend on the call site
+pub struct BuiltinValues {
+  package_name: String,
+  package_version: Option<String>,
+  source_directory: PathBuf,
+  dependencies: BTreeMap<DependencyName, String>,
+
+  /// The time of the build, in the ISO 8601 format
+  build_timestamp: String
+}
+
+impl BuiltinValues {
+  pub fn new(config: &Config) -> Self {
+    Self {
+      package_name: config.package.name.clone(),
+      package_version: config.package.version.clone(),
+      source_directory: PathBuf::from(&config.package.src),
+      dependencies: config.dependencies.clone(),
+      build_timestamp: get_build_timestamp()
+    }
+  }
+}
+
+/// The call of a built-in macro
+pub struct BuiltinCall<'a> {
+  pub name: &'a str,
+  pub origin: FileOrigin<'a>,
+  pub file: &'a ProcessedFile,
+
+  /// The offset of the call in the preprocessed content
+  pub offset: usize
+}
+
+/// Returns the code the call of the built-in macro expands to
+pub fn expand_builtin(
+  call: &BuiltinCall, values: &BuiltinValues, content: &str, offset_map: &OffsetMap
+) -> Result<String, PreprocessorError> {
+  // the code of an included file reports the included file
+  let location = offset_map.get_original_range(call.offset..call.offset);
+  let path = call.file.path.to_string_lossy();
+  let (path, original) = location.get_source(&path, &call.file.original_content);
+
+  let value = match call.name {
+    "__FILE__" => format!("\"{}\"", get_file_path(call, Path::new(path), values)),
+    "__LINE__" => {
+      let (line, _) = LineIndex::new(original).get_line_and_column(original, location.range.start);
+
+      line.to_string()
+    }
+    "__FUNCTION__" => match find_enclosing_function(content, call.offset) {
+      Some(function) => format!("\"{function}\""),
+      None => {
+        return Err(PreprocessorError::new(
+          String::from("__FUNCTION__! is used outside of a function"),
+          call.offset
+        ))
+      }
+    },
+    "__PACKAGE__" => match call.origin.package {
+      Some(dependency) => format!("\"{dependency}\""),
+      None => format!("\"{}\"", values.package_name)
+    },
+    "__VERSION__" => match &values.package_version {
+      Some(version) => format!("\"{version}\""),
+      None => {
+        return Err(PreprocessorError::new(
+          String::from(
+            "__VERSION__! needs a `version` in the [package] section of the cahirc.toml file"
+          ),
+          call.offset
+        ))
+      }
+    },
+    "__BUILD_TIMESTAMP__" => format!("\"{}\"", values.build_timestamp),
+    name => unreachable!("{name} is not a built-in macro")
+  };
+
+  Ok(value)
+}
+
+/// Returns the path of the file relative to the source directory, or to the
+/// directory of its dependency prefixed with the name of the dependency. So the
+/// path doesn't depend on where the project is on the disk.
+fn get_file_path(call: &BuiltinCall, path: &Path, values: &BuiltinValues) -> String {
+  let (prefix, directory) = match call.origin.package {
+    Some(dependency) => (
+      format!("{dependency}/"),
+      values
+        .dependencies
+        .get(dependency)
+        .map(PathBuf::from)
+        .unwrap_or_default()
+    ),
+    None => (String::new(), values.source_directory.clone())
+  };
+
+  let relative_path = path
+    .strip_prefix(&directory)
+    .unwrap_or(path)
+    .to_string_lossy()
+    .replace('\\', "/");
+
+  format!("{prefix}{}", relative_path.trim_start_matches("./"))
+}
+
+/// Returns the name of the function or the event the offset is in
+fn find_enclosing_function(content: &str, offset: usize) -> Option<String> {
+  let code = &content[..offset];
+  let mut scopes: Vec<Option<String>> = Vec::new();
+  let mut pending_function = None;
+  let mut previous_keyword = None;
+
+  for token in tokenize(code) {
+    let text = &code[token.range.clone()];
+
+    match (token.kind, text) {
+      (TokenKind::Identifier, _) if previous_keyword.is_some() => {
+        pending_function = Some(text.to_string());
+        previous_keyword = None;
+      }
+      (TokenKind::Identifier, "function" | "event") => previous_keyword = Some(text),
+      (TokenKind::Symbol, "{") => scopes.push(pending_function.take()),
+      (TokenKind::Symbol, "}") => {
+        scopes.pop();
+      }
+      // a function without a body, like an imported one
+      (TokenKind::Symbol, ";") => pending_function = None,
+      _ => {}
+    };
+  }
+
+  scopes.into_iter().rev().flatten().next()
+}
+
+/// Returns the current time in the ISO 8601 format. `SOURCE_DATE_EPOCH` can
+/// replace the current time for reproducible builds.
+fn get_build_timestamp() -> String {
+  let seconds = std::env::var("SOURCE_DATE_EPOCH")
+    .ok()
+    .and_then(|epoch| epoch.parse::<u64>().ok())
+    .unwrap_or_else(|| {
+      SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+    });
+
+  let days = (seconds / 86400) as i64;
+  let time = seconds % 86400;
+  let (year, month, day) = get_date_from_days(days);
+
+  format!(
+    "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+    time / 3600,
+    time % 3600 / 60,
+    time % 60
+  )
+}
+
+/// Converts a number of days since 1970-01-01 into a year, a month and a day
+/// of the gregorian calendar.
+fn get_date_from_days(days: i64) -> (i64, i64, i64) {
+  let days = days + 719468;
+  let era = days.div_euclid(146097);
+  let day_of_era = days.rem_euclid(146097);
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 {
+    month_index + 3
+  } else {
+    month_index - 9
+  };
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+  (year, month, day)
+}
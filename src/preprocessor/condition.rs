@@ -0,0 +1,424 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use super::lexer::TokenKind;
+use super::macro_registry::MacroScope;
+use super::types::{MacroDefinition, PreprocessorError};
+
+/// The condition of a `#if`, `#elif`, `#ifdef` or `#ifndef` directive
+#[derive(Debug)]
+pub enum Condition {
+  /// `#ifdef NAME`, true when the macro is defined whatever its value is
+  Defined(String),
+
+  /// A macro used in a condition, with the offset of its name
+  Macro(String, usize),
+
+  /// A number, a string or a boolean
+  Literal(String),
+
+  Not(Box<Condition>),
+  And(Box<Condition>, Box<Condition>),
+  Or(Box<Condition>, Box<Condition>),
+  Comparison(Box<Condition>, ComparisonOperator, Box<Condition>)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ComparisonOperator {
+  Equal,
+  NotEqual,
+  Lower,
+  LowerOrEqual,
+  Greater,
+  GreaterOrEqual
+}
+
+/// A significant token of a condition: its kind, its text and its range
+pub type ConditionToken<'a> = (TokenKind, &'a str, Range<usize>);
+
+/// Parses the tokens of a `#if` condition, from the lowest to the highest
+/// precedence: `||`, `&&`, `!`, the comparisons and then the operands.
+pub fn parse_condition(
+  tokens: &[ConditionToken], directive_start: usize
+) -> Result<Condition, PreprocessorError> {
+  let mut parser = ConditionParser {
+    tokens,
+    position: 0,
+    directive_start
+  };
+
+  let condition = parser.parse_or()?;
+
+  match parser.tokens.get(parser.position) {
+    Some((_, text, range)) => Err(PreprocessorError::new(
+      format!("unexpected `{text}` in the condition"),
+      range.start
+    )),
+    None => Ok(condition)
+  }
+}
+
+struct ConditionParser<'a, 'b> {
+  tokens: &'b [ConditionToken<'a>],
+  position: usize,
+  directive_start: usize
+}
+
+impl<'a, 'b> ConditionParser<'a, 'b> {
+  fn parse_or(&mut self) -> Result<Condition, PreprocessorError> {
+    let mut left = self.parse_and()?;
+
+    while self.eat_symbols(&["|", "|"]) {
+      left = Condition::Or(Box::new(left), Box::new(self.parse_and()?));
+    }
+
+    Ok(left)
+  }
+
+  fn parse_and(&mut self) -> Result<Condition, PreprocessorError> {
+    let mut left = self.parse_not()?;
+
+    while self.eat_symbols(&["&", "&"]) {
+      left = Condition::And(Box::new(left), Box::new(self.parse_not()?));
+    }
+
+    Ok(left)
+  }
+
+  fn parse_not(&mut self) -> Result<Condition, PreprocessorError> {
+    if !self.peek_symbols(&["!", "="]) && self.eat_symbols(&["!"]) {
+      return Ok(Condition::Not(Box::new(self.parse_not()?)));
+    }
+
+    self.parse_comparison()
+  }
+
+  fn parse_comparison(&mut self) -> Result<Condition, PreprocessorError> {
+    let left = self.parse_operand()?;
+
+    // the two characters operators first so `<=` is not read as `<`
+    let operators = [
+      (&["=", "="][..], ComparisonOperator::Equal),
+      (&["!", "="], ComparisonOperator::NotEqual),
+      (&["<", "="], ComparisonOperator::LowerOrEqual),
+      (&[">", "="], ComparisonOperator::GreaterOrEqual),
+      (&["<"], ComparisonOperator::Lower),
+      (&[">"], ComparisonOperator::Greater)
+    ];
+
+    for (symbols, operator) in operators {
+      if self.eat_symbols(symbols) {
+        let right = self.parse_operand()?;
+
+        return Ok(Condition::Comparison(
+          Box::new(left),
+          operator,
+          Box::new(right)
+        ));
+      }
+    }
+
+    Ok(left)
+  }
+
+  fn parse_operand(&mut self) -> Result<Condition, PreprocessorError> {
+    let Some((kind, text, range)) = self.tokens.get(self.position).cloned() else {
+      let offset = self
+        .tokens
+        .last()
+        .map(|(_, _, range)| range.end)
+        .unwrap_or(self.directive_start);
+
+      return Err(PreprocessorError::new(
+        String::from("expected a macro name or a value in the condition"),
+        offset
+      ));
+    };
+
+    self.position += 1;
+
+    match (kind, text) {
+      (TokenKind::Symbol, "(") => {
+        let condition = self.parse_or()?;
+
+        if !self.eat_symbols(&[")"]) {
+          return Err(PreprocessorError::new(
+            String::from("expected `)` to close the `(` of the condition"),
+            range.start
+          ));
+        }
+
+        Ok(condition)
+      }
+      (TokenKind::Symbol, "-") => match self.parse_operand()? {
+        Condition::Literal(number) if number.parse::<f64>().is_ok() => {
+          Ok(Condition::Literal(format!("-{number}")))
+        }
+        _ => Err(PreprocessorError::new(
+          String::from("expected a number after `-` in the condition"),
+          range.start
+        ))
+      },
+      (TokenKind::Literal, _) => Ok(Condition::Literal(text.to_string())),
+      (TokenKind::Identifier, "true" | "false") => Ok(Condition::Literal(text.to_string())),
+      (TokenKind::Identifier, _) if text.starts_with(|c: char| c.is_ascii_digit()) => {
+        let mut number = text.to_string();
+
+        // `4.5` is split in three tokens
+        if self.peek_symbols(&["."]) {
+          if let Some((TokenKind::Identifier, decimals, _)) = self.tokens.get(self.position + 1) {
+            number = format!("{number}.{decimals}");
+            self.position += 2;
+          }
+        }
+
+        Ok(Condition::Literal(number))
+      }
+      (TokenKind::Identifier, _) => Ok(Condition::Macro(text.to_string(), range.start)),
+      _ => Err(PreprocessorError::new(
+        format!("unexpected `{text}` in the condition"),
+        range.start
+      ))
+    }
+  }
+
+  /// Returns whether the next tokens are the given symbols, with nothing in
+  /// between.
+  fn peek_symbols(&self, symbols: &[&str]) -> bool {
+    let Some(tokens) = self
+      .tokens
+      .get(self.position..self.position + symbols.len())
+    else {
+      return false;
+    };
+
+    let glued = tokens
+      .windows(2)
+      .all(|pair| pair[0].2.end == pair[1].2.start);
+
+    glued
+      && tokens
+        .iter()
+        .zip(symbols)
+        .all(|((kind, text, _), symbol)| *kind == TokenKind::Symbol && text == symbol)
+  }
+
+  /// Moves after the given symbols if they are the next tokens
+  fn eat_symbols(&mut self, symbols: &[&str]) -> bool {
+    let found = self.peek_symbols(symbols);
+
+    if found {
+      self.position += symbols.len();
+    }
+
+    found
+  }
+}
+
+impl Condition {
+  /// Evaluates the condition against the registered macros. A macro alone is
+  /// true when it is defined and its value is not `false` nor `0`.
+  pub fn evaluate(&self, scope: MacroScope) -> Result<bool, PreprocessorError> {
+    let result = match self {
+      Condition::Defined(name) => scope.contains(name),
+      Condition::Macro(name, _) => match scope.get(name) {
+        Some(MacroDefinition::Constant(constant)) => is_truthy(&constant.value),
+        Some(MacroDefinition::Function(_)) => true,
+        None => false
+      },
+      Condition::Literal(value) => is_truthy(value),
+      Condition::Not(condition) => !condition.evaluate(scope)?,
+      Condition::And(left, right) => left.evaluate(scope)? && right.evaluate(scope)?,
+      Condition::Or(left, right) => left.evaluate(scope)? || right.evaluate(scope)?,
+      Condition::Comparison(left, operator, right) => {
+        let ordering = compare_values(&left.get_value(scope)?, &right.get_value(scope)?);
+
+        match operator {
+          ComparisonOperator::Equal => ordering == Ordering::Equal,
+          ComparisonOperator::NotEqual => ordering != Ordering::Equal,
+          ComparisonOperator::Lower => ordering == Ordering::Less,
+          ComparisonOperator::LowerOrEqual => ordering != Ordering::Greater,
+          ComparisonOperator::Greater => ordering == Ordering::Greater,
+          ComparisonOperator::GreaterOrEqual => ordering != Ordering::Less
+        }
+      }
+    };
+
+    Ok(result)
+  }
+
+  /// Moves the offsets of the macro names by `offset`
+  pub fn shift(&mut self, offset: usize) {
+    match self {
+      Condition::Macro(_, macro_offset) => *macro_offset += offset,
+      Condition::Defined(_) | Condition::Literal(_) => {}
+      Condition::Not(condition) => condition.shift(offset),
+      Condition::And(left, right)
+      | Condition::Or(left, right)
+      | Condition::Comparison(left, _, right) => {
+        left.shift(offset);
+        right.shift(offset);
+      }
+    }
+  }
+
+  /// Returns the names of the macros the condition reads
+  pub fn get_macro_names(&self) -> Vec<&str> {
+    match self {
+      Condition::Defined(name) | Condition::Macro(name, _) => vec![name.as_str()],
+      Condition::Literal(_) => Vec::new(),
+      Condition::Not(condition) => condition.get_macro_names(),
+      Condition::And(left, right)
+      | Condition::Or(left, right)
+      | Condition::Comparison(left, _, right) => {
+        let mut names = left.get_macro_names();
+        names.extend(right.get_macro_names());
+
+        names
+      }
+    }
+  }
+
+  /// Returns the value of an operand of a comparison
+  fn get_value(&self, scope: MacroScope) -> Result<String, PreprocessorError> {
+    match self {
+      Condition::Macro(name, offset) => match scope.get(name) {
+        Some(MacroDefinition::Constant(constant)) => Ok(constant.value.clone()),
+        Some(MacroDefinition::Function(_)) => Err(PreprocessorError::new(
+          format!("the macro function {name} has no value to compare"),
+          *offset
+        )),
+        None => Err(PreprocessorError::new(
+          format!("the macro {name} is compared but it is not defined"),
+          *offset
+        ))
+      },
+      Condition::Literal(value) => Ok(value.clone()),
+      condition => Ok(condition.evaluate(scope)?.to_string())
+    }
+  }
+}
+
+fn is_truthy(value: &str) -> bool {
+  value != "false" && value != "0"
+}
+
+/// Compares the values as numbers if both of them are numbers, or as text
+/// otherwise. The quotes of the strings are ignored.
+fn compare_values(left: &str, right: &str) -> Ordering {
+  let unquote = |value: &str| {
+    value
+      .trim()
+      .trim_matches(|c| c == '"' || c == '\'')
+      .to_string()
+  };
+  let (left, right) = (unquote(left), unquote(right));
+
+  match (left.parse::<f64>(), right.parse::<f64>()) {
+    (Ok(left), Ok(right)) => left.partial_cmp(&right).unwrap_or(Ordering::Equal),
+    _ => left.cmp(&right)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use super::*;
+  use crate::preprocessor::lexer::tokenize;
+  use crate::preprocessor::macro_registry::{FileOrigin, MacroRegistry};
+
+  fn parse(condition: &str) -> Result<Condition, PreprocessorError> {
+    let tokens: Vec<ConditionToken> = tokenize(condition)
+      .into_iter()
+      .filter(|token| !matches!(token.kind, TokenKind::Whitespace | TokenKind::Comment))
+      .map(|token| (token.kind, &condition[token.range.clone()], token.range))
+      .collect();
+
+    parse_condition(&tokens, 0)
+  }
+
+  fn evaluate(condition: &str) -> Result<bool, PreprocessorError> {
+    let constants = BTreeMap::from([
+      (String::from("DEBUG"), String::from("true")),
+      (String::from("DISABLED"), String::from("false")),
+      (String::from("VERSION"), String::from("4")),
+      (String::from("NAME"), String::from("\"geralt\""))
+    ]);
+    let registry = MacroRegistry::new(&constants);
+    let scope = registry.scope(FileOrigin {
+      name: "test.wss",
+      package: None
+    });
+
+    parse(condition)?.evaluate(scope)
+  }
+
+  #[test]
+  fn evaluates_the_macros_and_the_literals() {
+    assert!(evaluate("DEBUG").unwrap());
+    assert!(!evaluate("DISABLED").unwrap());
+    assert!(!evaluate("MISSING").unwrap());
+    assert!(evaluate("1").unwrap());
+    assert!(!evaluate("0").unwrap());
+    assert!(!evaluate("false").unwrap());
+  }
+
+  #[test]
+  fn follows_the_precedence_of_the_operators() {
+    assert!(evaluate("DEBUG || DISABLED && MISSING").unwrap());
+    assert!(!evaluate("(DEBUG || DISABLED) && MISSING").unwrap());
+    assert!(evaluate("!DISABLED && !MISSING").unwrap());
+    assert!(evaluate("!!DEBUG").unwrap());
+    assert!(evaluate("!(VERSION < 3)").unwrap());
+  }
+
+  #[test]
+  fn compares_numbers_and_strings() {
+    assert!(evaluate("VERSION >= 4").unwrap());
+    assert!(evaluate("VERSION > 3.5").unwrap());
+    assert!(evaluate("VERSION != 10").unwrap());
+    assert!(evaluate("VERSION > -1").unwrap());
+    assert!(!evaluate("VERSION <= 3").unwrap());
+    assert!(evaluate("NAME == \"geralt\"").unwrap());
+    assert!(evaluate("NAME < \"yennefer\"").unwrap());
+  }
+
+  #[test]
+  fn reports_the_malformed_conditions() {
+    let error = parse("(DEBUG").unwrap_err();
+    assert_eq!(
+      error.message,
+      "expected `)` to close the `(` of the condition"
+    );
+    assert_eq!(error.offset, 0);
+
+    let error = parse("DEBUG &&").unwrap_err();
+    assert_eq!(
+      error.message,
+      "expected a macro name or a value in the condition"
+    );
+    assert_eq!(error.offset, 8);
+
+    let error = parse("DEBUG DISABLED").unwrap_err();
+    assert_eq!(error.message, "unexpected `DISABLED` in the condition");
+    assert_eq!(error.offset, 6);
+
+    let error = parse("-DEBUG").unwrap_err();
+    assert_eq!(
+      error.message,
+      "expected a number after `-` in the condition"
+    );
+  }
+
+  #[test]
+  fn reports_the_comparisons_of_undefined_macros() {
+    let error = evaluate("VERSION > 2 && MISSING == 1").unwrap_err();
+
+    assert_eq!(
+      error.message,
+      "the macro MISSING is compared but it is not defined"
+    );
+    assert_eq!(error.offset, 15);
+  }
+}
@@ -1,100 +1,47 @@
-use std::collections::HashMap;
-
-use regex::Captures;
-
+use super::directives::ConditionDirective;
+use super::macro_registry::MacroScope;
+use super::offset_map::OffsetMap;
 use super::types::*;
 
-pub fn filter_conditionals(
-  registered_macros: &HashMap<String, MacroDefinition>, new_content: &mut String,
-  regex_collection: &RegexCollection, condition_type: ConditionType
-) {
-  loop {
-    let content_copy = new_content.clone();
-    let regex = match &condition_type {
-      ConditionType::IfDefined => &regex_collection.macro_ifdef,
-      ConditionType::IfNotDefined => &regex_collection.macro_ifndef
-    };
-    let cap = regex.captures(&content_copy);
+/// Replaces the `#if`, `#ifdef` or `#ifndef` directive with the body of the
+/// first branch whose condition is met, or removes it when none is. The kept
+/// body then starts where the directive started, so its nested directives are
+/// the next ones to handle.
+pub fn filter_conditional(
+  directive: ConditionDirective, scope: MacroScope, new_content: &mut String,
+  offset_map: &mut OffsetMap
+) -> Result<(), PreprocessorError> {
+  let mut kept_body = directive.else_body.clone();
+
+  for branch in &directive.branches {
+    if branch.condition.evaluate(scope)? {
+      kept_body = Some(branch.body.clone());
 
-    if cap.is_none() {
       break;
     }
-
-    let cap = cap.unwrap();
-    let start = new_content.find(&cap[0]).unwrap();
-
-    filter_conditional(
-      registered_macros,
-      new_content,
-      regex_collection,
-      &condition_type,
-      cap,
-      start
-    );
   }
-}
 
-fn filter_conditional(
-  registered_macros: &HashMap<String, MacroDefinition>, new_content: &mut String,
-  regex_collection: &RegexCollection, condition_type: &ConditionType, capture: Captures,
-  start: usize
-) {
-  let full_match = &capture[0];
+  let Some(body) = kept_body else {
+    offset_map.remove_range(new_content, directive.range);
 
-  // if we were able to find another nested ifdef macro inside the current macro,
-  // we will proceed from bottom to top, from the most nested to the global macros
-  // while expanding.
-  //
-  // we go forward in the fullmatch by just 1 to go past the # in #ifdef so it
-  // doesn't match with itself infinitely.
-  let regex = match condition_type {
-    ConditionType::IfDefined => &regex_collection.macro_ifdef,
-    ConditionType::IfNotDefined => &regex_collection.macro_ifndef
+    return Ok(());
   };
 
-  if let Some(sub_capture) = regex.captures(&full_match[1..]) {
-    let sub_start = full_match.find(&sub_capture[0]).unwrap();
+  // the directive around the body is removed in two steps rather than
+  // replacing the whole directive with the body, so the body keeps pointing
+  // to its original location.
+  offset_map.remove_range(new_content, body.end..directive.range.end);
+  offset_map.remove_range(new_content, directive.range.start..body.start);
 
-    filter_conditional(
-      registered_macros,
-      new_content,
-      regex_collection,
-      condition_type,
-      sub_capture,
-      start + sub_start
-    );
-
-    // we let the next iteration of the pre processor parse the current macro,
-    // if it doesn't have more nested macros in it that is.
-    return;
-  }
-
-  let constant_name = &capture[1];
-  let body = &capture[2];
-  let is_defined = registered_macros.contains_key(constant_name);
-
-  new_content.replace_range(
-    start..start + full_match.len(),
-    match condition_type {
-      ConditionType::IfDefined => {
-        if is_defined {
-          body
-        } else {
-          ""
-        }
-      }
-      ConditionType::IfNotDefined => {
-        if is_defined {
-          ""
-        } else {
-          body
-        }
-      }
-    }
-  );
+  Ok(())
 }
 
-pub enum ConditionType {
-  IfDefined,
-  IfNotDefined
+/// Returns whether a condition of the directive reads a macro the scope does
+/// not know, which another file may still define.
+pub fn reads_unknown_macros(directive: &ConditionDirective, scope: MacroScope) -> bool {
+  directive
+    .branches
+    .iter()
+    .flat_map(|branch| branch.condition.get_macro_names())
+    .any(|name| !scope.contains(name) && !scope.is_hidden(name))
 }
@@ -0,0 +1,677 @@
+use std::ops::Range;
+
+use super::condition::{parse_condition, Condition};
+use super::lexer::{tokenize, Token, TokenKind};
+use super::macro_registry::MacroVisibility;
+use super::types::PreprocessorError;
+
+/// A `#define function NAME(parameters) { body };` directive
+pub struct FunctionDirective {
+  pub name: String,
+  pub visibility: MacroVisibility,
+  pub parameters: Vec<String>,
+
+  /// The name of the trailing `...rest` parameter, if any
+  pub variadic: Option<String>,
+
+  pub body: Range<usize>,
+
+  /// The range of the whole directive, from the `#` to the `;`
+  pub range: Range<usize>
+}
+
+/// A `#define const NAME = value;` directive, the value is optional
+pub struct ConstantDirective {
+  pub name: String,
+  pub visibility: MacroVisibility,
+  pub value: Option<String>,
+  pub range: Range<usize>
+}
+
+/// A `#for item in rest { body };` directive of a macro body, that repeats
+/// its body for every value of the variadic parameter
+pub struct ForDirective {
+  pub item: String,
+  pub list: String,
+  pub body: Range<usize>,
+  pub range: Range<usize>
+}
+
+/// A `#include "path";` directive, the `;` is optional
+pub struct IncludeDirective {
+  /// The path as written, without its quotes
+  pub path: String,
+
+  pub range: Range<usize>
+}
+
+/// A `#undef NAME;` directive
+pub struct UndefDirective {
+  pub name: String,
+  pub range: Range<usize>
+}
+
+/// A `#if condition { body }` directive with its optional `#elif condition
+/// { body }` and `#else { body }` branches, ended by a `;`. The `#ifdef NAME`
+/// and `#ifndef NAME` directives are the `#if` directives of a `Defined`
+/// condition.
+pub struct ConditionDirective {
+  /// The first branch and the `#elif` branches, in order
+  pub branches: Vec<ConditionBranch>,
+
+  /// The body of the `#else` branch
+  pub else_body: Option<Range<usize>>,
+
+  pub range: Range<usize>
+}
+
+pub struct ConditionBranch {
+  pub condition: Condition,
+  pub body: Range<usize>
+}
+
+impl ConditionDirective {
+  /// Moves the offsets of the directive, for a directive found in a slice of
+  /// the content that starts at `offset`.
+  pub fn shift(&mut self, offset: usize) {
+    let shift = |range: &mut Range<usize>| *range = range.start + offset..range.end + offset;
+
+    for branch in &mut self.branches {
+      branch.condition.shift(offset);
+      shift(&mut branch.body);
+    }
+
+    if let Some(else_body) = &mut self.else_body {
+      shift(else_body);
+    }
+
+    shift(&mut self.range);
+  }
+}
+
+/// Returns the first `#define function` or `#define local function` directive
+/// of the content
+pub fn find_function_directive(
+  content: &str
+) -> Result<Option<FunctionDirective>, PreprocessorError> {
+  let mut cursor = TokenCursor::new(content);
+
+  let Some(visibility) = cursor.skip_to_definition("function") else {
+    return Ok(None);
+  };
+
+  let start = cursor.directive_start;
+  let (name, _) = cursor.expect_identifier("the name of the macro function")?;
+
+  cursor.expect_symbol("(", &format!("`(` after the name of the macro {name}"))?;
+
+  let mut parameters = Vec::new();
+  let mut variadic = None;
+
+  loop {
+    match cursor.next_significant() {
+      Some((TokenKind::Symbol, ")", _)) => break,
+      Some((TokenKind::Symbol, ",", _)) if !parameters.is_empty() => {}
+      Some((TokenKind::Identifier, parameter, _)) => parameters.push(parameter.to_string()),
+      // `...rest`, the variadic parameter must be the last one
+      Some((TokenKind::Symbol, ".", _)) => {
+        cursor.expect_symbol(".", "`...` before the name of the variadic parameter")?;
+        cursor.expect_symbol(".", "`...` before the name of the variadic parameter")?;
+
+        let (rest, _) = cursor.expect_identifier("the name of the variadic parameter")?;
+        variadic = Some(rest);
+
+        cursor.expect_symbol(
+          ")",
+          "`)` after the variadic parameter, it must be the last one"
+        )?;
+
+        break;
+      }
+      Some((_, text, range)) => {
+        return Err(PreprocessorError::new(
+          format!("unexpected `{text}` in the parameters of the macro {name}"),
+          range.start
+        ))
+      }
+      None => {
+        return Err(PreprocessorError::new(
+          format!("unterminated parameter list in the definition of the macro {name}"),
+          start
+        ))
+      }
+    };
+  }
+
+  let (body, end) = cursor.expect_body(&format!("the macro {name}"))?;
+
+  Ok(Some(FunctionDirective {
+    name,
+    visibility,
+    parameters,
+    variadic,
+    body,
+    range: start..end
+  }))
+}
+
+/// Returns the first `#define const` or `#define local const` directive of
+/// the content
+pub fn find_constant_directive(
+  content: &str
+) -> Result<Option<ConstantDirective>, PreprocessorError> {
+  let mut cursor = TokenCursor::new(content);
+
+  let Some(visibility) = cursor.skip_to_definition("const") else {
+    return Ok(None);
+  };
+
+  let start = cursor.directive_start;
+  let (name, name_range) = cursor.expect_identifier("the name of the macro constant")?;
+
+  let value = match cursor.next_significant() {
+    Some((TokenKind::Symbol, ";", _)) => None,
+    Some((TokenKind::Symbol, "=", range)) => {
+      let value_start = range.end;
+      let semicolon = cursor.find_symbol(";").ok_or_else(|| {
+        PreprocessorError::new(
+          format!("missing `;` at the end of the macro constant {name}"),
+          start
+        )
+      })?;
+
+      Some(content[value_start..semicolon.start].trim().to_string())
+    }
+    _ => {
+      return Err(PreprocessorError::new(
+        format!("expected `=` or `;` after the macro constant {name}"),
+        name_range.end
+      ))
+    }
+  };
+
+  Ok(Some(ConstantDirective {
+    name,
+    visibility,
+    value,
+    range: start..cursor.offset()
+  }))
+}
+
+/// Returns the first `#undef` directive of the content
+pub fn find_undef_directive(content: &str) -> Result<Option<UndefDirective>, PreprocessorError> {
+  let mut cursor = TokenCursor::new(content);
+
+  if !cursor.skip_to_directive(&["undef"]) {
+    return Ok(None);
+  }
+
+  let start = cursor.directive_start;
+  let (name, _) = cursor.expect_identifier("the name of the macro to undefine")?;
+  let semicolon = cursor.expect_symbol(";", &format!("`;` after `#undef {name}`"))?;
+
+  Ok(Some(UndefDirective {
+    name,
+    range: start..semicolon.end
+  }))
+}
+
+/// Returns the first `#include` directive of the content
+pub fn find_include_directive(
+  content: &str
+) -> Result<Option<IncludeDirective>, PreprocessorError> {
+  let mut cursor = TokenCursor::new(content);
+
+  if !cursor.skip_to_directive(&["include"]) {
+    return Ok(None);
+  }
+
+  let start = cursor.directive_start;
+  let path = match cursor.next_significant() {
+    Some((TokenKind::Literal, text, _)) if text.len() > 1 && text.starts_with('"') => {
+      text[1..text.len() - 1].to_string()
+    }
+    Some((_, text, range)) => {
+      return Err(PreprocessorError::new(
+        format!("expected the path of the file to include between double quotes, found `{text}`"),
+        range.start
+      ))
+    }
+    None => return Err(PreprocessorError::new(
+      String::from(
+        "expected the path of the file to include between double quotes, found the end of the file"
+      ),
+      start
+    ))
+  };
+
+  let mut end = cursor.offset();
+
+  if let Some((TokenKind::Symbol, ";", range)) = cursor.next_significant() {
+    end = range.end;
+  }
+
+  Ok(Some(IncludeDirective {
+    path,
+    range: start..end
+  }))
+}
+
+/// Returns the first `#for` directive of the content
+pub fn find_for_directive(content: &str) -> Result<Option<ForDirective>, PreprocessorError> {
+  let mut cursor = TokenCursor::new(content);
+
+  if !cursor.skip_to_directive(&["for"]) {
+    return Ok(None);
+  }
+
+  let start = cursor.directive_start;
+  let (item, _) = cursor.expect_identifier("the name of the item of the `#for` loop")?;
+
+  match cursor.next_significant() {
+    Some((TokenKind::Identifier, "in", _)) => {}
+    Some((_, text, range)) => {
+      return Err(PreprocessorError::new(
+        format!("expected `in` after `#for {item}`, found `{text}`"),
+        range.start
+      ))
+    }
+    None => {
+      return Err(PreprocessorError::new(
+        format!("expected `in` after `#for {item}`, found the end of the file"),
+        start
+      ))
+    }
+  };
+
+  let (list, _) = cursor.expect_identifier("the variadic parameter the `#for` loop iterates")?;
+  let (body, end) = cursor.expect_body("the `#for` loop")?;
+
+  Ok(Some(ForDirective {
+    item,
+    list,
+    body,
+    range: start..end
+  }))
+}
+
+/// Returns the first `#if`, `#ifdef` or `#ifndef` directive of the content,
+/// the directives nested in its bodies are left for the next calls.
+pub fn find_condition_directive(
+  content: &str
+) -> Result<Option<ConditionDirective>, PreprocessorError> {
+  let tokens = tokenize(content);
+  let first_directive = ["if", "ifdef", "ifndef"]
+    .into_iter()
+    .filter_map(|keyword| {
+      find_directive_index(content, &tokens, &[keyword]).map(|index| (index, keyword))
+    })
+    .min();
+
+  let Some((index, keyword)) = first_directive else {
+    return Ok(None);
+  };
+
+  let mut cursor = TokenCursor::with_tokens(content, tokens);
+  cursor.move_after_directive(index, 1);
+
+  let start = cursor.directive_start;
+  let condition = match keyword {
+    "if" => cursor.read_condition("#if")?,
+    _ => {
+      let (name, _) = cursor.expect_identifier("the name of the macro in the condition")?;
+      let defined = Condition::Defined(name);
+
+      match keyword {
+        "ifndef" => Condition::Not(Box::new(defined)),
+        _ => defined
+      }
+    }
+  };
+
+  let body = cursor.expect_block(&format!("the `#{keyword}` condition"))?;
+  let mut branches = vec![ConditionBranch { condition, body }];
+  let mut else_body = None;
+
+  loop {
+    match cursor.next_branch_keyword() {
+      Some("elif") => {
+        let condition = cursor.read_condition("#elif")?;
+        let body = cursor.expect_block("the `#elif` condition")?;
+
+        branches.push(ConditionBranch { condition, body });
+      }
+      Some(_) => {
+        else_body = Some(cursor.expect_block("the `#else` branch")?);
+
+        break;
+      }
+      None => break
+    };
+  }
+
+  let end = cursor
+    .expect_symbol(";", &format!("`;` after the `#{keyword}` directive"))?
+    .end;
+
+  Ok(Some(ConditionDirective {
+    branches,
+    else_body,
+    range: start..end
+  }))
+}
+
+/// Walks over the tokens of a content, ignoring the whitespaces and the
+/// comments.
+struct TokenCursor<'a> {
+  content: &'a str,
+  tokens: Vec<Token>,
+  position: usize,
+
+  /// The offset of the `#` of the directive the cursor is in
+  directive_start: usize
+}
+
+impl<'a> TokenCursor<'a> {
+  fn new(content: &'a str) -> Self {
+    Self::with_tokens(content, tokenize(content))
+  }
+
+  fn with_tokens(content: &'a str, tokens: Vec<Token>) -> Self {
+    Self {
+      content,
+      tokens,
+      position: 0,
+      directive_start: 0
+    }
+  }
+
+  /// Returns the offset right after the last token that was read
+  fn offset(&self) -> usize {
+    self
+      .position
+      .checked_sub(1)
+      .and_then(|index| self.tokens.get(index))
+      .map(|token| token.range.end)
+      .unwrap_or(0)
+  }
+
+  /// Moves the cursor after the first directive with the given keywords,
+  /// returns whether one was found.
+  fn skip_to_directive(&mut self, keywords: &[&str]) -> bool {
+    match find_directive_index(self.content, &self.tokens, keywords) {
+      Some(index) => {
+        self.move_after_directive(index, keywords.len());
+
+        true
+      }
+      None => false
+    }
+  }
+
+  /// Moves the cursor after the first `#define KIND` or `#define local KIND`
+  /// directive, returns the visibility of the macro it defines if one was
+  /// found.
+  fn skip_to_definition(&mut self, kind: &str) -> Option<MacroVisibility> {
+    let exported = find_directive_index(self.content, &self.tokens, &["define", kind]);
+    let local = find_directive_index(self.content, &self.tokens, &["define", "local", kind]);
+
+    let (index, visibility, keyword_count) = match (exported, local) {
+      (Some(exported), Some(local)) if local < exported => (local, MacroVisibility::File, 3),
+      (Some(exported), _) => (exported, MacroVisibility::Exported, 2),
+      (None, Some(local)) => (local, MacroVisibility::File, 3),
+      (None, None) => return None
+    };
+
+    self.move_after_directive(index, keyword_count);
+
+    Some(visibility)
+  }
+
+  /// Moves the cursor after the `#` at `index` and the given number of
+  /// keywords that follow it.
+  fn move_after_directive(&mut self, index: usize, keyword_count: usize) {
+    self.directive_start = self.tokens[index].range.start;
+    self.position = index + 1;
+
+    for _ in 0..keyword_count {
+      self.next_significant();
+    }
+  }
+
+  fn next_significant(&mut self) -> Option<(TokenKind, &'a str, Range<usize>)> {
+    while let Some(token) = self.tokens.get(self.position) {
+      self.position += 1;
+
+      if !matches!(token.kind, TokenKind::Whitespace | TokenKind::Comment) {
+        let range = token.range.clone();
+
+        return Some((token.kind, &self.content[range.clone()], range));
+      }
+    }
+
+    None
+  }
+
+  fn expect_identifier(
+    &mut self, expected: &str
+  ) -> Result<(String, Range<usize>), PreprocessorError> {
+    match self.next_significant() {
+      Some((TokenKind::Identifier, name, range)) => Ok((name.to_string(), range)),
+      Some((_, text, range)) => Err(PreprocessorError::new(
+        format!("expected {expected}, found `{text}`"),
+        range.start
+      )),
+      None => Err(PreprocessorError::new(
+        format!("expected {expected}, found the end of the file"),
+        self.directive_start
+      ))
+    }
+  }
+
+  fn expect_symbol(
+    &mut self, symbol: &str, expected: &str
+  ) -> Result<Range<usize>, PreprocessorError> {
+    match self.next_significant() {
+      Some((TokenKind::Symbol, text, range)) if text == symbol => Ok(range),
+      Some((_, text, range)) => Err(PreprocessorError::new(
+        format!("expected {expected}, found `{text}`"),
+        range.start
+      )),
+      None => Err(PreprocessorError::new(
+        format!("expected {expected}, found the end of the file"),
+        self.directive_start
+      ))
+    }
+  }
+
+  /// Moves the cursor after the next occurence of the symbol and returns its
+  /// range.
+  fn find_symbol(&mut self, symbol: &str) -> Option<Range<usize>> {
+    loop {
+      match self.next_significant() {
+        Some((TokenKind::Symbol, text, range)) if text == symbol => return Some(range),
+        Some(_) => continue,
+        None => return None
+      }
+    }
+  }
+
+  /// Reads a `{ body };` block where the braces of the body must be balanced,
+  /// returns the range of the body and the offset after the `;`.
+  fn expect_body(&mut self, owner: &str) -> Result<(Range<usize>, usize), PreprocessorError> {
+    let body = self.expect_block(owner)?;
+    let semicolon = self.expect_symbol(";", &format!("`;` after the body of {owner}"))?;
+
+    Ok((body, semicolon.end))
+  }
+
+  /// Reads a `{ body }` block where the braces of the body must be balanced,
+  /// returns the range of the body.
+  fn expect_block(&mut self, owner: &str) -> Result<Range<usize>, PreprocessorError> {
+    let opening_brace = self.expect_symbol("{", &format!("`{{` to open the body of {owner}"))?;
+    let mut depth = 1;
+
+    let closing_brace = loop {
+      match self.next_significant() {
+        Some((TokenKind::Symbol, "{", _)) => depth += 1,
+        Some((TokenKind::Symbol, "}", range)) => {
+          depth -= 1;
+
+          if depth == 0 {
+            break range;
+          }
+        }
+        Some(_) => {}
+        None => {
+          return Err(PreprocessorError::new(
+            format!("the body of {owner} is never closed, a `}}` is missing"),
+            opening_brace.start
+          ))
+        }
+      };
+    };
+
+    Ok(opening_brace.end..closing_brace.start)
+  }
+
+  /// Reads the tokens of a condition up to the `{` of its body
+  fn read_condition(&mut self, directive: &str) -> Result<Condition, PreprocessorError> {
+    let mut tokens = Vec::new();
+
+    loop {
+      let position = self.position;
+
+      match self.next_significant() {
+        Some((TokenKind::Symbol, "{", _)) => {
+          self.position = position;
+
+          break;
+        }
+        Some(token) => tokens.push(token),
+        None => {
+          return Err(PreprocessorError::new(
+            format!(
+              "expected `{{` after the condition of `{directive}`, found the end of the file"
+            ),
+            self.directive_start
+          ))
+        }
+      };
+    }
+
+    if tokens.is_empty() {
+      return Err(PreprocessorError::new(
+        format!("expected a condition after `{directive}`"),
+        self.offset()
+      ));
+    }
+
+    parse_condition(&tokens, self.directive_start)
+  }
+
+  /// Moves after the `#elif` or the `#else` that follows a body and returns
+  /// its keyword, or stays in place if the directive has no other branch.
+  fn next_branch_keyword(&mut self) -> Option<&'a str> {
+    let position = self.position;
+
+    if let Some((TokenKind::Symbol, "#", _)) = self.next_significant() {
+      if let Some((TokenKind::Identifier, keyword @ ("elif" | "else"), _)) = self.next_significant()
+      {
+        return Some(keyword);
+      }
+    }
+
+    self.position = position;
+
+    None
+  }
+}
+
+/// Returns the index of the `#` token of the first directive with the given
+/// keywords, like `#define function`.
+fn find_directive_index(content: &str, tokens: &[Token], keywords: &[&str]) -> Option<usize> {
+  let text = |token: &Token| &content[token.range.clone()];
+
+  (0..tokens.len()).find(|&index| {
+    if tokens[index].kind != TokenKind::Symbol || text(&tokens[index]) != "#" {
+      return false;
+    }
+
+    // the first keyword is glued to the `#` while the next ones are separated
+    // by whitespaces.
+    let mut position = index + 1;
+    for (i, keyword) in keywords.iter().enumerate() {
+      if i > 0 {
+        match tokens.get(position) {
+          Some(token) if token.kind == TokenKind::Whitespace => position += 1,
+          _ => return false
+        };
+      }
+
+      match tokens.get(position) {
+        Some(token) if token.kind == TokenKind::Identifier && text(token) == *keyword => {
+          position += 1
+        }
+        _ => return false
+      };
+    }
+
+    true
+  })
+}
+
+/// Returns the range of the body of the `{{ body }}` code block the content
+/// starts with, and the offset right after its `}}`. The braces are balanced so
+/// the code block can hold blocks and other code blocks.
+pub fn find_code_block(content: &str) -> Option<(Range<usize>, usize)> {
+  let mut depth = 0;
+  let mut body_end = 0;
+
+  for token in tokenize(content) {
+    if token.kind != TokenKind::Symbol {
+      continue;
+    }
+
+    match &content[token.range.clone()] {
+      "{" => depth += 1,
+      "}" => {
+        depth -= 1;
+
+        // the first brace of the closing `}}`
+        if depth == 1 {
+          body_end = token.range.start;
+        }
+
+        if depth == 0 {
+          return Some((2..body_end, token.range.end));
+        }
+      }
+      _ => {}
+    };
+  }
+
+  None
+}
+
+/// Returns the offset of the `,` or the `)` that ends the macro parameter the
+/// content starts with. The commas and the parenthesis nested in brackets are
+/// part of the parameter.
+pub fn find_parameter_end(content: &str) -> Option<usize> {
+  let mut depth = 0;
+
+  for token in tokenize(content) {
+    if token.kind != TokenKind::Symbol {
+      continue;
+    }
+
+    match &content[token.range.clone()] {
+      "(" | "[" | "{" => depth += 1,
+      ")" | "," if depth == 0 => return Some(token.range.start),
+      ")" | "]" | "}" => depth -= 1,
+      _ => {}
+    };
+  }
+
+  None
+}
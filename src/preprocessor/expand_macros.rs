@@ -1,110 +1,279 @@
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::ops::Range;
 
-use crate::ast::span_manager::SpanManager;
-use crate::ast::ProgramInformation;
+use ariadne::ReportKind;
+
+use crate::ast::{Diagnostic, DiagnosticLabel};
 use crate::preprocessor::MacroConstant;
 
+use super::builtins::{expand_builtin, BuiltinCall, BuiltinValues, BUILTIN_MACROS};
+use super::conditionals::{filter_conditional, reads_unknown_macros};
+use super::directives::{
+  find_code_block, find_condition_directive, find_constant_directive, find_function_directive,
+  find_parameter_end, find_undef_directive, ConditionDirective, ConstantDirective,
+  FunctionDirective, UndefDirective
+};
+use super::lexer::{find_macro_call_ranges, find_macro_calls, MacroCall};
+use super::macro_registry::{FileOrigin, MacroRegistry, MacroScope};
+use super::macro_substitution::{check_for_directives, substitute_parameters};
+use super::offset_map::OffsetMap;
 use super::pragma_replace::get_pragma_replace_directives;
 use super::types::*;
 
-pub fn expand_macros(
-  registered_macros: &mut HashMap<String, MacroDefinition>, new_content: &mut String,
-  regex_collection: &RegexCollection
-) -> bool {
-  loop {
-    let cap = regex_collection.macro_function.captures(&new_content);
+/// The settings of the expansion, the same for every file
+pub struct ExpansionOptions {
+  /// The length of the longest chain of macros that can expand into each other
+  pub max_depth: usize,
 
-    if cap.is_none() {
-      break;
-    }
+  pub builtins: BuiltinValues
+}
 
-    let cap = cap.unwrap();
-    let macro_name = &cap[1];
-    let macro_parameters = &cap[2].trim();
-    let macro_body = &cap[3].trim();
+/// Registers the macros the content of the file defines and expands the macro
+/// calls it contains, returns whether the content changed. The conflicting
+/// definitions are reported in the `diagnostics`.
+///
+/// The directives and the calls are handled in the order they are written, so
+/// an `#undef` only hides the macro from the code that follows it. The
+/// `#undef` directives, and the definitions that make a macro visible again
+/// after them, are left in the content for the final pass.
+///
+/// The conditions are evaluated as they are met, so the directives of the
+/// branches that are not kept are never registered. A condition that reads a
+/// macro no file defined yet stops the expansion of the file until the caller
+/// sets `settle_conditions`, once the other files defined everything they
+/// could. The condition is then evaluated with the macro undefined, and the
+/// flag is reset so the next conditions wait for what the kept body defines.
+pub fn expand_macros(
+  registry: &mut MacroRegistry, origin: FileOrigin, file: &ProcessedFile, new_content: &mut String,
+  options: &ExpansionOptions, settle_conditions: &mut bool, diagnostics: &mut Vec<Diagnostic>
+) -> Result<bool, PreprocessorError> {
+  let offset_map = &mut *file.offset_map.borrow_mut();
+  let mut changed = false;
+  let mut position = 0;
+
+  // the macros the `#undef` directives met so far hide from the rest of the
+  // file
+  let mut hidden = HashSet::new();
+
+  while let Some(step) = find_next_step(new_content, position)? {
+    let (name, definition, visibility, range) = match step {
+      Step::Function(directive) => {
+        let raw_body = &new_content[directive.body.clone()];
+        let body = raw_body.trim().to_string();
+        let body_start = directive.body.start + raw_body.len() - raw_body.trim_start().len();
+
+        check_for_directives(&body, directive.variadic.as_deref(), &directive.name)
+          .map_err(|error| PreprocessorError::new(error.message, body_start + error.offset))?;
+
+        // a call to a parameter is a call to the macro the caller passes
+        let calls = find_macro_calls(&body)
+          .into_iter()
+          .filter(|call| {
+            !directive.parameters.contains(call) && directive.variadic.as_ref() != Some(call)
+          })
+          .collect();
+
+        let definition = MacroDefinition::Function(MacroFunction {
+          parameters: directive.parameters,
+          variadic: directive.variadic,
+          body,
+          expansions: Cell::new(0),
+          calls
+        });
+
+        (
+          directive.name,
+          definition,
+          directive.visibility,
+          directive.range
+        )
+      }
+      Step::Constant(directive) => {
+        let value = directive.value.unwrap_or_else(|| String::from("true"));
+
+        let definition = MacroDefinition::Constant(MacroConstant {
+          calls: find_macro_calls(&value),
+          name: directive.name.clone(),
+          value
+        });
+
+        (
+          directive.name,
+          definition,
+          directive.visibility,
+          directive.range
+        )
+      }
+      Step::Undef(directive) => {
+        hidden.insert(directive.name);
+        position = directive.range.end;
+
+        continue;
+      }
+      Step::Condition(directive) => {
+        let scope = registry.scope(origin).hiding(&hidden);
 
-    let f = parse_macro_function(macro_parameters, macro_body);
-    let body_length = f.body.len();
-    let macro_end = new_content.find(&f.body).unwrap();
+        if reads_unknown_macros(&directive, scope) {
+          if !*settle_conditions {
+            break;
+          }
 
-    registered_macros.insert(macro_name.to_string(), MacroDefinition::Function(f));
+          *settle_conditions = false;
+        }
 
-    let macro_start_pattern = format!("#define function {macro_name}");
-    let macro_start = new_content.find(&macro_start_pattern).unwrap();
-    let closing_bracket_index = &new_content[macro_end + body_length..].find("};").unwrap();
+        position = directive.range.start;
+        filter_conditional(directive, scope, new_content, offset_map)?;
+        changed = true;
 
-    new_content.replace_range(
-      macro_start..macro_end + body_length + closing_bracket_index + 2,
-      ""
-    );
-  }
+        continue;
+      }
+      Step::Call(call) => {
+        let scope = registry.scope(origin).hiding(&hidden);
+        let is_builtin = BUILTIN_MACROS.contains(&call.name.as_str());
 
-  loop {
-    let cap = regex_collection.macro_const.captures(&new_content);
+        // the unknown macros are reported once every file is expanded, as
+        // another file may still define them.
+        if scope.is_hidden(&call.name) || !(is_builtin || scope.contains(&call.name)) {
+          position = call.range.end;
 
-    if cap.is_none() {
-      break;
-    }
+          continue;
+        }
 
-    let cap = cap.unwrap();
-    let macro_name = &cap[1];
+        position = if is_builtin {
+          expand_builtin_call(&call, origin, file, new_content, offset_map, options)?
+        } else {
+          expand_macro_call(new_content, offset_map, &call, scope, options.max_depth)?
+        };
+        changed = true;
 
-    let macro_value = match regex_collection.macro_const_value.captures(&cap[0]) {
-      Some(value_capture) => value_capture[1].to_string(),
-      None => String::from("true")
+        continue;
+      }
     };
 
-    println!("registering macro const: {macro_name} = {macro_value}");
+    let conflict = registry.define(name.clone(), definition, visibility, origin);
 
-    registered_macros.insert(
-      macro_name.to_string(),
-      MacroDefinition::Constant(MacroConstant {
-        name: macro_name.to_string(),
-        value: macro_value.to_string()
-      })
-    );
+    // a different value after an `#undef` is what the `#undef` is for
+    if let Some(previous_file) = conflict.filter(|_| !hidden.contains(&name)) {
+      diagnostics.push(get_redefinition_warning(
+        &name,
+        &previous_file,
+        offset_map,
+        range.clone()
+      ));
+    }
 
-    let macro_start = new_content
-      .find(&format!("#define const {macro_name}"))
-      .unwrap();
-    let macro_end = macro_start + &cap[0].len();
+    // a definition that follows an `#undef` of the macro makes it visible
+    // again, the final pass needs it to know where.
+    if hidden.remove(&name) {
+      position = range.end;
+    } else {
+      position = range.start;
+      offset_map.remove_range(new_content, range);
+      changed = true;
+    }
+  }
+
+  Ok(changed)
+}
+
+/// A directive or a macro call the expansion handles
+enum Step {
+  Function(FunctionDirective),
+  Constant(ConstantDirective),
+  Undef(UndefDirective),
+  Condition(ConditionDirective),
+  Call(MacroCall)
+}
 
-    new_content.replace_range(macro_start..macro_end, "");
+impl Step {
+  fn start(&self) -> usize {
+    match self {
+      Step::Function(directive) => directive.range.start,
+      Step::Constant(directive) => directive.range.start,
+      Step::Undef(directive) => directive.range.start,
+      Step::Condition(directive) => directive.range.start,
+      Step::Call(call) => call.range.start
+    }
   }
+}
 
-  let content_copy = new_content.clone();
-  for cap in regex_collection.macro_call.captures_iter(&content_copy) {
-    let macro_name = &cap[1];
+/// Returns the first directive or macro call of the content that starts after
+/// the position.
+fn find_next_step(content: &str, position: usize) -> Result<Option<Step>, PreprocessorError> {
+  let rest = &content[position..];
+  let shift = |range: Range<usize>| range.start + position..range.end + position;
+  let shift_error =
+    |error: PreprocessorError| PreprocessorError::new(error.message, error.offset + position);
 
-    // println!("found macro call: {macro_name}");
+  let mut steps = Vec::new();
 
-    if !registered_macros.contains_key(macro_name) {
-      println!("Warning, macro call to unkown macro: {}", &macro_name);
+  if let Some(mut directive) = find_function_directive(rest).map_err(shift_error)? {
+    directive.body = shift(directive.body);
+    directive.range = shift(directive.range);
+    steps.push(Step::Function(directive));
+  }
 
-      continue;
-    }
+  if let Some(mut directive) = find_constant_directive(rest).map_err(shift_error)? {
+    directive.range = shift(directive.range);
+    steps.push(Step::Constant(directive));
+  }
 
-    expand_macro_call(new_content, macro_name, &registered_macros);
+  if let Some(mut directive) = find_undef_directive(rest).map_err(shift_error)? {
+    directive.range = shift(directive.range);
+    steps.push(Step::Undef(directive));
   }
 
-  let contains_macro_call = regex_collection.macro_call.find(&new_content).is_some();
+  if let Some(mut directive) = find_condition_directive(rest).map_err(shift_error)? {
+    directive.shift(position);
+    steps.push(Step::Condition(directive));
+  }
+
+  if let Some(mut call) = find_macro_call_ranges(rest).into_iter().next() {
+    call.range = shift(call.range);
+    steps.push(Step::Call(call));
+  }
 
-  contains_macro_call
+  Ok(steps.into_iter().min_by_key(Step::start))
 }
 
-fn expand_macro_call(
-  content: &mut String, macro_name: &str, registered_macros: &HashMap<String, MacroDefinition>
-) {
-  let macro_call_index = content.find(&format!("{macro_name}!"));
+/// Expands the call to a builtin macro, returns the offset the expansion
+/// starts at.
+fn expand_builtin_call(
+  call: &MacroCall, origin: FileOrigin, file: &ProcessedFile, content: &mut String,
+  offset_map: &mut OffsetMap, options: &ExpansionOptions
+) -> Result<usize, PreprocessorError> {
+  let builtin_call = BuiltinCall {
+    name: &call.name,
+    origin,
+    file,
+    offset: call.range.start
+  };
+  let value = expand_builtin(&builtin_call, &options.builtins, content, offset_map)?;
 
-  if macro_call_index.is_none() {
-    println!("could not find macro call {macro_name}!");
+  offset_map.replace_range(content, call.range.clone(), &value, Some(&call.name));
 
-    return;
-  }
+  Ok(call.range.start)
+}
+
+/// Expands the call to a macro of the scope, returns the offset the expansion
+/// starts at.
+fn expand_macro_call(
+  content: &mut String, offset_map: &mut OffsetMap, call: &MacroCall, scope: MacroScope,
+  max_depth: usize
+) -> Result<usize, PreprocessorError> {
+  let macro_name = call.name.as_str();
+  let macro_call_index = call.range.start;
+
+  let Some(definition) = scope.get(macro_name) else {
+    return Err(PreprocessorError::new(
+      format!("macro call to unknown macro {macro_name}"),
+      macro_call_index
+    ));
+  };
 
-  let macro_call_index = macro_call_index.unwrap();
-  let definition = registered_macros.get(macro_name).unwrap();
+  check_recursion(scope, macro_name, macro_call_index)?;
+  check_expansion_depth(offset_map, macro_call_index, macro_name, max_depth)?;
 
   match definition {
     MacroDefinition::Function(function) => {
@@ -117,81 +286,53 @@ fn expand_macro_call(
           slice = &slice[1..];
         }
 
-        while slice.starts_with(" ") {
-          slice = &slice[1..];
-        }
+        slice = slice.trim_start();
+
+        // the variadic parameter takes every value up to the `)`
+        let has_all_parameters =
+          function.variadic.is_none() && parameters.len() == function.parameters.len();
 
-        if parameters.len() == function.parameters.len() || slice.starts_with(")") {
+        if has_all_parameters || slice.starts_with(")") {
           break;
         }
 
-        if slice.trim().starts_with("{{") {
+        if slice.starts_with("{{") {
           // it's a body of code,
+          let Some((body, body_end)) = find_code_block(slice) else {
+            return Err(PreprocessorError::new(
+              format!("unterminated body of code in macro call {macro_name}, a `}}}}` is missing"),
+              macro_call_index
+            ));
+          };
 
-          let body_end_index = slice.find("}}");
-
-          if body_end_index.is_none() {
-            panic!("Unterminated body of code in macro call {}", macro_name);
-          }
-
-          let body_end_index = body_end_index.unwrap();
-
-          parameters.push(&slice[2..body_end_index]);
+          parameters.push(&slice[body]);
 
-          slice = &slice[2 + body_end_index + 2..];
+          slice = &slice[body_end..];
         } else {
-          let program_information = ProgramInformation::new();
-          let mut span_manager = SpanManager::new();
-          let mut span_maker = span_manager.add_fake_source();
-
-          let parsing_result = crate::parser::ExpressionParser::new().parse(
-            &program_information,
-            &mut span_maker,
-            &slice
-          );
-
-          let parameter_slice = match parsing_result {
-            Ok(_) => unreachable!(),
-            // A bit of a weird case here, we expect to have an unrecognized token
-            // which will help us know when the expression end. So we get the
-            // index out of the error to build the slice.
-            Err(err) => match err {
-              lalrpop_util::ParseError::UnrecognizedToken { token, expected: _ } => {
-                let token_position = token.0;
-
-                &slice[..token_position]
-              }
-              lalrpop_util::ParseError::InvalidToken { location: _ } => unreachable!(),
-              lalrpop_util::ParseError::UnrecognizedEOF {
-                location: _,
-                expected: _
-              } => unreachable!(),
-              lalrpop_util::ParseError::ExtraToken { token: _ } => unreachable!(),
-              lalrpop_util::ParseError::User { error: _ } => unreachable!()
-            }
+          let Some(parameter_end) = find_parameter_end(slice) else {
+            return Err(PreprocessorError::new(
+              format!("unterminated parameter list in macro call {macro_name}"),
+              macro_call_index
+            ));
           };
 
-          parameters.push(&parameter_slice);
-          slice = &slice[parameter_slice.len()..];
-
-          // this is the old, buggy, way of doing it:
-          // let end = slice.len();
-          // let comma_index = slice.find(",").unwrap_or(end);
-          // let paren_index = slice.find(")").unwrap_or(end);
-
-          // parameters.push(&slice[..comma_index.min(paren_index)]);
-          // slice = &slice[comma_index.min(paren_index)..];
+          parameters.push(&slice[..parameter_end]);
+          slice = &slice[parameter_end..];
         }
       }
 
-      let mut body = function.body.clone();
-      for i in 0..function.parameters.len() {
-        let parameter = &function.parameters[i];
-        let value = &parameters[i];
-
-        body = body.replace(parameter, value.trim());
+      if !slice.starts_with(")") {
+        return Err(PreprocessorError::new(
+          format!(
+            "expected `)` after the {} parameters of the macro call {macro_name}",
+            function.parameters.len()
+          ),
+          macro_call_index
+        ));
       }
 
+      let mut body = substitute_parameters(function, macro_name, &parameters);
+
       let findreplace_directives = get_pragma_replace_directives(&body);
 
       for directive in findreplace_directives {
@@ -216,83 +357,152 @@ fn expand_macro_call(
         macro_call_index
       };
 
-      content.replace_range(start..end + 1, &body);
+      offset_map.replace_range(content, start..end + 1, &body, Some(macro_name));
+
+      Ok(start)
     }
     MacroDefinition::Constant(constant) => {
-      *content = content.replacen(&format!("{}!", constant.name), &constant.value, 1);
+      let call_length = constant.name.len() + 1;
+
+      offset_map.replace_range(
+        content,
+        macro_call_index..macro_call_index + call_length,
+        &constant.value,
+        Some(macro_name)
+      );
+
+      Ok(macro_call_index)
     }
   }
 }
 
-fn parse_macro_function(macro_parameters: &str, macro_body: &str) -> MacroFunction {
-  let parameters = if !macro_parameters.is_empty() {
-    let mut slice = &macro_parameters[..];
-    let mut output = Vec::new();
-
-    loop {
-      if slice.starts_with(",") {
-        slice = &slice[1..];
-      }
-
-      if slice.starts_with(")") {
-        break;
-      }
+/// Makes sure the macro doesn't expand into a call to itself, directly or
+/// through other macros, as the expansion would never end.
+fn check_recursion(
+  scope: MacroScope, macro_name: &str, macro_call_index: usize
+) -> Result<(), PreprocessorError> {
+  let mut path = vec![macro_name.to_string()];
+  let mut visited = HashSet::new();
+
+  if find_cycle(scope, macro_name, &mut path, &mut visited) {
+    return Err(PreprocessorError::new(
+      format!("recursive macro expansion: {}", format_macro_chain(&path)),
+      macro_call_index
+    ));
+  }
 
-      let end = slice.len();
-      let comma_index = slice.find(",");
-      let paren_index = slice.find(")");
-      let parameter = &slice[..comma_index.unwrap_or(end).min(paren_index.unwrap_or(end))];
+  Ok(())
+}
 
-      output.push(parameter.trim().to_string());
+/// Walks the calls of the last macro of the path, depth first, until it finds
+/// a call back to the `target`. The path then holds the cycle.
+fn find_cycle(
+  scope: MacroScope, target: &str, path: &mut Vec<String>, visited: &mut HashSet<String>
+) -> bool {
+  let current = path.last().unwrap().clone();
+  let Some(definition) = scope.get(&current) else {
+    return false;
+  };
 
-      slice = &slice[parameter.len()..];
+  for call in definition.calls() {
+    path.push(call.clone());
 
-      if comma_index.is_none() {
-        break;
-      }
+    if call == target {
+      return true;
     }
 
-    output
-  } else {
-    Vec::new()
-  };
-
-  // before starting to parse, we must find the actual end of the body since the
-  // regex is greedy. It is done on purpose to allow recursive macros and macros
-  // that expand into #define calls.
-  let mut slice = &macro_body[..];
-  loop {
-    let define_index = slice.find("#define function");
-    let macro_end_index = slice.find("};");
+    if visited.insert(call.clone()) && find_cycle(scope, target, path, visited) {
+      return true;
+    }
 
-    let macro_end_index = match macro_end_index {
-      Some(i) => i,
-      None => slice.len()
-    };
+    path.pop();
+  }
 
-    if define_index.is_none() || macro_end_index < define_index.unwrap() {
-      // let macro_end_index = slice.find("};");
+  false
+}
 
-      slice = &slice[..macro_end_index];
+/// Makes sure the chain of macros that led to the macro call, including the
+/// macros whose code blocks hold the call, is not deeper than the limit.
+fn check_expansion_depth(
+  offset_map: &OffsetMap, macro_call_index: usize, macro_name: &str, max_depth: usize
+) -> Result<(), PreprocessorError> {
+  let mut chain = offset_map.get_expansion_chain(macro_call_index);
+  chain.push(macro_name.to_string());
+
+  if chain.len() > max_depth {
+    return Err(PreprocessorError::new(
+      format!(
+        "macro expansion exceeds the maximum depth of {max_depth}: {}",
+        format_macro_chain(&chain)
+      ),
+      macro_call_index
+    ));
+  }
 
-      break;
-    }
+  Ok(())
+}
 
-    slice = &slice[define_index.unwrap() + "#define function".len()..];
+/// Formats a chain of macros like `A! -> B! -> A!`
+fn format_macro_chain(chain: &[String]) -> String {
+  chain
+    .iter()
+    .map(|name| format!("{name}!"))
+    .collect::<Vec<_>>()
+    .join(" -> ")
+}
 
-    let macro_end_index = slice.find("};");
+/// Builds the warning of a macro defined again with a different value or body,
+/// the range is the one of the new directive.
+fn get_redefinition_warning(
+  name: &str, previous_file: &str, offset_map: &OffsetMap, range: Range<usize>
+) -> Diagnostic {
+  let range = offset_map.get_original_range(range);
+
+  Diagnostic::build(ReportKind::Warning, range.range.start)
+    .with_message(format!("macro {name} is redefined with a different value"))
+    .with_label(
+      DiagnosticLabel::new(range.range).with_message("this definition replaces the previous one")
+    )
+    .with_note(format!("previously defined in {previous_file}"))
+    .with_included_file(range.file)
+}
 
-    if macro_end_index.is_none() {
-      panic!("2, unfinished macro");
-    }
+/// Removes the `#undef` directives the expansion left in the content, along
+/// with the definitions that follow them.
+pub fn remove_undef_directives(
+  new_content: &mut String, offset_map: &mut OffsetMap
+) -> Result<(), PreprocessorError> {
+  loop {
+    let ranges = [
+      find_undef_directive(new_content)?.map(|directive| directive.range),
+      find_function_directive(new_content)?.map(|directive| directive.range),
+      find_constant_directive(new_content)?.map(|directive| directive.range)
+    ];
+
+    let Some(range) = ranges.into_iter().flatten().min_by_key(|range| range.start) else {
+      return Ok(());
+    };
 
-    slice = &slice[macro_end_index.unwrap() + "};".len()..];
+    offset_map.remove_range(new_content, range);
   }
+}
 
-  let end = slice.as_ptr() as usize - macro_body.as_ptr() as usize + slice.len();
-
-  MacroFunction {
-    parameters,
-    body: macro_body[..end].to_string()
-  }
+/// Builds the warnings of the macro calls left in the content once the
+/// expansion is over, their macros are unknown to the file.
+pub fn get_unknown_macro_warnings(content: &str, offset_map: &OffsetMap) -> Vec<Diagnostic> {
+  find_macro_call_ranges(content)
+    .into_iter()
+    .map(|call| {
+      let range = offset_map.get_original_range(call.range);
+
+      Diagnostic::build(ReportKind::Warning, range.range.start)
+        .with_message(format!("macro call to unknown macro {}", call.name))
+        .with_label(
+          DiagnosticLabel::new(range.range)
+            .with_message(format!("no macro {} is visible here", call.name))
+        )
+        .with_help("define it, or check that no `#undef` hides it from this code")
+        .with_included_file(range.file)
+    })
+    .collect()
 }
@@ -0,0 +1,494 @@
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::condition::Condition;
+use super::directives::{find_condition_directive, find_include_directive};
+use super::get_files;
+use super::lexer::{tokenize, TokenKind};
+use super::offset_map::{IncludedFile, IncludedPart};
+use super::types::PreprocessorOutput;
+use crate::config::Config;
+use crate::error::CompileError;
+use crate::utils::{convert_line_endings, LineIndex};
+
+/// Replaces the `#include "path";` directives of the files with the content of
+/// the files they include. A file is looked up next to the file that includes
+/// it, and then in the directories of the dependencies sorted by name.
+///
+/// A file with a `#pragma once`, or whose content is wrapped in a
+/// `#ifndef GUARD { ... };` include guard, is included only once per file of
+/// the project.
+///
+/// The included code keeps pointing to the file it comes from in the offset
+/// maps, so the diagnostics in it are reported in the included file.
+///
+/// Returns the paths of the files that were included.
+pub fn handle_includes(
+  output: &PreprocessorOutput, config: &Config
+) -> Result<BTreeSet<PathBuf>, CompileError> {
+  let dependency_directories: Vec<PathBuf> =
+    config.dependencies.values().map(PathBuf::from).collect();
+  let mut included_files = BTreeSet::new();
+
+  for (_, file) in get_files(output) {
+    let mut context = IncludeContext {
+      dependency_directories: &dependency_directories,
+      included_once: HashSet::new(),
+      guards: HashSet::new(),
+      stack: vec![(get_canonical_path(&file.path), file.path.clone())],
+      included_files: &mut included_files
+    };
+
+    let mut new_content = file.content.borrow().to_string();
+    let replacements = context.resolve_includes(&new_content, 0..new_content.len(), &file.path)?;
+
+    let mut offset_map = file.offset_map.borrow_mut();
+
+    // from the last to the first so the ranges that remain are still valid
+    for (range, parts) in replacements.into_iter().rev() {
+      offset_map.include_range(&mut new_content, range, &parts);
+    }
+
+    file.content.replace(new_content);
+  }
+
+  Ok(included_files)
+}
+
+/// The ranges of the `#include` directives of a file, along with the parts of
+/// the included files that replace each of them.
+type Inclusions = Vec<(Range<usize>, Vec<IncludedPart>)>;
+
+struct IncludeContext<'a> {
+  dependency_directories: &'a [PathBuf],
+
+  /// The files with a `#pragma once` that were already included
+  included_once: HashSet<PathBuf>,
+
+  /// The include guards of the files that were already included
+  guards: HashSet<String>,
+
+  /// The files being included, from the file of the project to the last
+  /// included one. Along with the path as it is displayed.
+  stack: Vec<(PathBuf, PathBuf)>,
+
+  /// Every file that was read, for all the files of the project
+  included_files: &'a mut BTreeSet<PathBuf>
+}
+
+impl<'a> IncludeContext<'a> {
+  /// Returns the ranges of the `#include` directives in the `range` of the
+  /// content, along with the parts of the included files that replace each of
+  /// them.
+  fn resolve_includes(
+    &mut self, content: &str, range: Range<usize>, path: &Path
+  ) -> Result<Inclusions, CompileError> {
+    let mut replacements = Vec::new();
+    let mut offset = range.start;
+
+    while let Some(directive) = find_include_directive(&content[offset..range.end])
+      .map_err(|error| get_error(path, content, offset + error.offset, error.message))?
+    {
+      let directive_range = offset + directive.range.start..offset + directive.range.end;
+      offset = directive_range.end;
+
+      let Some(included_path) = self.find_file(&directive.path, path) else {
+        return Err(get_error(
+          path,
+          content,
+          directive_range.start,
+          format!("cannot find the included file {}", directive.path)
+        ));
+      };
+
+      let canonical_path = get_canonical_path(&included_path);
+      self.included_files.insert(canonical_path.clone());
+
+      if self.included_once.contains(&canonical_path) {
+        replacements.push((directive_range, Vec::new()));
+
+        continue;
+      }
+
+      if let Some(index) = self.stack.iter().position(|(p, _)| *p == canonical_path) {
+        let chain: Vec<String> = self.stack[index..]
+          .iter()
+          .map(|(_, displayed)| displayed)
+          .chain([&included_path])
+          .map(|path| path.display().to_string())
+          .collect();
+
+        return Err(get_error(
+          path,
+          content,
+          directive_range.start,
+          format!("include cycle: {}", chain.join(" -> "))
+        ));
+      }
+
+      let included_content = std::fs::read_to_string(&included_path)
+        .map(convert_line_endings)
+        .map_err(|error| CompileError::Io {
+          path: included_path.clone(),
+          error
+        })?;
+
+      if has_pragma_once(&included_content) {
+        self.included_once.insert(canonical_path.clone());
+      }
+
+      // the guard is resolved here as the macros it defines are visible to the
+      // other files of the project, which would skip the content otherwise.
+      let body = match get_include_guard(&included_content) {
+        Some((guard, _)) if self.guards.contains(&guard) => {
+          replacements.push((directive_range, Vec::new()));
+
+          continue;
+        }
+        Some((guard, body)) => {
+          self.guards.insert(guard);
+
+          body
+        }
+        None => 0..included_content.len()
+      };
+
+      self.stack.push((canonical_path, included_path.clone()));
+      let nested_replacements =
+        self.resolve_includes(&included_content, body.clone(), &included_path)?;
+      self.stack.pop();
+
+      let file = Rc::new(IncludedFile {
+        path: included_path.display().to_string(),
+        content: included_content
+      });
+      let mut parts = Vec::new();
+      let mut position = body.start;
+
+      for (range, nested_parts) in nested_replacements {
+        parts.push(IncludedPart {
+          file: file.clone(),
+          range: position..range.start
+        });
+        parts.extend(nested_parts);
+        position = range.end;
+      }
+
+      parts.push(IncludedPart {
+        file,
+        range: position..body.end
+      });
+      replacements.push((directive_range, parts));
+    }
+
+    Ok(replacements)
+  }
+
+  fn find_file(&self, include: &str, including_file: &Path) -> Option<PathBuf> {
+    let directory = including_file.parent().unwrap_or(Path::new(""));
+
+    std::iter::once(directory)
+      .chain(self.dependency_directories.iter().map(PathBuf::as_path))
+      .map(|directory| directory.join(include))
+      .find(|path| path.is_file())
+  }
+}
+
+fn has_pragma_once(content: &str) -> bool {
+  tokenize(content).iter().any(|token| {
+    token.kind == TokenKind::Pragma && content[token.range.clone()].trim() == "#pragma once"
+  })
+}
+
+/// Returns the name of the include guard of the content and the range of the
+/// code it guards, if the whole content is in a `#ifndef GUARD { ... };`
+/// directive.
+fn get_include_guard(content: &str) -> Option<(String, Range<usize>)> {
+  let directive = find_condition_directive(content).ok()??;
+
+  let is_empty = |text: &str| {
+    tokenize(text).iter().all(|token| {
+      matches!(
+        token.kind,
+        TokenKind::Whitespace | TokenKind::Comment | TokenKind::Pragma
+      )
+    })
+  };
+
+  if !is_empty(&content[..directive.range.start])
+    || !is_empty(&content[directive.range.end..])
+    || directive.branches.len() != 1
+    || directive.else_body.is_some()
+  {
+    return None;
+  }
+
+  let branch = &directive.branches[0];
+  match &branch.condition {
+    Condition::Not(condition) => match condition.as_ref() {
+      Condition::Defined(name) => Some((name.clone(), branch.body.clone())),
+      _ => None
+    },
+    _ => None
+  }
+}
+
+fn get_canonical_path(path: &Path) -> PathBuf {
+  std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Builds the error of an `#include` directive, the content is the one of the
+/// file as it was read.
+fn get_error(path: &Path, content: &str, offset: usize, message: String) -> CompileError {
+  let (line, column) = LineIndex::new(content).get_line_and_column(content, offset);
+
+  CompileError::Preprocessor {
+    path: path.to_path_buf(),
+    line,
+    column,
+    message
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::offset_map::OffsetMap;
+  use super::*;
+
+  /// A directory of the system's temporary directory that is removed once the
+  /// test ends.
+  struct TestDirectory(PathBuf);
+
+  impl TestDirectory {
+    fn new(name: &str, files: &[(&str, &str)]) -> Self {
+      let path = std::env::temp_dir().join(format!("cahirc-include-{name}-{}", std::process::id()));
+      std::fs::create_dir_all(&path).unwrap();
+
+      for (file, content) in files {
+        std::fs::write(path.join(file), content).unwrap();
+      }
+
+      Self(path)
+    }
+
+    /// Returns the content of `main.wss` with its includes resolved
+    fn include(&self) -> Result<String, CompileError> {
+      let path = self.0.join("main.wss");
+      let mut content = std::fs::read_to_string(&path).unwrap();
+      let mut included_files = BTreeSet::new();
+      let mut context = IncludeContext {
+        dependency_directories: &[],
+        included_once: HashSet::new(),
+        guards: HashSet::new(),
+        stack: vec![(get_canonical_path(&path), path.clone())],
+        included_files: &mut included_files
+      };
+
+      let replacements = context.resolve_includes(&content, 0..content.len(), &path)?;
+
+      for (range, parts) in replacements.into_iter().rev() {
+        content.replace_range(
+          range,
+          &parts.iter().map(IncludedPart::text).collect::<String>()
+        );
+      }
+
+      Ok(content)
+    }
+
+    fn display(&self, file: &str) -> String {
+      self.0.join(file).display().to_string()
+    }
+  }
+
+  impl Drop for TestDirectory {
+    fn drop(&mut self) {
+      let _ = std::fs::remove_dir_all(&self.0);
+    }
+  }
+
+  fn get_message(error: CompileError) -> String {
+    match error {
+      CompileError::Preprocessor { message, .. } => message,
+      error => panic!("expected a preprocessor error, got {error}")
+    }
+  }
+
+  #[test]
+  fn replaces_the_nested_includes() {
+    let directory = TestDirectory::new(
+      "nested",
+      &[
+        ("main.wss", "a;\n#include \"a.wssh\";\nb;"),
+        ("a.wssh", "x;\n#include \"b.wssh\";"),
+        ("b.wssh", "y;")
+      ]
+    );
+
+    assert_eq!(directory.include().unwrap(), "a;\nx;\ny;\nb;");
+  }
+
+  #[test]
+  fn maps_the_included_code_to_the_included_files() {
+    let directory = TestDirectory::new(
+      "offsets",
+      &[
+        ("main.wss", "a;\n#include \"a.wssh\";\nb;"),
+        ("a.wssh", "x;\n#include \"b.wssh\";"),
+        ("b.wssh", "y;")
+      ]
+    );
+
+    let path = directory.0.join("main.wss");
+    let mut content = std::fs::read_to_string(&path).unwrap();
+    let mut offset_map = OffsetMap::new(content.len());
+    let mut included_files = BTreeSet::new();
+    let mut context = IncludeContext {
+      dependency_directories: &[],
+      included_once: HashSet::new(),
+      guards: HashSet::new(),
+      stack: vec![(get_canonical_path(&path), path.clone())],
+      included_files: &mut included_files
+    };
+
+    let replacements = context
+      .resolve_includes(&content, 0..content.len(), &path)
+      .unwrap();
+
+    for (range, parts) in replacements.into_iter().rev() {
+      offset_map.include_range(&mut content, range, &parts);
+    }
+
+    assert_eq!(content, "a;\nx;\ny;\nb;");
+
+    let get_location = |range: Range<usize>| {
+      let original_range = offset_map.get_original_range(range);
+      let path = original_range.file.as_ref().map(|file| file.path.clone());
+
+      (path, original_range.range)
+    };
+
+    assert_eq!(
+      get_location(3..4),
+      (Some(directory.display("a.wssh")), 0..1)
+    );
+    assert_eq!(
+      get_location(6..7),
+      (Some(directory.display("b.wssh")), 0..1)
+    );
+    assert_eq!(get_location(9..10), (None, 22..23));
+  }
+
+  #[test]
+  fn includes_the_pragma_once_files_once() {
+    let directory = TestDirectory::new(
+      "once",
+      &[
+        ("main.wss", "#include \"a.wssh\";\n#include \"a.wssh\";"),
+        ("a.wssh", "#pragma once\nx;")
+      ]
+    );
+
+    assert_eq!(directory.include().unwrap(), "#pragma once\nx;\n");
+  }
+
+  #[test]
+  fn includes_the_guarded_files_once() {
+    let directory = TestDirectory::new(
+      "guard",
+      &[
+        ("main.wss", "#include \"a.wssh\";\n#include \"a.wssh\";"),
+        (
+          "a.wssh",
+          "// constants\n#ifndef A { #define const A; x; };\n"
+        )
+      ]
+    );
+
+    assert_eq!(directory.include().unwrap(), " #define const A; x; \n");
+  }
+
+  #[test]
+  fn reports_the_include_cycles() {
+    let directory = TestDirectory::new(
+      "cycle",
+      &[
+        ("main.wss", "#include \"a.wssh\";"),
+        ("a.wssh", "#include \"b.wssh\";"),
+        ("b.wssh", "x;\n#include \"a.wssh\";")
+      ]
+    );
+
+    let error = directory.include().unwrap_err();
+    let CompileError::Preprocessor {
+      path,
+      line,
+      column,
+      message
+    } = error
+    else {
+      panic!("expected a preprocessor error, got {error}");
+    };
+
+    assert_eq!(path, directory.0.join("b.wssh"));
+    assert_eq!((line, column), (2, 1));
+    assert_eq!(
+      message,
+      format!(
+        "include cycle: {} -> {} -> {}",
+        directory.display("a.wssh"),
+        directory.display("b.wssh"),
+        directory.display("a.wssh")
+      )
+    );
+  }
+
+  #[test]
+  fn reports_a_file_that_includes_itself() {
+    let directory = TestDirectory::new("self", &[("main.wss", "#include \"main.wss\";")]);
+
+    assert_eq!(
+      get_message(directory.include().unwrap_err()),
+      format!(
+        "include cycle: {} -> {}",
+        directory.display("main.wss"),
+        directory.display("main.wss")
+      )
+    );
+  }
+
+  #[test]
+  fn reports_the_missing_files() {
+    let directory = TestDirectory::new("missing", &[("main.wss", "#include \"missing.wssh\";")]);
+
+    assert_eq!(
+      get_message(directory.include().unwrap_err()),
+      "cannot find the included file missing.wssh"
+    );
+  }
+
+  #[test]
+  fn finds_the_pragma_once() {
+    assert!(has_pragma_once("// shared\n#pragma once\nx;"));
+    assert!(!has_pragma_once("// #pragma once\nx;"));
+    assert!(!has_pragma_once("\"#pragma once\";"));
+  }
+
+  #[test]
+  fn finds_the_include_guards() {
+    let content = "/* guard */\n#ifndef GUARD {\n  x;\n};\n";
+    let (guard, body) = get_include_guard(content).unwrap();
+
+    assert_eq!(guard, "GUARD");
+    assert_eq!(&content[body], "\n  x;\n");
+
+    // the content must be entirely guarded by a single `#ifndef`
+    assert!(get_include_guard("y;\n#ifndef GUARD { x; };").is_none());
+    assert!(get_include_guard("#ifndef GUARD { x; };\ny;").is_none());
+    assert!(get_include_guard("#ifdef GUARD { x; };").is_none());
+    assert!(get_include_guard("#ifndef GUARD { x; } #else { y; };").is_none());
+    assert!(get_include_guard("#if !GUARD { x; };").is_none());
+  }
+}
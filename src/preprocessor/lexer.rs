@@ -0,0 +1,238 @@
+use std::ops::Range;
+
+/// A piece of code as seen by the preprocessor, which only cares about the
+/// identifiers and the parts of the code where identifiers must be ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+  pub kind: TokenKind,
+  pub range: Range<usize>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+  Identifier,
+
+  /// A `"string"` or a `'name'` literal
+  Literal,
+
+  /// A `// line` or a `/* block */` comment
+  Comment,
+
+  /// A `#pragma` line, up to the end of the line
+  Pragma,
+
+  Whitespace,
+
+  /// Any other character
+  Symbol
+}
+
+pub fn tokenize(input: &str) -> Vec<Token> {
+  let bytes = input.as_bytes();
+  let mut tokens = Vec::new();
+  let mut start = 0;
+
+  while start < bytes.len() {
+    let rest = &input[start..];
+    let byte = bytes[start];
+
+    let (kind, length) = if is_identifier_byte(byte) {
+      let length = rest
+        .bytes()
+        .position(|b| !is_identifier_byte(b))
+        .unwrap_or(rest.len());
+
+      (TokenKind::Identifier, length)
+    } else if byte == b'"' || byte == b'\'' {
+      // the literals have no escape sequences in the language
+      let length = rest[1..]
+        .bytes()
+        .position(|b| b == byte)
+        .map(|end| end + 2)
+        .unwrap_or(rest.len());
+
+      (TokenKind::Literal, length)
+    } else if rest.starts_with("//") {
+      (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+    } else if rest.starts_with("/*") {
+      let length = rest.find("*/").map(|end| end + 2).unwrap_or(rest.len());
+
+      (TokenKind::Comment, length)
+    } else if rest.starts_with("#pragma ") {
+      (TokenKind::Pragma, rest.find('\n').unwrap_or(rest.len()))
+    } else if byte.is_ascii_whitespace() {
+      let length = rest
+        .bytes()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(rest.len());
+
+      (TokenKind::Whitespace, length)
+    } else {
+      let length = rest.chars().next().map(char::len_utf8).unwrap_or(1);
+
+      (TokenKind::Symbol, length)
+    };
+
+    tokens.push(Token {
+      kind,
+      range: start..start + length
+    });
+
+    start += length;
+  }
+
+  tokens
+}
+
+fn is_identifier_byte(byte: u8) -> bool {
+  byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+/// A `NAME!` macro call, the range covers the name and the `!`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacroCall {
+  pub name: String,
+  pub range: Range<usize>
+}
+
+/// Returns the macro calls of the code, in order. The calls in the literals,
+/// the comments and the pragmas are ignored.
+pub fn find_macro_call_ranges(input: &str) -> Vec<MacroCall> {
+  let tokens = tokenize(input);
+  let text = |token: &Token| &input[token.range.clone()];
+
+  tokens
+    .iter()
+    .enumerate()
+    .filter_map(|(index, name)| {
+      let bang = tokens.get(index + 1)?;
+
+      // `a!= b` is a comparison, not a macro call
+      let is_call = name.kind == TokenKind::Identifier
+        && text(bang) == "!"
+        && tokens.get(index + 2).map(text) != Some("=");
+
+      is_call.then(|| MacroCall {
+        name: text(name).to_string(),
+        range: name.range.start..bang.range.end
+      })
+    })
+    .collect()
+}
+
+/// Returns the names of the macros the code calls, in order and without
+/// duplicates. The calls in the literals, the comments and the pragmas are
+/// ignored.
+pub fn find_macro_calls(input: &str) -> Vec<String> {
+  let mut calls: Vec<String> = Vec::new();
+
+  for call in find_macro_call_ranges(input) {
+    if !calls.contains(&call.name) {
+      calls.push(call.name);
+    }
+  }
+
+  calls
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn kinds(input: &str) -> Vec<(TokenKind, &str)> {
+    tokenize(input)
+      .into_iter()
+      .map(|token| (token.kind, &input[token.range]))
+      .collect()
+  }
+
+  #[test]
+  fn splits_the_code_in_tokens() {
+    use TokenKind::*;
+
+    assert_eq!(
+      kinds("var a_1 = \"b c\" + 'd';"),
+      vec![
+        (Identifier, "var"),
+        (Whitespace, " "),
+        (Identifier, "a_1"),
+        (Whitespace, " "),
+        (Symbol, "="),
+        (Whitespace, " "),
+        (Literal, "\"b c\""),
+        (Whitespace, " "),
+        (Symbol, "+"),
+        (Whitespace, " "),
+        (Literal, "'d'"),
+        (Symbol, ";")
+      ]
+    );
+  }
+
+  #[test]
+  fn reads_the_comments_and_the_pragmas_as_one_token() {
+    use TokenKind::*;
+
+    assert_eq!(
+      kinds("a // b c\n/* d\ne */#pragma once\nf"),
+      vec![
+        (Identifier, "a"),
+        (Whitespace, " "),
+        (Comment, "// b c"),
+        (Whitespace, "\n"),
+        (Comment, "/* d\ne */"),
+        (Pragma, "#pragma once"),
+        (Whitespace, "\n"),
+        (Identifier, "f")
+      ]
+    );
+  }
+
+  #[test]
+  fn ends_the_unterminated_tokens_with_the_input() {
+    use TokenKind::*;
+
+    assert_eq!(
+      kinds("a \"b c"),
+      vec![(Identifier, "a"), (Whitespace, " "), (Literal, "\"b c")]
+    );
+    assert_eq!(kinds("/* a"), vec![(Comment, "/* a")]);
+  }
+
+  #[test]
+  fn covers_the_whole_input() {
+    let input = "é! // ü\n\"ß\" a!=b";
+    let tokens = tokenize(input);
+
+    assert_eq!(tokens.first().map(|t| t.range.start), Some(0));
+    assert_eq!(tokens.last().map(|t| t.range.end), Some(input.len()));
+    assert!(tokens
+      .windows(2)
+      .all(|pair| pair[0].range.end == pair[1].range.start));
+  }
+
+  #[test]
+  fn finds_the_macro_calls() {
+    let input = "FOO!(a) a != b c!= d \"BAR!\" // BAZ!\n#pragma find QUX!\nFOO!";
+
+    assert_eq!(
+      find_macro_call_ranges(input),
+      vec![
+        MacroCall {
+          name: String::from("FOO"),
+          range: 0..4
+        },
+        MacroCall {
+          name: String::from("FOO"),
+          range: input.len() - 4..input.len()
+        }
+      ]
+    );
+    assert_eq!(find_macro_calls(input), vec!["FOO"]);
+  }
+
+  #[test]
+  fn keeps_the_order_of_the_macro_calls() {
+    assert_eq!(find_macro_calls("B!(A!, C!) A!"), vec!["B", "A", "C"]);
+  }
+}
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::types::{DependencyName, FileName, MacroConstant, MacroDefinition};
+
+/// The name the macros of the config are registered under
+pub const CONFIG_FILE_NAME: &str = "cahirc.toml";
+
+/// The macros of the project, sorted by visibility. The macros the files
+/// define are exported to their package unless they are defined with
+/// `#define local`, in which case only the file sees them.
+///
+/// A macro is looked up, in order: in the file, in the package of the file, in
+/// the config, in the sources and then in the dependencies sorted by name. So a
+/// library cannot replace a macro of the project, and the order doesn't depend
+/// on the order the files are read in.
+pub struct MacroRegistry {
+  /// The constants of the config and of the command line
+  config: HashMap<String, RegisteredMacro>,
+
+  /// The macros each package exports, the sources are the `None` package
+  packages: BTreeMap<Option<DependencyName>, HashMap<String, RegisteredMacro>>,
+
+  /// The macros defined with `#define local`, for each file
+  files: HashMap<FileName, HashMap<String, RegisteredMacro>>
+}
+
+pub struct RegisteredMacro {
+  pub definition: MacroDefinition,
+
+  /// The file that defines the macro
+  pub file: FileName
+}
+
+/// The visibility of a macro definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacroVisibility {
+  /// Only visible to the file that defines it, `#define local`
+  File,
+
+  /// Visible to every file, the files of the package the macro comes from
+  /// first.
+  Exported
+}
+
+/// A file of the project and the package it belongs to
+#[derive(Debug, Clone, Copy)]
+pub struct FileOrigin<'a> {
+  pub name: &'a str,
+
+  /// The dependency the file comes from, `None` for the sources
+  pub package: Option<&'a DependencyName>
+}
+
+/// The macros a file sees
+#[derive(Clone, Copy)]
+pub struct MacroScope<'a> {
+  registry: &'a MacroRegistry,
+  origin: FileOrigin<'a>,
+
+  /// The macros the `#undef` directives of the file hide at this point of the
+  /// file
+  hidden: Option<&'a HashSet<String>>
+}
+
+impl MacroRegistry {
+  /// Creates a registry that holds the constants of the config
+  pub fn new(macro_constants: &BTreeMap<String, String>) -> Self {
+    let config = macro_constants
+      .iter()
+      .map(|(name, value)| {
+        let definition = MacroDefinition::Constant(MacroConstant {
+          name: name.clone(),
+          value: value.clone(),
+          calls: Vec::new()
+        });
+
+        let registered = RegisteredMacro {
+          definition,
+          file: CONFIG_FILE_NAME.to_string()
+        };
+
+        (name.clone(), registered)
+      })
+      .collect();
+
+    Self {
+      config,
+      packages: BTreeMap::new(),
+      files: HashMap::new()
+    }
+  }
+
+  /// Returns the macros the file sees
+  pub fn scope<'a>(&'a self, origin: FileOrigin<'a>) -> MacroScope<'a> {
+    MacroScope {
+      registry: self,
+      origin,
+      hidden: None
+    }
+  }
+
+  /// Registers the macro the file defines. If the macro was already defined
+  /// differently in the same scope or in the config, returns the file of the
+  /// previous definition so the caller can report the conflict.
+  pub fn define(
+    &mut self, name: String, definition: MacroDefinition, visibility: MacroVisibility,
+    origin: FileOrigin
+  ) -> Option<FileName> {
+    let FileOrigin {
+      name: file,
+      package
+    } = origin;
+
+    let previous = match visibility {
+      MacroVisibility::File => self.files.get(file).and_then(|macros| macros.get(&name)),
+      MacroVisibility::Exported => self
+        .packages
+        .get(&package.cloned())
+        .and_then(|macros| macros.get(&name))
+        .or_else(|| self.config.get(&name))
+    };
+
+    let conflict = previous
+      .filter(|previous| !previous.definition.is_same_as(&definition))
+      .map(|previous| previous.file.clone());
+
+    let scope = match visibility {
+      MacroVisibility::File => self.files.entry(file.to_string()).or_default(),
+      MacroVisibility::Exported => self.packages.entry(package.cloned()).or_default()
+    };
+
+    scope.insert(
+      name,
+      RegisteredMacro {
+        definition,
+        file: file.to_string()
+      }
+    );
+
+    conflict
+  }
+}
+
+impl<'a> MacroScope<'a> {
+  /// Returns the scope without the macros `#undef` directives hide, whatever
+  /// the scope they come from.
+  pub fn hiding(self, hidden: &'a HashSet<String>) -> Self {
+    Self {
+      hidden: Some(hidden),
+      ..self
+    }
+  }
+
+  pub fn is_hidden(&self, name: &str) -> bool {
+    self.hidden.is_some_and(|hidden| hidden.contains(name))
+  }
+
+  pub fn get(&self, name: &str) -> Option<&'a MacroDefinition> {
+    let registry = self.registry;
+    let FileOrigin {
+      name: file,
+      package
+    } = self.origin;
+
+    if self.is_hidden(name) {
+      return None;
+    }
+
+    let own_package = registry.packages.get(&package.cloned());
+    let other_packages = registry
+      .packages
+      .iter()
+      .filter(|(other, _)| other.as_ref() != package)
+      .map(|(_, macros)| macros);
+
+    registry
+      .files
+      .get(file)
+      .into_iter()
+      .chain(own_package)
+      .chain(std::iter::once(&registry.config))
+      .chain(other_packages)
+      .find_map(|macros| macros.get(name))
+      .map(|registered| &registered.definition)
+  }
+
+  pub fn contains(&self, name: &str) -> bool {
+    self.get(name).is_some()
+  }
+}
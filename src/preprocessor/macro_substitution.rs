@@ -0,0 +1,386 @@
+use std::collections::HashSet;
+
+use super::directives::find_for_directive;
+use super::lexer::{tokenize, Token, TokenKind};
+use super::types::{MacroFunction, PreprocessorError};
+
+/// Returns the body of the macro function with its parameters replaced by the
+/// given values. Only the whole identifiers are replaced, the literals and the
+/// comments are left untouched. The pragma lines are the exception as their
+/// find & replace patterns are plain text.
+///
+/// The values after the fixed parameters go to the variadic parameter, which
+/// expands to the values separated by commas or is iterated with `#for`. In the
+/// body, `#parameter` turns the value into a string literal and `a ## b` pastes
+/// the two sides together.
+///
+/// If the body contains a `#pragma hygiene` line, the variables the body
+/// declares are renamed so they can't collide with the variables of another
+/// expansion of the macro.
+pub fn substitute_parameters(
+  function: &MacroFunction, macro_name: &str, values: &[&str]
+) -> String {
+  let fixed_count = values.len().min(function.parameters.len());
+  let (fixed_values, extra_values) = values.split_at(fixed_count);
+
+  let body = expand_for_directives(
+    &function.body,
+    function.variadic.as_deref(),
+    extra_values.len()
+  );
+  let tokens = tokenize(&body);
+  let hygienic_variables = get_hygienic_variables(function, &body, &tokens);
+
+  let expansion_index = function.expansions.get() + 1;
+  function.expansions.set(expansion_index);
+
+  let get_value = |name: &str| -> Option<String> {
+    if let Some(index) = function.parameters.iter().position(|p| p == name) {
+      let value = fixed_values
+        .get(index)
+        .map(|value| value.trim())
+        .unwrap_or("");
+
+      return Some(value.to_string());
+    }
+
+    let rest = function.variadic.as_deref()?;
+    if name == rest {
+      let values: Vec<&str> = extra_values.iter().map(|value| value.trim()).collect();
+
+      return Some(values.join(", "));
+    }
+
+    get_loop_item_index(name, rest)
+      .and_then(|index| extra_values.get(index))
+      .map(|value| value.trim().to_string())
+  };
+
+  let text = |token: &Token| &body[token.range.clone()];
+  let is_symbol = |token: Option<&Token>, symbol: &str| {
+    token.is_some_and(|t| t.kind == TokenKind::Symbol && text(t) == symbol)
+  };
+
+  let mut output = String::with_capacity(body.len());
+  let mut i = 0;
+
+  while let Some(token) = tokens.get(i) {
+    let next = tokens
+      .get(i + 1)
+      .filter(|next| next.range.start == token.range.end);
+    i += 1;
+
+    // `a ## b`, the whitespaces around the operator are removed
+    if is_symbol(Some(token), "#") && is_symbol(next, "#") {
+      output.truncate(output.trim_end().len());
+      i += 1;
+
+      while tokens
+        .get(i)
+        .is_some_and(|t| t.kind == TokenKind::Whitespace)
+      {
+        i += 1;
+      }
+
+      continue;
+    }
+
+    // `#parameter`
+    if is_symbol(Some(token), "#") {
+      if let Some(value) = next
+        .filter(|next| next.kind == TokenKind::Identifier)
+        .and_then(|next| get_value(text(next)))
+      {
+        output.push_str(&stringify(&value));
+        i += 1;
+
+        continue;
+      }
+    }
+
+    match token.kind {
+      TokenKind::Identifier => {
+        let name = text(token);
+
+        if let Some(value) = get_value(name) {
+          output.push_str(&value);
+        } else if hygienic_variables.contains(name) && !is_member_access(&body, &tokens[..i - 1]) {
+          output.push_str(&format!("{name}_{macro_name}_{expansion_index}"));
+        } else {
+          output.push_str(name);
+        }
+      }
+      TokenKind::Pragma => {
+        let mut pragma = text(token).to_string();
+
+        for (parameter, value) in function.parameters.iter().zip(fixed_values) {
+          pragma = pragma.replace(parameter, value.trim());
+        }
+
+        output.push_str(&pragma);
+      }
+      _ => output.push_str(text(token))
+    };
+  }
+
+  output
+}
+
+/// Makes sure the `#for` directives of the body iterate over the variadic
+/// parameter, the offsets of the errors are relative to the body.
+pub fn check_for_directives(
+  body: &str, variadic: Option<&str>, macro_name: &str
+) -> Result<(), PreprocessorError> {
+  let mut offset = 0;
+
+  while let Some(directive) = find_for_directive(&body[offset..])
+    .map_err(|error| PreprocessorError::new(error.message, offset + error.offset))?
+  {
+    if variadic != Some(directive.list.as_str()) {
+      return Err(PreprocessorError::new(
+        format!(
+          "`#for` can only iterate over the variadic parameter of the macro {macro_name}, not {}",
+          directive.list
+        ),
+        offset + directive.range.start
+      ));
+    }
+
+    // the nested directives are checked as well
+    offset += directive.body.start;
+  }
+
+  Ok(())
+}
+
+/// Repeats the body of the `#for` directives once per value of the variadic
+/// parameter. The item is replaced by a placeholder that the substitution
+/// replaces by the value, so a value is never substituted twice.
+fn expand_for_directives(body: &str, variadic: Option<&str>, value_count: usize) -> String {
+  let mut body = body.to_string();
+
+  while let Ok(Some(directive)) = find_for_directive(&body) {
+    let Some(rest) = variadic.filter(|rest| *rest == directive.list) else {
+      break;
+    };
+
+    let loop_body = &body[directive.body.clone()];
+    let repeated_body: String = (0..value_count)
+      .map(|index| {
+        tokenize(loop_body)
+          .into_iter()
+          .map(|token| match &loop_body[token.range.clone()] {
+            name if token.kind == TokenKind::Identifier && name == directive.item => {
+              get_loop_item_name(rest, index)
+            }
+            text => text.to_string()
+          })
+          .collect::<String>()
+      })
+      .collect();
+
+    body.replace_range(directive.range, &repeated_body);
+  }
+
+  body
+}
+
+fn get_loop_item_name(rest: &str, index: usize) -> String {
+  format!("__{rest}_item_{index}__")
+}
+
+/// Returns the index of the value a `#for` placeholder stands for
+fn get_loop_item_index(name: &str, rest: &str) -> Option<usize> {
+  name
+    .strip_prefix(&format!("__{rest}_item_"))
+    .and_then(|name| name.strip_suffix("__"))
+    .and_then(|index| index.parse().ok())
+}
+
+/// Turns the value into a string literal, the literals have no escape
+/// sequences so its double quotes become single quotes.
+fn stringify(value: &str) -> String {
+  format!("\"{}\"", value.replace('"', "'"))
+}
+
+/// Returns the variables to rename in the body. `#pragma hygiene i, j` renames
+/// the listed variables while `#pragma hygiene` alone renames every variable
+/// the body declares with `var`.
+fn get_hygienic_variables(
+  function: &MacroFunction, body: &str, tokens: &[Token]
+) -> HashSet<String> {
+  let pragma = tokens
+    .iter()
+    .filter(|token| token.kind == TokenKind::Pragma)
+    .map(|token| body[token.range.clone()].trim())
+    .find_map(|pragma| pragma.strip_prefix("#pragma hygiene"));
+
+  let Some(names) = pragma else {
+    return HashSet::new();
+  };
+
+  let listed_names: HashSet<String> = names
+    .split(|c: char| c == ',' || c.is_whitespace())
+    .filter(|name| !name.is_empty())
+    .map(str::to_string)
+    .collect();
+
+  if !listed_names.is_empty() {
+    return listed_names;
+  }
+
+  let mut declared_names = HashSet::new();
+  let mut significant_tokens = tokens
+    .iter()
+    .filter(|token| !matches!(token.kind, TokenKind::Whitespace | TokenKind::Comment))
+    .map(|token| (token.kind, &body[token.range.clone()]));
+
+  while let Some((kind, text)) = significant_tokens.next() {
+    if kind != TokenKind::Identifier || text != "var" {
+      continue;
+    }
+
+    // var a, b: int;
+    for (kind, text) in significant_tokens.by_ref() {
+      match (kind, text) {
+        (TokenKind::Identifier, name) => declared_names.insert(name.to_string()),
+        (TokenKind::Symbol, ",") => continue,
+        _ => break
+      };
+    }
+  }
+
+  // the parameters are replaced by the values of the caller, they are not
+  // local to the macro.
+  declared_names
+    .into_iter()
+    .filter(|name| !function.parameters.contains(name) && function.variadic.as_ref() != Some(name))
+    .collect()
+}
+
+/// Returns whether the token after the given ones is accessed as a member of
+/// something else, like `this.i`.
+fn is_member_access(body: &str, previous_tokens: &[Token]) -> bool {
+  previous_tokens
+    .iter()
+    .rev()
+    .find(|token| token.kind != TokenKind::Whitespace)
+    .is_some_and(|token| &body[token.range.clone()] == ".")
+}
+
+#[cfg(test)]
+mod tests {
+  use std::cell::Cell;
+
+  use super::*;
+
+  fn function(parameters: &[&str], variadic: Option<&str>, body: &str) -> MacroFunction {
+    MacroFunction {
+      parameters: parameters.iter().map(|p| p.to_string()).collect(),
+      variadic: variadic.map(str::to_string),
+      body: body.to_string(),
+      expansions: Cell::new(0),
+      calls: Vec::new()
+    }
+  }
+
+  #[test]
+  fn replaces_only_the_whole_identifiers() {
+    let function = function(&["a"], None, "a + ab + a_b + 'a' + \"a\" // a");
+
+    assert_eq!(
+      substitute_parameters(&function, "M", &[" 1 "]),
+      "1 + ab + a_b + 'a' + \"a\" // a"
+    );
+  }
+
+  #[test]
+  fn replaces_the_missing_values_with_nothing() {
+    let function = function(&["a", "b"], None, "f(a, b)");
+
+    assert_eq!(substitute_parameters(&function, "M", &["1"]), "f(1, )");
+  }
+
+  #[test]
+  fn replaces_the_parameters_of_the_pragmas_as_text() {
+    let function = function(&["name"], None, "#pragma find name_x\n");
+
+    assert_eq!(
+      substitute_parameters(&function, "M", &["foo"]),
+      "#pragma find foo_x\n"
+    );
+  }
+
+  #[test]
+  fn expands_the_variadic_parameter() {
+    let function = function(&["prefix"], Some("rest"), "log(prefix, rest);");
+
+    assert_eq!(
+      substitute_parameters(&function, "M", &["a", " b", " c"]),
+      "log(a, b, c);"
+    );
+    assert_eq!(substitute_parameters(&function, "M", &["a"]), "log(a, );");
+  }
+
+  #[test]
+  fn repeats_the_for_directives() {
+    let function = function(&[], Some("rest"), "#for item in rest { f(item); };");
+
+    assert_eq!(
+      substitute_parameters(&function, "M", &["a", "b"]),
+      " f(a);  f(b); "
+    );
+    assert_eq!(substitute_parameters(&function, "M", &[]), "");
+  }
+
+  #[test]
+  fn stringifies_and_pastes_the_parameters() {
+    let function = function(&["a", "b"], None, "#a + a ## _ ## b");
+
+    assert_eq!(
+      substitute_parameters(&function, "M", &["say(\"hi\")", "x"]),
+      "\"say('hi')\" + say(\"hi\")_x"
+    );
+  }
+
+  #[test]
+  fn renames_the_hygienic_variables() {
+    let function = function(
+      &["value"],
+      None,
+      "#pragma hygiene\nvar i, j: int;\ni = value;\nthis.i = j;"
+    );
+
+    assert_eq!(
+      substitute_parameters(&function, "LOOP", &["1"]),
+      "#pragma hygiene\nvar i_LOOP_1, j_LOOP_1: int;\ni_LOOP_1 = 1;\nthis.i = j_LOOP_1;"
+    );
+    assert_eq!(
+      substitute_parameters(&function, "LOOP", &["2"]),
+      "#pragma hygiene\nvar i_LOOP_2, j_LOOP_2: int;\ni_LOOP_2 = 2;\nthis.i = j_LOOP_2;"
+    );
+  }
+
+  #[test]
+  fn renames_only_the_listed_hygienic_variables() {
+    let function = function(&[], None, "#pragma hygiene i\nvar i, j: int;");
+
+    assert_eq!(
+      substitute_parameters(&function, "M", &[]),
+      "#pragma hygiene i\nvar i_M_1, j: int;"
+    );
+  }
+
+  #[test]
+  fn checks_the_list_of_the_for_directives() {
+    assert!(check_for_directives("#for a in rest { a };", Some("rest"), "M").is_ok());
+
+    let error = check_for_directives("x;\n#for a in other { a };", Some("rest"), "M").unwrap_err();
+
+    assert_eq!(
+      error.message,
+      "`#for` can only iterate over the variadic parameter of the macro M, not other"
+    );
+    assert_eq!(error.offset, 3);
+  }
+}
@@ -1,30 +1,45 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::path::Path;
-
-use regex::{Regex, RegexBuilder};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
 
+mod builtins;
+mod condition;
 mod conditionals;
+mod directives;
 mod expand_macros;
+mod include;
+mod lexer;
+mod macro_registry;
+mod macro_substitution;
+pub mod offset_map;
 mod pragma_replace;
 mod registry;
+mod strip_pragmas;
 pub mod types;
 
-use crate::utils::convert_line_endings;
+use crate::config::{Config, DEFAULT_MAX_MACRO_DEPTH};
+use crate::error::CompileError;
+use crate::utils::{convert_line_endings, LineIndex};
 
-use self::conditionals::filter_conditionals;
+use self::builtins::BuiltinValues;
+use self::expand_macros::ExpansionOptions;
+use self::macro_registry::{FileOrigin, MacroRegistry};
+use self::strip_pragmas::{get_print_pragma_report, strip_pragmas};
 use self::types::*;
 
 /// Entry point for the pre-processor,
-/// It takes as input the source directory and the list of dependencies and their
-/// names. And returns as output the files content from the source directory
-/// and the files content from the dependencies.
-pub fn preprocess(
-  source_directory: &str, dependencies: &HashMap<String, String>
-) -> std::io::Result<PreprocessorOutput> {
+/// It takes as input the config of the project, for its source directory, its
+/// dependencies and the macros it defines. And returns as output the files
+/// content from the source directory and the files content from the
+/// dependencies.
+pub fn preprocess(config: &Config) -> Result<PreprocessorOutput, CompileError> {
+  let source_directory = &config.package.src;
+  let dependencies = &config.dependencies;
+
   let mut output = PreprocessorOutput {
-    dependencies_files_content: HashMap::new(),
-    source_files_content: HashMap::new()
+    dependencies_files_content: BTreeMap::new(),
+    source_files_content: BTreeMap::new(),
+    diagnostics: Vec::new(),
+    included_files: BTreeSet::new()
   };
 
   for (name, content) in get_wss_files_content_for_directory(&Path::new(source_directory))? {
@@ -33,117 +48,159 @@ pub fn preprocess(
 
   for (name, value) in dependencies.iter() {
     if value.starts_with("https://") {
-      todo!();
+      return Err(CompileError::UnsupportedDependency {
+        name: name.to_string(),
+        source: value.to_string()
+      });
     } else {
       output.dependencies_files_content.insert(
         name.to_string(),
-        HashMap::from_iter(get_wss_files_content_for_directory(&Path::new(value))?.into_iter())
+        BTreeMap::from_iter(get_wss_files_content_for_directory(&Path::new(value))?.into_iter())
       );
     }
   }
 
-  let regex_collection = RegexCollection {
-    macro_call: Regex::new(r"(\w+)!").unwrap(),
-    macro_function: RegexBuilder::new(r"#define function (\w+)\((.*?)\) \{(.*)\};")
-      .multi_line(true)
-      .dot_matches_new_line(true)
-      .build()
-      .unwrap(),
-    macro_const: Regex::new(r"#define const (\w+).*;").unwrap(),
-    macro_const_value: Regex::new(r"= (.*);").unwrap(),
-    macro_ifdef: RegexBuilder::new(r"#ifdef (\w*) \{(.*?)\};")
-      .multi_line(true)
-      .dot_matches_new_line(true)
-      .build()
-      .unwrap(),
-    macro_ifndef: RegexBuilder::new(r"#ifndef (\w*) \{(.*?)\};")
-      .multi_line(true)
-      .dot_matches_new_line(true)
-      .build()
-      .unwrap()
+  let options = ExpansionOptions {
+    max_depth: config
+      .package
+      .max_macro_depth
+      .unwrap_or(DEFAULT_MAX_MACRO_DEPTH),
+    builtins: BuiltinValues::new(config)
   };
 
+  output.included_files = include::handle_includes(&output, config)?;
   registry::handle_registers(&mut output);
 
-  let mut registered_macros = HashMap::new();
-  let mut contains_macro_call = true;
-  while contains_macro_call {
-    contains_macro_call = false;
+  let mut registry = MacroRegistry::new(&config.get_macro_constants());
+  let mut diagnostics = Vec::new();
+
+  // the macros can be defined in a file and used in another one, so the files
+  // are processed until none of them changes anymore.
+  let mut settle_conditions = false;
+  loop {
+    let mut changed = false;
 
-    for (_name, files) in output.dependencies_files_content.iter() {
-      for (_filename, content) in files.iter() {
-        let mut new_content = content.content.borrow().to_string();
+    for (origin, file) in get_files(&output) {
+      let mut new_content = file.content.borrow().to_string();
+      let mut file_diagnostics = Vec::new();
+
+      let result = expand_macros::expand_macros(
+        &mut registry,
+        origin,
+        file,
+        &mut new_content,
+        &options,
+        &mut settle_conditions,
+        &mut file_diagnostics
+      );
+      let file_changed = result.map_err(|error| get_preprocessor_error(file, error))?;
 
-        let file_still_contains_macro_calls =
-          expand_macros::expand_macros(&mut registered_macros, &mut new_content, &regex_collection);
+      changed = changed || file_changed;
+      diagnostics.extend(
+        file_diagnostics
+          .into_iter()
+          .map(|diagnostic| (origin.name.to_string(), diagnostic))
+      );
 
-        contains_macro_call = contains_macro_call || file_still_contains_macro_calls;
+      file.content.replace(new_content);
+    }
 
-        content.content.replace(new_content);
+    if !changed {
+      // every macro the files can define is defined, the conditions that read
+      // an unknown macro are evaluated one by one with the macro undefined.
+      if settle_conditions {
+        break;
       }
+
+      settle_conditions = true;
     }
+  }
 
-    for (_filename, content) in output.source_files_content.iter() {
-      let mut new_content = content.content.borrow().to_string();
+  // a final pass over the files to remove the `#undef` directives and the
+  // pragmas
+  for (origin, file) in get_files(&output) {
+    let mut new_content = file.content.borrow().to_string();
 
-      let file_still_contains_macro_calls =
-        expand_macros::expand_macros(&mut registered_macros, &mut new_content, &regex_collection);
+    let result =
+      expand_macros::remove_undef_directives(&mut new_content, &mut file.offset_map.borrow_mut());
+    result.map_err(|error| get_preprocessor_error(file, error))?;
 
-      contains_macro_call = contains_macro_call || file_still_contains_macro_calls;
+    let mut offset_map = file.offset_map.borrow_mut();
 
-      content.content.replace(new_content);
+    diagnostics.extend(
+      expand_macros::get_unknown_macro_warnings(&new_content, &offset_map)
+        .into_iter()
+        .map(|diagnostic| (origin.name.to_string(), diagnostic))
+    );
+
+    if let Some(report) = get_print_pragma_report(&new_content, &offset_map) {
+      diagnostics.push((origin.name.to_string(), report));
     }
+
+    strip_pragmas(&mut new_content, &mut offset_map);
+
+    file.content.replace(new_content);
   }
 
-  // a final pass over the files to remove the conditional macros
-  for (_name, files) in output.dependencies_files_content.iter() {
-    for (_filename, content) in files.iter() {
-      let mut new_content = content.content.borrow().to_string();
+  output.diagnostics.extend(diagnostics);
 
-      filter_conditionals(
-        &registered_macros,
-        &mut new_content,
-        &regex_collection,
-        conditionals::ConditionType::IfDefined
-      );
+  Ok(output)
+}
 
-      filter_conditionals(
-        &registered_macros,
-        &mut new_content,
-        &regex_collection,
-        conditionals::ConditionType::IfNotDefined
-      );
+/// Returns the files of the dependencies followed by the files of the sources,
+/// along with their names and the package they belong to.
+fn get_files(
+  output: &PreprocessorOutput
+) -> impl Iterator<Item = (FileOrigin<'_>, &'_ ProcessedFile)> {
+  let dependencies_files =
+    output
+      .dependencies_files_content
+      .iter()
+      .flat_map(|(dependency, files)| {
+        files.iter().map(move |(name, file)| {
+          let origin = FileOrigin {
+            name,
+            package: Some(dependency)
+          };
 
-      content.content.replace(new_content);
-    }
-  }
+          (origin, file)
+        })
+      });
 
-  for (_filename, content) in output.source_files_content.iter() {
-    let mut new_content = content.content.borrow().to_string();
+  let source_files = output.source_files_content.iter().map(|(name, file)| {
+    let origin = FileOrigin {
+      name,
+      package: None
+    };
 
-    filter_conditionals(
-      &registered_macros,
-      &mut new_content,
-      &regex_collection,
-      conditionals::ConditionType::IfDefined
-    );
+    (origin, file)
+  });
 
-    filter_conditionals(
-      &registered_macros,
-      &mut new_content,
-      &regex_collection,
-      conditionals::ConditionType::IfNotDefined
-    );
+  dependencies_files.chain(source_files)
+}
 
-    content.content.replace(new_content);
-  }
+/// Builds the error of a malformed directive or macro call, located in the
+/// file the user wrote, or in the included file the code comes from.
+fn get_preprocessor_error(file: &ProcessedFile, error: PreprocessorError) -> CompileError {
+  let location = file
+    .offset_map
+    .borrow()
+    .get_original_range(error.offset..error.offset);
+  let path = file.path.to_string_lossy();
+  let (path, content) = location.get_source(&path, &file.original_content);
+  let (line, column) = LineIndex::new(content).get_line_and_column(content, location.range.start);
 
-  Ok(output)
+  CompileError::Preprocessor {
+    path: PathBuf::from(path),
+    line,
+    column,
+    message: error.message
+  }
 }
 
-fn get_wss_files_content_for_directory(
+pub fn get_wss_files_content_for_directory(
   dir: &Path
-) -> std::io::Result<Vec<(FileName, ProcessedFile)>> {
+) -> Result<Vec<(FileName, ProcessedFile)>, CompileError> {
   let files = walkdir::WalkDir::new(&dir)
     .into_iter()
     .filter(Result::is_ok)
@@ -158,18 +215,154 @@ fn get_wss_files_content_for_directory(
 
   let mut output = Vec::new();
   for filename in files {
-    let content = RefCell::new(convert_line_endings(std::fs::read_to_string(
-      filename.path()
-    )?));
-
+    let content = std::fs::read_to_string(filename.path()).map_err(|error| CompileError::Io {
+      path: filename.path().to_path_buf(),
+      error
+    })?;
     output.push((
       filename.path().to_str().unwrap().to_string(),
-      ProcessedFile {
-        content,
-        path: filename.path().to_path_buf()
-      }
+      ProcessedFile::new(convert_line_endings(content), filename.path().to_path_buf())
     ));
   }
 
   Ok(output)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A source directory in the system's temporary directory, removed once the
+  /// test ends.
+  struct TestSources(std::path::PathBuf);
+
+  impl TestSources {
+    fn new(name: &str, files: &[(&str, &str)]) -> Self {
+      let path =
+        std::env::temp_dir().join(format!("cahirc-preprocess-{name}-{}", std::process::id()));
+      std::fs::create_dir_all(&path).unwrap();
+
+      for (file, content) in files {
+        std::fs::write(path.join(file), content).unwrap();
+      }
+
+      Self(path)
+    }
+
+    /// Preprocesses the sources with the `[defines]` of a config
+    fn preprocess(&self, defines: &str) -> PreprocessorOutput {
+      let source_directory = self.0.to_string_lossy();
+      let config = format!(
+        r#"
+          [package]
+          name = "test"
+          src = {source_directory:?}
+          dist = "dist"
+
+          [dependencies]
+
+          [defines]
+          {defines}
+        "#
+      );
+
+      preprocess(&toml::from_str(&config).unwrap()).unwrap()
+    }
+
+    /// Returns the preprocessed content of the file, without its whitespace
+    fn get_content(&self, output: &PreprocessorOutput, file: &str) -> String {
+      let name = self.0.join(file).to_string_lossy().to_string();
+      let content = output.source_files_content[&name].content.borrow();
+
+      content.split_whitespace().collect()
+    }
+  }
+
+  impl Drop for TestSources {
+    fn drop(&mut self) {
+      let _ = std::fs::remove_dir_all(&self.0);
+    }
+  }
+
+  #[test]
+  fn registers_only_the_definitions_of_the_kept_branch() {
+    let sources = TestSources::new(
+      "branches",
+      &[(
+        "main.wss",
+        "#if DEBUG { #define const LEVEL = 3; } #else { #define const LEVEL = 0; };\nLEVEL!;"
+      )]
+    );
+
+    let output = sources.preprocess("DEBUG = true");
+    assert_eq!(sources.get_content(&output, "main.wss"), "3;");
+    assert!(output.diagnostics.is_empty());
+
+    let output = sources.preprocess("");
+    assert_eq!(sources.get_content(&output, "main.wss"), "0;");
+    assert!(output.diagnostics.is_empty());
+  }
+
+  #[test]
+  fn evaluates_the_conditions_in_the_order_they_are_written() {
+    let sources = TestSources::new(
+      "order",
+      &[(
+        "main.wss",
+        "#ifdef LATER { a; };\n#define const LATER;\n#ifdef LATER { b; };"
+      )]
+    );
+
+    let output = sources.preprocess("");
+    assert_eq!(sources.get_content(&output, "main.wss"), "b;");
+  }
+
+  #[test]
+  fn waits_for_the_macros_of_the_other_files() {
+    let sources = TestSources::new(
+      "files",
+      &[
+        ("a.wss", "#ifdef SHARED { a; } #else { b; };"),
+        ("b.wss", "#if true { #define const SHARED; };")
+      ]
+    );
+
+    let output = sources.preprocess("");
+    assert_eq!(sources.get_content(&output, "a.wss"), "a;");
+  }
+
+  #[test]
+  fn applies_the_undef_directives_to_the_conditions_that_follow() {
+    let sources = TestSources::new(
+      "undef",
+      &[(
+        "main.wss",
+        "#define const A;\n#ifdef A { a; };\n#undef A;\n#ifdef A { b; };\n#define const A = 2;\n#ifdef A { c; };"
+      )]
+    );
+
+    let output = sources.preprocess("");
+    assert_eq!(sources.get_content(&output, "main.wss"), "a;c;");
+  }
+
+  #[test]
+  fn ignores_the_undef_directives_of_the_branches_not_kept() {
+    let sources = TestSources::new(
+      "undef-branches",
+      &[(
+        "main.wss",
+        "#define const A = 1;\n#ifdef B { #undef A; #define const A = 2; } #else { #define const A = 3; };\nA!;"
+      )]
+    );
+
+    // the `#undef` of the branch that is kept allows the new value
+    let output = sources.preprocess("B = true");
+    assert_eq!(sources.get_content(&output, "main.wss"), "2;");
+    assert!(output.diagnostics.is_empty());
+
+    // the other branch redefines the macro without it
+    let output = sources.preprocess("");
+    assert_eq!(sources.get_content(&output, "main.wss"), "3;");
+    assert_eq!(output.diagnostics.len(), 1);
+  }
+}
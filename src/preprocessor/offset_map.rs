@@ -0,0 +1,428 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+/// Remembers where each segment of a preprocessed file comes from in the file
+/// the user wrote. Every edit the preprocessor does on the content of a file
+/// goes through `replace_range` so the map stays in sync with the content.
+#[derive(Debug, Clone)]
+pub struct OffsetMap {
+  segments: Vec<Segment>
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+  /// The offset of the segment in the preprocessed content
+  start: usize,
+  length: usize,
+
+  /// The offset in the original content the segment starts at
+  original_offset: usize,
+
+  /// The kind of code the segment holds, the text of a generated segment does
+  /// not exist in the original content, so all of its offsets point to the
+  /// code it was generated from.
+  kind: SegmentKind,
+
+  /// The included file the original offset points to, `None` when it points
+  /// to the file the map belongs to.
+  file: Option<Rc<IncludedFile>>
+}
+
+#[derive(Debug, Clone)]
+enum SegmentKind {
+  Original,
+  Generated {
+    /// The length of the code the segment replaced in the original content
+    original_length: usize,
+
+    /// The macros whose expansions produced the segment, from the outermost
+    /// one the user wrote to the one that generated the segment.
+    expansion_chain: Vec<String>
+  }
+}
+
+/// A range of the original content, along with the macro it was expanded
+/// from if the preprocessed range is the result of a macro expansion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginalRange {
+  pub range: Range<usize>,
+  pub expanded_from: Option<String>,
+
+  /// The included file the range is in, `None` when the range is in the file
+  /// the map belongs to.
+  pub file: Option<Rc<IncludedFile>>
+}
+
+impl OriginalRange {
+  /// Returns the path and the content of the file the range is in, out of the
+  /// ones of the file the map belongs to.
+  pub fn get_source<'a>(&'a self, path: &'a str, content: &'a str) -> (&'a str, &'a str) {
+    match &self.file {
+      Some(file) => (&file.path, &file.content),
+      None => (path, content)
+    }
+  }
+}
+
+/// A file an `#include` directive read, as the user wrote it
+#[derive(Debug, PartialEq, Eq)]
+pub struct IncludedFile {
+  pub path: String,
+  pub content: String
+}
+
+/// A piece of an included file that is copied in the content of the file
+/// that includes it.
+#[derive(Debug, Clone)]
+pub struct IncludedPart {
+  pub file: Rc<IncludedFile>,
+  pub range: Range<usize>
+}
+
+impl IncludedPart {
+  pub fn text(&self) -> &str {
+    &self.file.content[self.range.clone()]
+  }
+}
+
+impl OffsetMap {
+  /// Creates the map of a file that was not edited yet
+  pub fn new(content_length: usize) -> Self {
+    Self {
+      segments: vec![Segment {
+        start: 0,
+        length: content_length,
+        original_offset: 0,
+        kind: SegmentKind::Original,
+        file: None
+      }]
+    }
+  }
+
+  /// Replaces the `range` of the content with the `replacement`, and records
+  /// the replacement as generated code. `expanded_from` is the name of the macro
+  /// the replacement comes from, if any.
+  pub fn replace_range(
+    &mut self, content: &mut String, range: Range<usize>, replacement: &str,
+    expanded_from: Option<&str>
+  ) {
+    let original_range = self.get_original_range(range.clone());
+    let mut expansion_chain = self.get_expansion_chain(range.start);
+    expansion_chain.extend(expanded_from.map(str::to_string));
+
+    let generated = Segment {
+      start: range.start,
+      length: replacement.len(),
+      original_offset: original_range.range.start,
+      kind: SegmentKind::Generated {
+        original_length: original_range.range.len(),
+        expansion_chain
+      },
+      file: original_range.file
+    };
+
+    self.splice(content, range, replacement, vec![generated]);
+  }
+
+  /// Replaces the `range` of the content with the parts of the included files,
+  /// and records each part as original code of its file.
+  pub fn include_range(
+    &mut self, content: &mut String, range: Range<usize>, parts: &[IncludedPart]
+  ) {
+    let mut replacement = String::new();
+    let mut included = Vec::with_capacity(parts.len());
+
+    for part in parts {
+      included.push(Segment {
+        start: range.start + replacement.len(),
+        length: part.range.len(),
+        original_offset: part.range.start,
+        kind: SegmentKind::Original,
+        file: Some(part.file.clone())
+      });
+
+      replacement.push_str(part.text());
+    }
+
+    self.splice(content, range, &replacement, included);
+  }
+
+  /// Replaces the `range` of the content with the `replacement`, whose
+  /// segments are `inserted`.
+  fn splice(
+    &mut self, content: &mut String, range: Range<usize>, replacement: &str, inserted: Vec<Segment>
+  ) {
+    let mut segments = Vec::with_capacity(self.segments.len() + inserted.len() + 1);
+
+    for segment in &self.segments {
+      let end = segment.start + segment.length;
+
+      if segment.start < range.start {
+        segments.push(segment.slice(segment.start, end.min(range.start)));
+      }
+    }
+
+    segments.extend(inserted.into_iter().filter(|segment| segment.length > 0));
+
+    for segment in &self.segments {
+      let end = segment.start + segment.length;
+
+      if end > range.end {
+        let mut segment = segment.slice(segment.start.max(range.end), end);
+        segment.start = segment.start - range.end + range.start + replacement.len();
+
+        segments.push(segment);
+      }
+    }
+
+    self.segments = segments;
+    content.replace_range(range, replacement);
+  }
+
+  /// Removes the `range` from the content
+  pub fn remove_range(&mut self, content: &mut String, range: Range<usize>) {
+    self.replace_range(content, range, "", None);
+  }
+
+  /// Returns the range of the original content the given range of the
+  /// preprocessed content comes from.
+  pub fn get_original_range(&self, range: Range<usize>) -> OriginalRange {
+    let Some(start_segment) = self.find_segment(range.start) else {
+      return OriginalRange {
+        range,
+        expanded_from: None,
+        file: None
+      };
+    };
+
+    let start = start_segment.get_original_offset(range.start);
+
+    // the end is exclusive, so it is the offset after the last character of
+    // the range that is looked up. A range that ends in another file than the
+    // one it starts in is cut at the end of its first segment.
+    let end = match (range.end > range.start, &start_segment.kind) {
+      (true, _) => match self.find_segment(range.end - 1) {
+        Some(segment) if segment.is_in_same_file(start_segment) => {
+          segment.get_original_end(range.end - 1)
+        }
+        _ => start_segment
+          .get_original_end((start_segment.start + start_segment.length).saturating_sub(1))
+      },
+      (false, SegmentKind::Original) => start,
+      (false, SegmentKind::Generated { .. }) => start_segment.get_original_end(range.start)
+    };
+
+    // a macro call that was itself expanded from another macro reports the
+    // outermost macro, it is the one the user wrote.
+    let expanded_from = match &start_segment.kind {
+      SegmentKind::Original => None,
+      SegmentKind::Generated {
+        expansion_chain, ..
+      } => expansion_chain.first().cloned()
+    };
+
+    OriginalRange {
+      range: start..end.max(start),
+      expanded_from,
+      file: start_segment.file.clone()
+    }
+  }
+
+  /// Returns the macros whose expansions produced the code at the offset, the
+  /// outermost one first. The chain is empty for the code the user wrote.
+  pub fn get_expansion_chain(&self, offset: usize) -> Vec<String> {
+    match self.find_segment(offset).map(|segment| &segment.kind) {
+      Some(SegmentKind::Generated {
+        expansion_chain, ..
+      }) => expansion_chain.clone(),
+      _ => Vec::new()
+    }
+  }
+
+  /// Returns the offset in the preprocessed content of the given offset of the
+  /// original content, if the code at this offset was not replaced.
+  pub fn get_processed_offset(&self, original_offset: usize) -> Option<usize> {
+    self
+      .segments
+      .iter()
+      .filter(|segment| matches!(segment.kind, SegmentKind::Original) && segment.file.is_none())
+      .find(|segment| {
+        original_offset >= segment.original_offset
+          && original_offset <= segment.original_offset + segment.length
+      })
+      .map(|segment| segment.start + original_offset - segment.original_offset)
+  }
+
+  /// Returns the segment that contains the offset, the offset right after the
+  /// last segment belongs to it.
+  fn find_segment(&self, offset: usize) -> Option<&Segment> {
+    let index = self
+      .segments
+      .partition_point(|segment| segment.start + segment.length <= offset);
+
+    self.segments.get(index).or(self.segments.last())
+  }
+}
+
+impl Segment {
+  /// Returns the part of the segment between the given offsets of the
+  /// preprocessed content.
+  fn slice(&self, start: usize, end: usize) -> Self {
+    let original_offset = match self.kind {
+      SegmentKind::Original => self.original_offset + start - self.start,
+      SegmentKind::Generated { .. } => self.original_offset
+    };
+
+    Self {
+      start,
+      length: end - start,
+      original_offset,
+      kind: self.kind.clone(),
+      file: self.file.clone()
+    }
+  }
+
+  fn is_in_same_file(&self, other: &Segment) -> bool {
+    match (&self.file, &other.file) {
+      (Some(file), Some(other_file)) => Rc::ptr_eq(file, other_file),
+      (file, other_file) => file.is_none() && other_file.is_none()
+    }
+  }
+
+  fn get_original_offset(&self, offset: usize) -> usize {
+    match self.kind {
+      SegmentKind::Original => self.original_offset + offset.saturating_sub(self.start),
+      SegmentKind::Generated { .. } => self.original_offset
+    }
+  }
+
+  /// Returns the offset right after the character at `offset`, in the
+  /// original content.
+  fn get_original_end(&self, offset: usize) -> usize {
+    match self.kind {
+      SegmentKind::Original => self.get_original_offset(offset) + 1,
+      SegmentKind::Generated {
+        original_length, ..
+      } => self.original_offset + original_length
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::rc::Rc;
+
+  use super::{IncludedFile, IncludedPart, OffsetMap, OriginalRange};
+
+  fn original(range: std::ops::Range<usize>) -> OriginalRange {
+    OriginalRange {
+      range,
+      expanded_from: None,
+      file: None
+    }
+  }
+
+  fn expanded(range: std::ops::Range<usize>, name: &str) -> OriginalRange {
+    OriginalRange {
+      range,
+      expanded_from: Some(name.to_string()),
+      file: None
+    }
+  }
+
+  #[test]
+  fn keeps_the_offsets_of_an_unedited_file() {
+    let map = OffsetMap::new(10);
+
+    assert_eq!(map.get_original_range(2..5), original(2..5));
+    assert_eq!(map.get_original_range(10..10), original(10..10));
+    assert_eq!(map.get_processed_offset(4), Some(4));
+  }
+
+  #[test]
+  fn maps_the_code_around_a_replacement() {
+    let mut content = String::from("a FOO! b");
+    let mut map = OffsetMap::new(content.len());
+
+    map.replace_range(&mut content, 2..6, "hello world", Some("FOO"));
+
+    assert_eq!(content, "a hello world b");
+    assert_eq!(map.get_original_range(0..1), original(0..1));
+    assert_eq!(map.get_original_range(14..15), original(7..8));
+    assert_eq!(map.get_original_range(0..15), original(0..8));
+    assert_eq!(map.get_processed_offset(7), Some(14));
+    assert_eq!(map.get_processed_offset(3), None);
+  }
+
+  #[test]
+  fn maps_generated_code_to_the_macro_call() {
+    let mut content = String::from("a FOO! b");
+    let mut map = OffsetMap::new(content.len());
+
+    map.replace_range(&mut content, 2..6, "hello world", Some("FOO"));
+
+    assert_eq!(map.get_original_range(4..9), expanded(2..6, "FOO"));
+    assert_eq!(map.get_original_range(5..5), expanded(2..6, "FOO"));
+    assert_eq!(map.get_expansion_chain(4), vec!["FOO"]);
+    assert!(map.get_expansion_chain(0).is_empty());
+  }
+
+  #[test]
+  fn reports_the_outermost_macro_of_nested_expansions() {
+    let mut content = String::from("X!;");
+    let mut map = OffsetMap::new(content.len());
+
+    map.replace_range(&mut content, 0..2, "Y! + 1", Some("X"));
+    map.replace_range(&mut content, 0..2, "42", Some("Y"));
+
+    assert_eq!(content, "42 + 1;");
+    assert_eq!(map.get_expansion_chain(0), vec!["X", "Y"]);
+    assert_eq!(map.get_original_range(0..2), expanded(0..2, "X"));
+    assert_eq!(map.get_original_range(3..6), expanded(0..2, "X"));
+    assert_eq!(map.get_original_range(6..7), original(2..3));
+  }
+
+  #[test]
+  fn shifts_the_code_after_a_removal() {
+    let mut content = String::from("#pragma once\ncode");
+    let mut map = OffsetMap::new(content.len());
+
+    map.remove_range(&mut content, 0..13);
+
+    assert_eq!(content, "code");
+    assert_eq!(map.get_original_range(0..4), original(13..17));
+    assert_eq!(map.get_processed_offset(13), Some(0));
+    assert_eq!(map.get_processed_offset(5), None);
+  }
+
+  #[test]
+  fn maps_the_included_code_to_the_included_file() {
+    let mut content = String::from("a;\n#include \"b.wssh\";\nc;");
+    let mut map = OffsetMap::new(content.len());
+    let file = Rc::new(IncludedFile {
+      path: String::from("b.wssh"),
+      content: String::from("#pragma once\nb;")
+    });
+    let part = IncludedPart {
+      file: file.clone(),
+      range: 13..15
+    };
+
+    map.include_range(&mut content, 3..21, &[part]);
+
+    assert_eq!(content, "a;\nb;\nc;");
+    assert_eq!(map.get_original_range(0..2), original(0..2));
+    assert_eq!(map.get_original_range(6..8), original(22..24));
+    assert_eq!(
+      map.get_original_range(3..5),
+      OriginalRange {
+        range: 13..15,
+        expanded_from: None,
+        file: Some(file.clone())
+      }
+    );
+    assert_eq!(map.get_original_range(3..8).range, 13..15);
+    assert_eq!(map.get_processed_offset(13), None);
+    assert_eq!(map.get_processed_offset(22), Some(6));
+  }
+}
@@ -1,145 +1,269 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 
+use ariadne::ReportKind;
 pub use nom::bytes::complete::{tag, take_until1};
 pub use nom::character::complete::char;
+use nom::character::complete::i32 as integer;
+use nom::combinator::opt;
 pub use nom::sequence::delimited;
+use nom::sequence::terminated;
 pub use nom::IResult;
 use nom::Offset;
 
-use super::PreprocessorOutput;
-
+use super::directives::find_code_block;
+use super::lexer::{tokenize, TokenKind};
+use super::offset_map::OriginalRange;
+use super::types::{FileName, ProcessedFile};
+use super::{get_files, PreprocessorOutput};
+use crate::ast::{Diagnostic, DiagnosticLabel};
+
+/// Moves the code of the `@register` calls into the `@registry` calls with the
+/// same name, whatever the package of the files they are in. So a library can
+/// declare a registry the project fills.
+///
+/// The values of a registry are sorted by priority, the highest first, and
+/// then by file in the order the files are processed: the dependencies sorted
+/// by name and then the sources.
 pub fn handle_registers(output: &mut PreprocessorOutput) {
-  let registers = collect_registers(output);
-  emit_registers(output, &registers);
+  let mut diagnostics = Vec::new();
+  let registers = collect_registers(output, &mut diagnostics);
+  emit_registers(output, &registers, &mut diagnostics);
+
+  output.diagnostics.extend(diagnostics);
 }
 
-fn collect_registers(output: &mut PreprocessorOutput) -> HashMap<String, Vec<String>> {
-  use nom::Offset;
+/// The code of a `@register` call and where it comes from
+struct RegisterValue {
+  priority: i32,
+  body: String,
+  file: FileName,
 
-  let mut registers = HashMap::new();
+  /// The range of the call in the file the user wrote
+  range: OriginalRange
+}
 
-  'fileloop: for (_filename, file) in &output.source_files_content {
+fn collect_registers(
+  output: &PreprocessorOutput, diagnostics: &mut Vec<(FileName, Diagnostic)>
+) -> BTreeMap<String, Vec<RegisterValue>> {
+  let mut registers: BTreeMap<String, Vec<RegisterValue>> = BTreeMap::new();
+
+  'fileloop: for (origin, file) in get_files(output) {
     loop {
       let content = file.content.borrow();
       let content_ref = content.as_str();
 
-      let Ok((start, _)) = Register::parse_find_register(content_ref) else {
+      let Some(start_idx) = Register::find_call(content_ref) else {
         // no @register was found
         continue 'fileloop;
       };
 
-      let start_idx = content_ref.offset(start);
-
-      match Register::parse(start) {
+      match Register::parse(&content_ref[start_idx..]) {
         Ok((new_i, register)) => {
+          let end_idx = content_ref.offset(new_i);
+
           registers
             .entry(register.name.to_owned())
-            .or_insert(Vec::new())
-            .push(register.body.to_owned());
+            .or_default()
+            .push(RegisterValue {
+              priority: register.priority,
+              body: register.body.to_owned(),
+              file: origin.name.to_string(),
+              range: file
+                .offset_map
+                .borrow()
+                .get_original_range(start_idx..end_idx)
+            });
 
-          let end_idx = content_ref.offset(new_i);
           std::mem::drop(content);
 
           // remove the register code from the file
           file
-            .content
+            .offset_map
             .borrow_mut()
-            .replace_range(start_idx..end_idx, "");
+            .remove_range(&mut file.content.borrow_mut(), start_idx..end_idx);
         }
-        Err(e) => {
-          println!("ERROR parsing @register: {e}");
+        Err(_) => {
+          diagnostics.push((
+            origin.name.to_string(),
+            Register::get_malformed_call_error(file, start_idx)
+          ));
+
           continue 'fileloop;
         }
       };
     }
   }
 
+  // a stable sort, the values with the same priority stay in the file order
+  for values in registers.values_mut() {
+    values.sort_by_key(|value| -i64::from(value.priority));
+  }
+
   registers
 }
 
-fn emit_registers(output: &mut PreprocessorOutput, registers: &HashMap<String, Vec<String>>) {
-  // memorize which register was used to emit a log about the unused ones
-  let mut used_registers: HashSet<String> = HashSet::new();
+/// Emits the registries and reports the registers that are never emitted and
+/// the registries that have no value.
+fn emit_registers(
+  output: &PreprocessorOutput, registers: &BTreeMap<String, Vec<RegisterValue>>,
+  diagnostics: &mut Vec<(FileName, Diagnostic)>
+) {
+  // memorize which register was used to emit a warning about the unused ones
+  let mut used_registers: BTreeSet<String> = BTreeSet::new();
 
-  for (_filename, file) in &output.source_files_content {
+  for (origin, file) in get_files(output) {
     'parsing_loop: loop {
       let content = file.content.borrow();
       let content_ref = content.as_str();
 
-      let Ok((start, _)) = RegisterEmitter::parse_find_register(content_ref) else {
+      let Some(start_idx) = RegisterEmitter::find_call(content_ref) else {
         // no @registry was found
         break 'parsing_loop;
       };
 
-      let start_idx = content_ref.offset(start);
-
-      match RegisterEmitter::parse(start) {
+      match RegisterEmitter::parse(&content_ref[start_idx..]) {
         Ok((new_i, register_emitter)) => {
           let end_idx = content_ref.offset(new_i);
 
+          if !registers.contains_key(register_emitter.name) {
+            let range = file
+              .offset_map
+              .borrow()
+              .get_original_range(start_idx..end_idx);
+
+            diagnostics.push((
+              origin.name.to_string(),
+              get_empty_registry_warning(register_emitter.name, range)
+            ));
+          }
+
           // replace the registerEmitter's code with the emitted code
           let output = register_emitter.emit(registers, &mut used_registers);
 
           std::mem::drop(content);
-          file
-            .content
-            .borrow_mut()
-            .replace_range(start_idx..end_idx, &output);
+          file.offset_map.borrow_mut().replace_range(
+            &mut file.content.borrow_mut(),
+            start_idx..end_idx,
+            &output,
+            None
+          );
         }
-        Err(e) => {
-          println!("ERROR parsing @register: {e}");
+        Err(_) => {
+          diagnostics.push((
+            origin.name.to_string(),
+            RegisterEmitter::get_malformed_call_error(file, start_idx)
+          ));
+
           break 'parsing_loop;
         }
       };
     }
   }
 
-  for register in registers.keys() {
+  for (register, values) in registers {
     if !used_registers.contains(register) {
-      println!("register [{register}] defined but unused, no matching @registry found.");
-
-      if let Some(values) = registers.get(register) {
-        println!("  values:");
-
-        for value in values {
-          println!("  - {value}");
-        }
-      }
+      diagnostics.extend(values.iter().map(|value| {
+        (
+          value.file.clone(),
+          get_unused_register_warning(register, value)
+        )
+      }));
     }
   }
 }
 
+fn get_unused_register_warning(name: &str, value: &RegisterValue) -> Diagnostic {
+  Diagnostic::build(ReportKind::Warning, value.range.range.start)
+    .with_message(format!("register [{name}] is defined but never used"))
+    .with_label(
+      DiagnosticLabel::new(value.range.range.clone())
+        .with_message(format!("no @registry('{name}', ...) emits this value"))
+    )
+    .with_included_file(value.range.file.clone())
+}
+
+fn get_empty_registry_warning(name: &str, range: OriginalRange) -> Diagnostic {
+  Diagnostic::build(ReportKind::Warning, range.range.start)
+    .with_message(format!("registry [{name}] has no value"))
+    .with_label(DiagnosticLabel::new(range.range).with_message("this registry emits nothing"))
+    .with_help(format!("add values with @register('{name}', ...)"))
+    .with_included_file(range.file)
+}
+
 trait RegisterParser<'a>
 where
   Self: Sized
 {
-  fn prefix() -> &'static str;
-  fn from_name_and_body(name: &'a str, body: &'a str) -> Self;
+  /// The name of the call, without its `@`
+  fn keyword() -> &'static str;
+  fn from_name_and_body(name: &'a str, priority: i32, body: &'a str) -> Self;
 
   fn parse(start: &'a str) -> IResult<&'a str, Self> {
-    let (i, _) = tag(Self::prefix())(start)?;
+    let (i, _) = tag("@")(start)?;
+    let (i, _) = tag(Self::keyword())(i)?;
     let (i, _) = char('(')(i)?;
     let (i, name) = delimited(tag("'"), take_until1("'"), tag("'"))(i.trim_start())?;
     let (i, _) = char(',')(i.trim_start())?;
+    let (i, priority) = Self::parse_priority(i.trim_start())?;
     let (i, body) = Self::parse_body(i.trim_start())?;
 
-    Ok((i, Self::from_name_and_body(name, body)))
+    Ok((i, Self::from_name_and_body(name, priority, body)))
   }
 
-  fn parse_find_register(i: &'a str) -> IResult<&'a str, ()> {
-    let (i, _) = take_until1(Self::prefix())(i)?;
+  /// Parses the optional `priority,` that follows the name, only the registers
+  /// have one.
+  fn parse_priority(i: &'a str) -> IResult<&'a str, i32> {
+    Ok((i, 0))
+  }
+
+  /// Returns the offset of the first call of the content, the calls in the
+  /// comments and the literals are ignored.
+  fn find_call(content: &str) -> Option<usize> {
+    let tokens = tokenize(content);
+
+    tokens.windows(2).find_map(|window| {
+      let [at, keyword] = window else {
+        return None;
+      };
+
+      let is_call = &content[at.range.clone()] == "@"
+        && keyword.kind == TokenKind::Identifier
+        && &content[keyword.range.clone()] == Self::keyword();
 
-    Ok((i, ()))
+      is_call.then_some(at.range.start)
+    })
+  }
+
+  /// Builds the error of a call that cannot be parsed, the call is left in the
+  /// file.
+  fn get_malformed_call_error(file: &ProcessedFile, start: usize) -> Diagnostic {
+    let keyword = Self::keyword();
+    let range = file
+      .offset_map
+      .borrow()
+      .get_original_range(start..start + keyword.len() + 1);
+
+    Diagnostic::build(ReportKind::Error, range.range.start)
+      .with_message(format!("malformed @{keyword} call"))
+      .with_label(DiagnosticLabel::new(range.range).with_message(format!(
+        "expected @{keyword}('name', code) or @{keyword}('name', {{{{ multiline code }}}})"
+      )))
+      .with_included_file(range.file)
   }
 
   fn parse_body(i: &'a str) -> IResult<&'a str, &'a str> {
     if i.starts_with("{{") {
-      let (i, _) = tag("{{")(i)?;
-      let (i, body) = take_until1("}}")(i)?;
-      let (i, _) = tag("}}")(i)?;
-      let (i, _) = tag(")")(i.trim())?;
+      // the braces of the code block are balanced, so a `}}` in a comment or
+      // in a literal doesn't end it
+      let Some((body, body_end)) = find_code_block(i) else {
+        return Err(nom::Err::Error(nom::error::Error::new(
+          i,
+          nom::error::ErrorKind::TakeUntil
+        )));
+      };
+      let (rest, _) = tag(")")(i[body_end..].trim_start())?;
 
-      Ok((i, body))
+      Ok((rest, &i[body]))
     } else {
       let (i, body) = take_until1(")")(i)?;
       let (i, _) = tag(")")(i)?;
@@ -151,16 +275,27 @@ where
 
 struct Register<'a> {
   pub name: &'a str,
+  pub priority: i32,
   pub body: &'a str
 }
 
 impl<'a> RegisterParser<'a> for Register<'a> {
-  fn prefix() -> &'static str {
-    "@register"
+  fn keyword() -> &'static str {
+    "register"
   }
 
-  fn from_name_and_body(name: &'a str, body: &'a str) -> Self {
-    Self { name, body }
+  fn from_name_and_body(name: &'a str, priority: i32, body: &'a str) -> Self {
+    Self {
+      name,
+      priority,
+      body
+    }
+  }
+
+  fn parse_priority(i: &'a str) -> IResult<&'a str, i32> {
+    let (i, priority) = opt(terminated(integer, |i: &'a str| char(',')(i.trim_start())))(i)?;
+
+    Ok((i, priority.unwrap_or(0)))
   }
 }
 
@@ -170,18 +305,18 @@ struct RegisterEmitter<'a> {
 }
 
 impl<'a> RegisterParser<'a> for RegisterEmitter<'a> {
-  fn prefix() -> &'static str {
-    "@registry"
+  fn keyword() -> &'static str {
+    "registry"
   }
 
-  fn from_name_and_body(name: &'a str, body: &'a str) -> Self {
+  fn from_name_and_body(name: &'a str, _priority: i32, body: &'a str) -> Self {
     Self { name, body }
   }
 }
 
 impl<'a> RegisterEmitter<'a> {
   pub fn emit(
-    &self, registers: &HashMap<String, Vec<String>>, used_register: &mut HashSet<String>
+    &self, registers: &BTreeMap<String, Vec<RegisterValue>>, used_register: &mut BTreeSet<String>
   ) -> String {
     let Some(values) = registers.get(self.name) else {
       return String::new();
@@ -190,7 +325,7 @@ impl<'a> RegisterEmitter<'a> {
     let mut output = String::new();
 
     for value in values {
-      output.push_str(&self.body.replace("REGISTER", value));
+      output.push_str(&self.body.replace("REGISTER", &value.body));
     }
 
     used_register.insert(self.name.to_owned());
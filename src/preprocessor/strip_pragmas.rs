@@ -0,0 +1,57 @@
+use ariadne::ReportKind;
+
+use super::offset_map::OffsetMap;
+use crate::ast::{Diagnostic, DiagnosticLabel};
+
+const PRINT_PRAGMA: &str = "#pragma cahirc-preprocessor-print";
+
+/// Empties the lines that hold a pragma, the lines themselves are kept so the
+/// rest of the content does not move.
+pub fn strip_pragmas(content: &mut String, offset_map: &mut OffsetMap) {
+  let mut pragma_ranges = Vec::new();
+  let mut line_start = 0;
+
+  for line in content.split('\n') {
+    if contains_pragma(line) {
+      pragma_ranges.push(line_start..line_start + line.len());
+    }
+
+    line_start += line.len() + 1;
+  }
+
+  // from the last to the first so the ranges that remain are still valid
+  for range in pragma_ranges.into_iter().rev() {
+    offset_map.remove_range(content, range);
+  }
+}
+
+/// Returns a report holding the preprocessed content if the file asks for it
+/// with a `#pragma cahirc-preprocessor-print`, it must run before the pragmas
+/// are stripped.
+pub fn get_print_pragma_report(content: &str, offset_map: &OffsetMap) -> Option<Diagnostic> {
+  let mut line_start = 0;
+
+  for line in content.split('\n') {
+    if contains_pragma(line) && line.trim() == PRINT_PRAGMA {
+      let range = offset_map.get_original_range(line_start..line_start + line.len());
+
+      return Some(
+        Diagnostic::build(ReportKind::Advice, range.range.start)
+          .with_message("content of the file after the preprocessor pass")
+          .with_label(
+            DiagnosticLabel::new(range.range).with_message("printed because of this pragma")
+          )
+          .with_note(content)
+          .with_included_file(range.file)
+      );
+    }
+
+    line_start += line.len() + 1;
+  }
+
+  None
+}
+
+fn contains_pragma(line: &str) -> bool {
+  line.trim_start().starts_with("#pragma ")
+}
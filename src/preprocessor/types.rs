@@ -1,33 +1,87 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::PathBuf;
 
-use regex::Regex;
+use super::offset_map::OffsetMap;
+use crate::ast::Diagnostic;
 
 pub type FileName = String;
 pub type DependencyName = String;
 
 pub struct ProcessedFile {
   pub content: RefCell<String>,
+
+  /// The content of the file as the user wrote it
+  pub original_content: String,
+
+  /// Maps the offsets of `content` to the offsets of `original_content`
+  pub offset_map: RefCell<OffsetMap>,
+
   pub path: PathBuf
 }
 
+impl ProcessedFile {
+  pub fn new(content: String, path: PathBuf) -> Self {
+    Self {
+      offset_map: RefCell::new(OffsetMap::new(content.len())),
+      original_content: content.clone(),
+      content: RefCell::new(content),
+      path
+    }
+  }
+}
+
 pub struct PreprocessorOutput {
-  pub source_files_content: HashMap<FileName, ProcessedFile>,
+  pub source_files_content: BTreeMap<FileName, ProcessedFile>,
 
-  pub dependencies_files_content: HashMap<DependencyName, HashMap<FileName, ProcessedFile>>
+  pub dependencies_files_content: BTreeMap<DependencyName, BTreeMap<FileName, ProcessedFile>>,
+
+  /// The warnings of the preprocessor, their offsets already point to the
+  /// files the user wrote.
+  pub diagnostics: Vec<(FileName, Diagnostic)>,
+
+  /// The files the `#include` directives read
+  pub included_files: BTreeSet<PathBuf>
+}
+
+impl PreprocessorOutput {
+  /// Returns the file with the given name, from the sources or from the
+  /// dependencies.
+  pub fn get_file(&self, filename: &str) -> Option<&ProcessedFile> {
+    self.source_files_content.get(filename).or_else(|| {
+      self
+        .dependencies_files_content
+        .values()
+        .find_map(|files| files.get(filename))
+    })
+  }
 }
 
 #[derive(Debug)]
 pub struct MacroFunction {
   pub parameters: Vec<String>,
-  pub body: String
+
+  /// The name of the trailing `...rest` parameter that receives the extra
+  /// values of a call
+  pub variadic: Option<String>,
+
+  pub body: String,
+
+  /// How many times the macro was expanded, used to give unique names to the
+  /// variables of the hygienic macros.
+  pub expansions: Cell<usize>,
+
+  /// The macros the body calls, in order
+  pub calls: Vec<String>
 }
 
 #[derive(Debug)]
 pub struct MacroConstant {
   pub name: String,
-  pub value: String
+  pub value: String,
+
+  /// The macros the value calls, in order
+  pub calls: Vec<String>
 }
 
 #[derive(Debug)]
@@ -36,11 +90,36 @@ pub enum MacroDefinition {
   Constant(MacroConstant)
 }
 
-pub struct RegexCollection {
-  pub macro_const: Regex,
-  pub macro_const_value: Regex,
-  pub macro_function: Regex,
-  pub macro_call: Regex,
-  pub macro_ifdef: Regex,
-  pub macro_ifndef: Regex
+impl MacroDefinition {
+  /// Returns whether both definitions expand to the same code
+  pub fn is_same_as(&self, other: &MacroDefinition) -> bool {
+    match (self, other) {
+      (MacroDefinition::Function(a), MacroDefinition::Function(b)) => {
+        a.parameters == b.parameters && a.variadic == b.variadic && a.body == b.body
+      }
+      (MacroDefinition::Constant(a), MacroDefinition::Constant(b)) => a.value == b.value,
+      _ => false
+    }
+  }
+
+  pub fn calls(&self) -> &[String] {
+    match self {
+      MacroDefinition::Function(function) => &function.calls,
+      MacroDefinition::Constant(constant) => &constant.calls
+    }
+  }
+}
+
+/// A malformed directive or macro call, the offset points to the content of
+/// the file at the time the error was found.
+#[derive(Debug)]
+pub struct PreprocessorError {
+  pub message: String,
+  pub offset: usize
+}
+
+impl PreprocessorError {
+  pub fn new(message: String, offset: usize) -> Self {
+    Self { message, offset }
+  }
 }
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::Span;
+use crate::error::CompileError;
+use crate::utils::LineIndex;
+use crate::ProjectAnalysis;
+
+/// Maps the lines of an emitted `.ws` file to the `.wss` code they come from.
+/// It is written next to the emitted file, with the `.ws.map` extension.
+#[derive(Serialize, Deserialize)]
+pub struct SourceMap {
+  /// The number of lines of the emitted file
+  pub line_count: usize,
+
+  pub mappings: Vec<SourceMapping>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SourceMapping {
+  /// The line in the emitted file, starting at 1
+  pub line: usize,
+
+  pub source: String,
+  pub source_line: usize,
+  pub source_column: usize
+}
+
+impl SourceMap {
+  /// Builds the source map out of the spans found on the lines of the emitted
+  /// code, which has `line_count` lines.
+  pub fn new(spans: &[(usize, Span)], line_count: usize, analysis: &ProjectAnalysis) -> Self {
+    let mut line_indexes: HashMap<String, LineIndex> = HashMap::new();
+    let mut mappings = Vec::new();
+
+    for (line, span) in spans {
+      // the lines point to the file the user wrote, not the preprocessed one
+      let Some((file, original_range)) = analysis.get_original_range(*span) else {
+        continue;
+      };
+
+      let (source, content) = original_range.get_source(
+        analysis.span_manager.get_source(span),
+        &file.original_content
+      );
+
+      let line_index = line_indexes
+        .entry(source.to_string())
+        .or_insert_with(|| LineIndex::new(content));
+
+      let (source_line, source_column) =
+        line_index.get_line_and_column(content, original_range.range.start);
+
+      mappings.push(SourceMapping {
+        line: *line,
+        source: source.to_string(),
+        source_line,
+        source_column
+      });
+    }
+
+    Self {
+      line_count,
+      mappings
+    }
+  }
+
+  pub fn to_json(&self) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(self)
+  }
+
+  /// Returns the mapping of the given line, or of the closest mapped line
+  /// before it as some emitted lines do not come from any expression. The
+  /// lines outside of the emitted file have no mapping.
+  pub fn resolve(&self, line: usize) -> Option<&SourceMapping> {
+    if line == 0 || line > self.line_count {
+      return None;
+    }
+
+    self
+      .mappings
+      .iter()
+      .take_while(|mapping| mapping.line <= line)
+      .last()
+  }
+}
+
+/// Returns the path of the source map for the emitted file at `path`
+pub fn get_source_map_path(path: &Path) -> PathBuf {
+  let mut path = path.as_os_str().to_owned();
+  path.push(".map");
+
+  PathBuf::from(path)
+}
+
+/// Prints the `.wss` location a location in an emitted file comes from, the
+/// location is in the form of `dist/foo.ws:812`.
+pub fn print_source_location(location: &str) -> Result<(), CompileError> {
+  let invalid_location = || {
+    CompileError::Arguments(format!(
+      "invalid location {location}, expected a location like dist/foo.ws:812"
+    ))
+  };
+
+  let (path, line) = location.rsplit_once(':').ok_or_else(invalid_location)?;
+  let line: usize = line.parse().map_err(|_| invalid_location())?;
+
+  let source_map_path = get_source_map_path(Path::new(path));
+  let content = std::fs::read_to_string(&source_map_path).map_err(|error| CompileError::Io {
+    path: source_map_path.clone(),
+    error
+  })?;
+
+  let source_map: SourceMap = serde_json::from_str(&content).map_err(|error| CompileError::Io {
+    path: source_map_path.clone(),
+    error: error.into()
+  })?;
+
+  if line == 0 || line > source_map.line_count {
+    return Err(CompileError::Arguments(format!(
+      "line {line} is outside of {path}, which has {} lines",
+      source_map.line_count
+    )));
+  }
+
+  match source_map.resolve(line) {
+    Some(mapping) => {
+      println!(
+        "{}:{}:{}",
+        mapping.source, mapping.source_line, mapping.source_column
+      );
+
+      Ok(())
+    }
+    None => Err(CompileError::Arguments(format!(
+      "line {line} of {path} does not come from any source"
+    )))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn mapping(line: usize, source_line: usize) -> SourceMapping {
+    SourceMapping {
+      line,
+      source: String::from("main.wss"),
+      source_line,
+      source_column: 1
+    }
+  }
+
+  #[test]
+  fn resolves_the_lines_of_the_emitted_file_only() {
+    let source_map = SourceMap {
+      line_count: 5,
+      mappings: vec![mapping(2, 10), mapping(4, 20)]
+    };
+
+    let resolve = |line| source_map.resolve(line).map(|mapping| mapping.source_line);
+
+    assert_eq!(resolve(1), None);
+    assert_eq!(resolve(2), Some(10));
+    assert_eq!(resolve(3), Some(10));
+    assert_eq!(resolve(5), Some(20));
+    assert_eq!(resolve(6), None);
+    assert_eq!(resolve(0), None);
+  }
+}
@@ -0,0 +1,75 @@
+/// The offsets where each line of a text starts, to convert byte offsets into
+/// lines and columns without walking the whole text every time.
+pub struct LineIndex {
+  line_starts: Vec<usize>
+}
+
+impl LineIndex {
+  pub fn new(content: &str) -> Self {
+    let line_starts = std::iter::once(0)
+      .chain(content.match_indices('\n').map(|(index, _)| index + 1))
+      .collect();
+
+    Self { line_starts }
+  }
+
+  /// Returns the 1-based line and column of the byte offset, the column is
+  /// counted in characters.
+  pub fn get_line_and_column(&self, content: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(content.len());
+    let line = match self.line_starts.binary_search(&offset) {
+      Ok(line) => line,
+      Err(next_line) => next_line - 1
+    };
+
+    let line_start = self.line_starts[line];
+    let column = content
+      .get(line_start..offset)
+      .map(|text| text.chars().count())
+      .unwrap_or(offset - line_start);
+
+    (line + 1, column + 1)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::LineIndex;
+
+  #[test]
+  fn finds_the_line_and_column_of_offsets() {
+    let content = "first\nsecond\n\nlast";
+    let index = LineIndex::new(content);
+
+    assert_eq!(index.get_line_and_column(content, 0), (1, 1));
+    assert_eq!(index.get_line_and_column(content, 4), (1, 5));
+    assert_eq!(index.get_line_and_column(content, 5), (1, 6));
+    assert_eq!(index.get_line_and_column(content, 6), (2, 1));
+    assert_eq!(index.get_line_and_column(content, 13), (3, 1));
+    assert_eq!(index.get_line_and_column(content, 14), (4, 1));
+  }
+
+  #[test]
+  fn counts_the_columns_in_characters() {
+    let content = "let é = \"à\";";
+    let index = LineIndex::new(content);
+
+    assert_eq!(
+      index.get_line_and_column(content, content.find('=').unwrap()),
+      (1, 7)
+    );
+    assert_eq!(
+      index.get_line_and_column(content, content.find(';').unwrap()),
+      (1, 12)
+    );
+  }
+
+  #[test]
+  fn clamps_the_offsets_past_the_end() {
+    let content = "ab\ncd";
+    let index = LineIndex::new(content);
+
+    assert_eq!(index.get_line_and_column(content, content.len()), (2, 3));
+    assert_eq!(index.get_line_and_column(content, 100), (2, 3));
+  }
+}
@@ -1,5 +1,8 @@
-mod strip_pragmas;
-pub use strip_pragmas::strip_pragmas;
-
 mod convert_line_endings;
 pub use convert_line_endings::*;
+
+mod stable_hash;
+pub use stable_hash::stable_hash;
+
+mod line_index;
+pub use line_index::LineIndex;
@@ -0,0 +1,53 @@
+/// Hashes the given parts into a 16 characters hexadecimal string. Unlike the
+/// hashers from the standard library the FNV-1a algorithm used here is
+/// guaranteed to stay the same between builds and Rust versions, so the names
+/// mangled with it are identical from one compilation to the other.
+pub fn stable_hash(parts: &[&str]) -> String {
+  const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+  const PRIME: u64 = 0x100000001b3;
+
+  let mut hash = OFFSET_BASIS;
+
+  for (i, part) in parts.iter().enumerate() {
+    // a separator between the parts, so ["ab", "c"] and ["a", "bc"] give
+    // different hashes.
+    if i > 0 {
+      hash ^= 0xff;
+      hash = hash.wrapping_mul(PRIME);
+    }
+
+    for byte in part.bytes() {
+      hash ^= byte as u64;
+      hash = hash.wrapping_mul(PRIME);
+    }
+  }
+
+  format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::stable_hash;
+
+  #[test]
+  fn matches_fnv1a() {
+    assert_eq!(stable_hash(&[]), "cbf29ce484222325");
+    assert_eq!(stable_hash(&[""]), "cbf29ce484222325");
+    assert_eq!(stable_hash(&["a"]), "af63dc4c8601ec8c");
+    assert_eq!(stable_hash(&["foobar"]), "85944171f73967e8");
+  }
+
+  #[test]
+  fn is_always_16_characters() {
+    for parts in [&["a"][..], &["lambda", "42"], &["x"; 10]] {
+      assert_eq!(stable_hash(parts).len(), 16);
+    }
+  }
+
+  #[test]
+  fn separates_the_parts() {
+    assert_ne!(stable_hash(&["ab", "c"]), stable_hash(&["a", "bc"]));
+    assert_ne!(stable_hash(&["abc"]), stable_hash(&["ab", "c"]));
+    assert_ne!(stable_hash(&["a", ""]), stable_hash(&["a"]));
+  }
+}
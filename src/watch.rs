@@ -0,0 +1,139 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::CompileError;
+use crate::preprocessor::get_wss_files_content_for_directory;
+use crate::ParseCache;
+
+/// How long the watcher waits between two scans of the project's files.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The content of every file the project depends on, indexed by the path of
+/// the files.
+type Snapshot = HashMap<PathBuf, String>;
+
+/// Compiles the project then keeps scanning its config, its source directory,
+/// the directories of its dependencies and the files they include, to compile
+/// it again every time one of them is added, removed or edited.
+///
+/// The compilations share a cache, so only the files whose content changed
+/// are parsed again. The config is read again when it changes, which empties
+/// the cache.
+pub fn watch(
+  config_path: &Path, config: Config, load_config: impl Fn() -> Result<Config, CompileError>,
+  compile: impl Fn(&Config, &mut ParseCache) -> Result<(), CompileError>
+) -> Result<(), CompileError> {
+  let mut config = config;
+  let mut cache = ParseCache::default();
+  let mut snapshot = take_snapshot(config_path, &config, &cache.included_files)?;
+
+  print_compilation_result(compile(&config, &mut cache));
+  add_new_files(&mut snapshot, &cache.included_files);
+
+  loop {
+    std::thread::sleep(POLL_INTERVAL);
+
+    // a file may be unreadable for a moment while an editor is saving it, the
+    // next scan will pick it up.
+    let Ok(mut new_snapshot) = take_snapshot(config_path, &config, &cache.included_files) else {
+      continue;
+    };
+
+    let changed_files = get_changed_files(&snapshot, &new_snapshot);
+
+    if changed_files.is_empty() {
+      continue;
+    }
+
+    for file in &changed_files {
+      println!("changed: {}", file.display());
+    }
+
+    if changed_files.contains(&config_path) {
+      match load_config() {
+        Ok(new_config) => config = new_config,
+        Err(error) => {
+          print_compilation_result(Err(error));
+          snapshot = new_snapshot;
+
+          continue;
+        }
+      };
+
+      // the directories and the names the files are emitted under may have
+      // changed along with the config
+      cache = ParseCache::default();
+
+      let Ok(snapshot_of_new_config) = take_snapshot(config_path, &config, &cache.included_files)
+      else {
+        continue;
+      };
+
+      new_snapshot = snapshot_of_new_config;
+    }
+
+    snapshot = new_snapshot;
+    print_compilation_result(compile(&config, &mut cache));
+    add_new_files(&mut snapshot, &cache.included_files);
+  }
+}
+
+fn take_snapshot(
+  config_path: &Path, config: &Config, included_files: &BTreeSet<PathBuf>
+) -> Result<Snapshot, CompileError> {
+  let mut snapshot = HashMap::new();
+  let directories = std::iter::once(&config.package.src).chain(config.dependencies.values());
+
+  for directory in directories {
+    for (filename, file) in get_wss_files_content_for_directory(Path::new(directory))? {
+      snapshot.insert(PathBuf::from(filename), file.content.into_inner());
+    }
+  }
+
+  // a config or an included file that is removed is a change like another,
+  // the compilation reports it.
+  add_new_files(&mut snapshot, std::iter::once(&config_path.to_path_buf()));
+  add_new_files(&mut snapshot, included_files);
+
+  Ok(snapshot)
+}
+
+/// Adds the files that are not in the snapshot yet, like the files that are
+/// included for the first time.
+fn add_new_files<'a>(snapshot: &mut Snapshot, paths: impl IntoIterator<Item = &'a PathBuf>) {
+  for path in paths {
+    if snapshot.contains_key(path) {
+      continue;
+    }
+
+    if let Ok(content) = std::fs::read_to_string(path) {
+      snapshot.insert(path.clone(), content);
+    }
+  }
+}
+
+fn get_changed_files<'a>(previous: &'a Snapshot, current: &'a Snapshot) -> Vec<&'a Path> {
+  let mut changed_files: Vec<&Path> = current
+    .iter()
+    .filter(|(path, content)| previous.get(*path) != Some(*content))
+    .map(|(path, _)| path.as_path())
+    .chain(
+      previous
+        .keys()
+        .filter(|path| !current.contains_key(*path))
+        .map(PathBuf::as_path)
+    )
+    .collect();
+
+  changed_files.sort();
+  changed_files
+}
+
+fn print_compilation_result(result: Result<(), CompileError>) {
+  match result {
+    Ok(_) => println!("compilation succeeded, watching for changes..."),
+    Err(error) => eprintln!("error: {error}\nwatching for changes...")
+  };
+}